@@ -1,8 +1,18 @@
 use crate::camera::Camera;
 use na::{Isometry3, Matrix4, Perspective3, Point3, Vector2, Vector3};
+use serde_derive::{Deserialize, Serialize};
 use std::f32;
 use window::{Event, MouseButton};
 
+/// The placement of an `ArcBall`, as saved in a session.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct CameraPose {
+    pub target: (f32, f32, f32),
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+}
+
 /// A camera that orbits around a point in space.
 pub struct ArcBall {
     target: Point3<f32>,
@@ -61,6 +71,24 @@ impl ArcBall {
     pub fn get_target(&self) -> (f32, f32, f32) {
         (self.target.x, self.target.y, self.target.z)
     }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            target: self.get_target(),
+            yaw: self.yaw,
+            pitch: self.pitch,
+            distance: self.distance,
+        }
+    }
+
+    pub fn set_pose(&mut self, pose: CameraPose) {
+        let (x, y, z) = pose.target;
+        self.target = Point3::new(x, y, z);
+        self.yaw = pose.yaw;
+        self.pitch = pose.pitch;
+        self.distance = pose.distance;
+        self.recalculate_matrices();
+    }
 }
 
 impl Camera for ArcBall {
@@ -1,7 +1,7 @@
 //! Contains various cameras and their projection matrices.
 mod arcball;
 
-pub use self::arcball::ArcBall;
+pub use self::arcball::{ArcBall, CameraPose};
 
 use na::Matrix4;
 use std::f32;
@@ -0,0 +1,196 @@
+//! The versioned container format for vector fields.
+//!
+//! Layout: the magic bytes, the format version as a little-endian `u32`, a bincode-encoded
+//! [`Header`] and finally the bincode-encoded [`Payload`], optionally gzip-compressed.
+//! Files without the magic bytes are read as a bare `VectorField`, as written by older versions.
+//!
+//! Version history:
+//! 1. A single field.
+//! 2. A sequence of frames with a time step.
+
+use super::LoadError;
+use crate::particles::{Affine, FieldSequence, Metadata, Vector4, VectorField};
+use flate2::read::GzDecoder;
+use serde_derive::{Deserialize, Serialize};
+use std::io::Read;
+
+const MAGIC: &[u8; 8] = b"BRNFIELD";
+const VERSION: u32 = 2;
+
+/// Compression applied to the payload.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Compression {
+    None,
+    Gzip,
+}
+
+/// Describes the field without having to read the payload.
+#[derive(Debug, Deserialize, Serialize)]
+struct Header {
+    width: usize,
+    height: usize,
+    depth: usize,
+    affine: Affine,
+    metadata: Metadata,
+    compression: Compression,
+    frame_count: usize,
+    time_step: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct HeaderV1 {
+    width: usize,
+    height: usize,
+    depth: usize,
+    affine: Affine,
+    metadata: Metadata,
+    compression: Compression,
+}
+
+impl From<HeaderV1> for Header {
+    fn from(header: HeaderV1) -> Self {
+        Header {
+            width: header.width,
+            height: header.height,
+            depth: header.depth,
+            affine: header.affine,
+            metadata: header.metadata,
+            compression: header.compression,
+            frame_count: 1,
+            time_step: 1.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct Payload {
+    frames: Vec<Vec<Vector4>>,
+    directional: Vec<(f32, f32, f32)>,
+}
+
+#[derive(Deserialize)]
+struct PayloadV1 {
+    vectors: Vec<Vector4>,
+    directional: Vec<(f32, f32, f32)>,
+}
+
+impl From<PayloadV1> for Payload {
+    fn from(payload: PayloadV1) -> Self {
+        Payload {
+            frames: vec![payload.vectors],
+            directional: payload.directional,
+        }
+    }
+}
+
+/// Reads a field sequence, either from a container or from a bare bincode `VectorField`.
+pub fn read(data: &[u8]) -> Result<FieldSequence, LoadError> {
+    if !data.starts_with(MAGIC) {
+        let field: VectorField =
+            bincode::deserialize(data).map_err(|e| format!("Failed to parse data: {}", e))?;
+        return Ok(field.into());
+    }
+
+    let mut version = [0u8; 4];
+    version.copy_from_slice(
+        data.get(MAGIC.len()..MAGIC.len() + 4)
+            .ok_or_else(|| "Truncated field file.".to_owned())?,
+    );
+    let version = u32::from_le_bytes(version);
+    if version == 0 {
+        return Err(LoadError::Format(
+            "Field file has the invalid version 0.".to_owned(),
+        ));
+    }
+    if version > VERSION {
+        return Err(LoadError::Format(format!(
+            "Field file version {} is newer than the supported version {}.",
+            version, VERSION
+        )));
+    }
+
+    let mut reader = &data[MAGIC.len() + 4..];
+    let header: Header = match version {
+        1 => bincode::deserialize_from::<_, HeaderV1>(&mut reader).map(Into::into),
+        _ => bincode::deserialize_from(&mut reader),
+    }
+    .map_err(|e| format!("Failed to parse field header: {}", e))?;
+
+    let reader: Box<dyn Read> = match header.compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(GzDecoder::new(reader)),
+    };
+    let payload: Payload = match version {
+        1 => bincode::deserialize_from::<_, PayloadV1>(reader).map(Into::into),
+        _ => bincode::deserialize_from(reader),
+    }
+    .map_err(|e| format!("Failed to parse field data: {}", e))?;
+
+    let count = header
+        .width
+        .checked_mul(header.height)
+        .and_then(|n| n.checked_mul(header.depth))
+        .ok_or_else(|| LoadError::Format("Field file dimensions are too large.".to_owned()))?;
+    if payload.frames.len() != header.frame_count {
+        return Err(LoadError::Format(format!(
+            "Field file has {} frames, expected {}.",
+            payload.frames.len(),
+            header.frame_count
+        )));
+    }
+    let mut frames = Vec::with_capacity(payload.frames.len());
+    for vectors in payload.frames {
+        if vectors.len() != count {
+            return Err(LoadError::DimensionMismatch {
+                expected: count,
+                found: vectors.len(),
+            });
+        }
+        let mut field = VectorField::new(header.width, header.height, header.depth, vectors);
+        field.set_affine(header.affine.clone());
+        frames.push(field);
+    }
+
+    let mut fields = FieldSequence::new(frames, header.time_step)?;
+    let first = fields.first_mut();
+    first.set_directional(payload.directional);
+    first.set_metadata(header.metadata);
+    Ok(fields)
+}
+
+/// Writes a field sequence as a container.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(fields: &FieldSequence, compression: Compression) -> Result<Vec<u8>, LoadError> {
+    let first = fields.first();
+    let (width, height, depth) = first.dimensions();
+    let header = Header {
+        width,
+        height,
+        depth,
+        affine: first.affine().clone(),
+        metadata: first.metadata().clone(),
+        compression,
+        frame_count: fields.frames().len(),
+        time_step: fields.time_step(),
+    };
+    let payload = Payload {
+        frames: fields.frames().iter().map(VectorField::data).collect(),
+        directional: first.directional().to_vec(),
+    };
+
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    bincode::serialize_into(&mut out, &header)
+        .map_err(|e| format!("Failed to write field header: {}", e))?;
+    match compression {
+        Compression::None => bincode::serialize_into(&mut out, &payload),
+        Compression::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(&mut out, flate2::Compression::default());
+            bincode::serialize_into(&mut encoder, &payload)
+                .and_then(|_| encoder.finish().map(|_| ()).map_err(Into::into))
+        }
+    }
+    .map_err(|e| format!("Failed to write field data: {}", e))?;
+    Ok(out)
+}
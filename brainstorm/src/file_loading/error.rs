@@ -0,0 +1,68 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+/// Everything that can go wrong when loading a field.
+#[derive(Debug)]
+pub enum LoadError {
+    /// A file or directory could not be read or written.
+    Io { path: PathBuf, error: io::Error },
+    /// A file needed for loading was not selected, or the browser did not hand it over.
+    MissingFile(String),
+    /// The data is malformed or uses an unsupported feature of its format.
+    Format(String),
+    /// The number of values does not match the dimensions of the field.
+    DimensionMismatch { expected: usize, found: usize },
+    /// A vector contains NaN or infinite components.
+    NonFinite {
+        frame: usize,
+        position: (usize, usize, usize),
+    },
+    /// A highly directional point lies outside the field.
+    OutOfBounds { point: (f32, f32, f32) },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io { path, error } => {
+                write!(f, "Failed to access {}: {}", path.display(), error)
+            }
+            LoadError::MissingFile(message) | LoadError::Format(message) => {
+                write!(f, "{}", message)
+            }
+            LoadError::DimensionMismatch { expected, found } => write!(
+                f,
+                "Dimension mismatch: expected {} values, found {}.",
+                expected, found
+            ),
+            LoadError::NonFinite {
+                frame,
+                position: (x, y, z),
+            } => write!(
+                f,
+                "Field has a non-finite vector at ({}, {}, {}) in frame {}.",
+                x, y, z, frame
+            ),
+            LoadError::OutOfBounds { point: (x, y, z) } => write!(
+                f,
+                "Directional point ({}, {}, {}) is outside the field.",
+                x, y, z
+            ),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Parsers report malformed data as plain messages.
+impl From<String> for LoadError {
+    fn from(message: String) -> Self {
+        LoadError::Format(message)
+    }
+}
@@ -0,0 +1,321 @@
+mod container;
+mod error;
+mod nrrd;
+mod samples;
+mod vtk;
+
+use crate::particles::FieldSequence;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::particles::{Mask, ScalarVolume};
+use crate::{FieldProvider, GPUFieldProvider, State};
+#[cfg(target_arch = "wasm32")]
+use std::path::PathBuf;
+#[cfg(target_arch = "wasm32")]
+use stdweb::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::container::{write as write_container, Compression};
+pub use self::error::LoadError;
+pub use self::nrrd::Header as NrrdHeader;
+
+#[allow(clippy::large_enum_variant)]
+pub enum FileResult {
+    OptionsFile(NrrdHeader),
+    VectorField((FieldProvider, GPUFieldProvider)),
+}
+
+/// The result of reading a file, before any providers are created.
+enum Loaded {
+    Header(NrrdHeader),
+    Field(FieldSequence),
+}
+
+pub fn reload_file(state: &State) -> Result<FileResult, LoadError> {
+    match read(state)? {
+        Loaded::Header(header) => Ok(FileResult::OptionsFile(header)),
+        Loaded::Field(field) => Ok(create_providers(field)),
+    }
+}
+
+/// Loads the fields of a file or directory that does not need a separately selected data file.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_fields(state: &State) -> Result<FieldSequence, LoadError> {
+    match read(state)? {
+        Loaded::Header(_) => Err(LoadError::MissingFile(
+            "No data file found for the header.".to_owned(),
+        )),
+        Loaded::Field(field) => Ok(field),
+    }
+}
+
+/// Reads and validates a field sequence from a container.
+pub fn read_container(data: &[u8]) -> Result<FieldSequence, LoadError> {
+    let fields = container::read(data)?;
+    validate(&fields)?;
+    Ok(fields)
+}
+
+fn read(state: &State) -> Result<Loaded, LoadError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if let Some(dir) = state.file_path.as_ref().filter(|p| p.is_dir()) {
+            return Ok(Loaded::Field(read_directory(dir)?));
+        }
+    }
+    let ext = get_ext(state)?;
+    let data = get_data(state)?;
+    let loaded = handle_file_ext(&ext, &data, state)?;
+    if let Loaded::Field(fields) = &loaded {
+        validate(fields)?;
+    }
+    Ok(loaded)
+}
+
+/// Checks that every frame has a vector per voxel, that all vectors are finite
+/// and that the highly directional points lie within the field.
+fn validate(fields: &FieldSequence) -> Result<(), LoadError> {
+    for (frame, field) in fields.frames().iter().enumerate() {
+        let (width, height, depth) = field.dimensions();
+        let count = field.vectors().count();
+        if count != width * height * depth {
+            return Err(LoadError::DimensionMismatch {
+                expected: width * height * depth,
+                found: count,
+            });
+        }
+        let non_finite = field.vectors().position(|&(x, y, z, w)| {
+            !(x.is_finite() && y.is_finite() && z.is_finite() && w.is_finite())
+        });
+        if let Some(index) = non_finite {
+            let position = (
+                index % width,
+                index / width % height,
+                index / (width * height),
+            );
+            return Err(LoadError::NonFinite { frame, position });
+        }
+    }
+
+    let field = fields.first();
+    let (width, height, depth) = field.dimensions();
+    let within = |value: f32, size: usize| value >= 0.0 && value <= size as f32;
+    for &point in field.directional() {
+        let (x, y, z) = point;
+        if !(within(x, width) && within(y, height) && within(z, depth)) {
+            return Err(LoadError::OutOfBounds { point });
+        }
+    }
+    Ok(())
+}
+
+/// Reads a label volume from a NRRD file, which must have the given dimensions.
+/// Every non-zero label is inside the mask.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_mask(
+    path: &std::path::Path,
+    dimensions: (usize, usize, usize),
+) -> Result<Mask, LoadError> {
+    let (width, height, depth) = dimensions;
+    let labels = read_scalars(path, dimensions)?;
+    Ok(Mask::from_labels(width, height, depth, &labels))
+}
+
+/// Reads a scalar volume from a NRRD file, which must have the given dimensions.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_scalar_volume(
+    path: &std::path::Path,
+    dimensions: (usize, usize, usize),
+) -> Result<ScalarVolume, LoadError> {
+    let (width, height, depth) = dimensions;
+    let values = read_scalars(path, dimensions)?;
+    Ok(ScalarVolume::new(width, height, depth, values))
+}
+
+/// Reads a NRRD file with a single value per voxel, checking that it has the given dimensions.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_scalars(
+    path: &std::path::Path,
+    dimensions: (usize, usize, usize),
+) -> Result<Vec<f32>, LoadError> {
+    let data = read_file(path)?;
+    let (header, offset) = NrrdHeader::parse(&data)?;
+    let samples = match &header.data_file {
+        Some(data_file) => {
+            let dir = path.parent().unwrap_or_else(|| std::path::Path::new(""));
+            header.decode(&read_file(&dir.join(data_file))?)?
+        }
+        None => header.decode(&data[offset..])?,
+    };
+
+    let (width, height, depth) = match header.sizes[..] {
+        [w, h, d] | [1, w, h, d] => (w, h, d),
+        _ => {
+            return Err(LoadError::Format(format!(
+                "Unsupported volume layout, expected a single value per voxel: {:?}",
+                header.sizes
+            )))
+        }
+    };
+    if (width, height, depth) != dimensions {
+        let (w, h, d) = dimensions;
+        return Err(LoadError::Format(format!(
+            "{} is {}x{}x{} voxels, but the field is {}x{}x{}.",
+            path.display(),
+            width,
+            height,
+            depth,
+            w,
+            h,
+            d
+        )));
+    }
+    Ok(samples)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_data(state: &State) -> Result<Vec<u8>, LoadError> {
+    let path = state
+        .file_path
+        .as_ref()
+        .ok_or_else(|| LoadError::MissingFile("No file path saved.".to_owned()))?;
+    read_file(path)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_file(path: &std::path::Path) -> Result<Vec<u8>, LoadError> {
+    std::fs::read(path).map_err(|error| LoadError::Io {
+        path: path.to_owned(),
+        error,
+    })
+}
+
+/// Reads every field file in a directory, in name order, as the frames of a sequence.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_directory(dir: &std::path::Path) -> Result<FieldSequence, LoadError> {
+    const FRAME_EXTENSIONS: [&str; 5] = ["bincode", "nrrd", "nhdr", "vtk", "vti"];
+
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .map_err(|error| LoadError::Io {
+            path: dir.to_owned(),
+            error,
+        })?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .filter(|ext| FRAME_EXTENSIONS.contains(ext))
+                .is_some()
+        })
+        .collect();
+    paths.sort();
+
+    let mut frames = Vec::new();
+    for path in paths {
+        let mut state = State::new();
+        state.file_path = Some(path.clone());
+        match read(&state)? {
+            Loaded::Field(fields) => frames.extend(fields.into_frames()),
+            Loaded::Header(_) => {
+                return Err(LoadError::MissingFile(format!(
+                    "No data file found for {}.",
+                    path.display()
+                )));
+            }
+        }
+    }
+    Ok(FieldSequence::new(frames, 1.0)?)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn get_data(_state: &State) -> Result<Vec<u8>, LoadError> {
+    // Get file extension
+    let content = js!(return getData();)
+        .into_string()
+        .ok_or_else(|| LoadError::MissingFile("Failed to get data from JS.".to_owned()))?;
+
+    let pos = content.find(",").map(|i| i + 1).unwrap_or(0);
+    let b64 = content.split_at(pos).1;
+    base64::decode(b64)
+        .map_err(|e| LoadError::Format(format!("Failed to decode base64 content: {}", e)))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn get_ext(state: &State) -> Result<String, LoadError> {
+    state
+        .file_path
+        .as_ref()
+        .ok_or_else(|| LoadError::MissingFile("No file path saved.".to_owned()))?
+        .extension()
+        .ok_or_else(|| LoadError::Format("No file extension.".to_owned()))
+        .map(|s| s.to_string_lossy().into_owned())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn get_ext(_state: &State) -> Result<String, LoadError> {
+    let path_str = js!(return getPath();)
+        .into_string()
+        .ok_or_else(|| LoadError::MissingFile("Failed to get path from JS.".to_owned()))?;
+    let mut path = PathBuf::new();
+    path.push(path_str);
+
+    path.extension()
+        .ok_or_else(|| LoadError::Format("No file extension.".to_owned()))
+        .map(|s| s.to_string_lossy().into_owned())
+}
+
+fn handle_file_ext(file_ext: &str, data: &[u8], state: &State) -> Result<Loaded, LoadError> {
+    match file_ext {
+        "bincode" => Ok(Loaded::Field(container::read(data)?)),
+        "nhdr" | "nrrd" => {
+            let (header, offset) = NrrdHeader::parse(data)?;
+            if file_ext == "nrrd" && header.data_file.is_none() {
+                return load_nrrd(&header, &data[offset..]);
+            }
+            match get_detached_data(&header, state) {
+                Some(detached) => load_nrrd(&header, &detached?),
+                None => Ok(Loaded::Header(header)),
+            }
+        }
+        "vtk" => Ok(Loaded::Field(vtk::load_legacy(data)?.into())),
+        "vti" => Ok(Loaded::Field(vtk::load_image_data(data)?.into())),
+        "raw" | "gz" => {
+            let header = state
+                .options_file
+                .as_ref()
+                .ok_or_else(|| LoadError::MissingFile("No options file loaded.".to_owned()))?;
+            load_nrrd(header, data)
+        }
+        _ => Err(LoadError::Format("Unknown file extension".to_owned())),
+    }
+}
+
+/// Reads the data file referenced by a detached header, relative to the header's location.
+#[cfg(not(target_arch = "wasm32"))]
+fn get_detached_data(header: &NrrdHeader, state: &State) -> Option<Result<Vec<u8>, LoadError>> {
+    let data_file = header.data_file.as_ref()?;
+    let path = state
+        .file_path
+        .as_ref()
+        .and_then(|p| p.parent())
+        .map(|dir| dir.join(data_file))
+        .unwrap_or_else(|| data_file.into());
+    Some(read_file(&path))
+}
+
+/// The browser only gives us access to the selected file, so the data file has to be
+/// picked separately.
+#[cfg(target_arch = "wasm32")]
+fn get_detached_data(_header: &NrrdHeader, _state: &State) -> Option<Result<Vec<u8>, LoadError>> {
+    None
+}
+
+fn load_nrrd(header: &NrrdHeader, data: &[u8]) -> Result<Loaded, LoadError> {
+    let samples = header.decode(data)?;
+    Ok(Loaded::Field(header.to_vector_field(&samples)?.into()))
+}
+
+fn create_providers(fields: FieldSequence) -> FileResult {
+    let gpu_field_provider = GPUFieldProvider::new(&fields);
+    let field_provider = FieldProvider::new(fields);
+    FileResult::VectorField((field_provider, gpu_field_provider))
+}
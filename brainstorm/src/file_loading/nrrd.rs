@@ -0,0 +1,380 @@
+//! A reader for the NRRD format, both with detached (`.nhdr`) and attached (`.nrrd`) data.
+//! Reference: http://teem.sourceforge.net/nrrd/format.html
+
+use super::samples::{read_samples, Endian, SampleType};
+use super::LoadError;
+use crate::particles::{tensor::SymmetricTensor, Affine, Metadata, VectorField};
+use flate2::read::GzDecoder;
+use std::io::Read;
+
+fn parse_type(value: &str) -> Result<SampleType, String> {
+    match value {
+        "signed char" | "int8" | "int8_t" => Ok(SampleType::Int8),
+        "uchar" | "unsigned char" | "uint8" | "uint8_t" => Ok(SampleType::UInt8),
+        "short" | "short int" | "signed short" | "signed short int" | "int16" | "int16_t" => {
+            Ok(SampleType::Int16)
+        }
+        "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" => {
+            Ok(SampleType::UInt16)
+        }
+        "int" | "signed int" | "int32" | "int32_t" => Ok(SampleType::Int32),
+        "uint" | "unsigned int" | "uint32" | "uint32_t" => Ok(SampleType::UInt32),
+        "longlong"
+        | "long long"
+        | "long long int"
+        | "signed long long"
+        | "signed long long int"
+        | "int64"
+        | "int64_t" => Ok(SampleType::Int64),
+        "ulonglong" | "unsigned long long" | "unsigned long long int" | "uint64" | "uint64_t" => {
+            Ok(SampleType::UInt64)
+        }
+        "float" => Ok(SampleType::Float),
+        "double" => Ok(SampleType::Double),
+        _ => Err(format!("Unsupported NRRD type: {}", value)),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Encoding {
+    Raw,
+    Gzip,
+    Ascii,
+}
+
+/// The parsed fields of a NRRD header.
+#[derive(Clone, Debug)]
+pub struct Header {
+    pub sample_type: SampleType,
+    pub sizes: Vec<usize>,
+    pub endian: Endian,
+    pub encoding: Encoding,
+    pub data_file: Option<String>,
+    pub byte_skip: isize,
+    pub line_skip: usize,
+    /// Mapping of the spatial axes to physical space, from `space directions`, `space origin`
+    /// or `spacings`. Unit spacing if none of these are present.
+    pub affine: Affine,
+    /// Unit of the physical coordinates, from `space units`.
+    pub units: Option<String>,
+}
+
+impl Header {
+    /// Parses a NRRD header from the start of `data`.
+    /// Returns the header along with the offset of the attached data, if any.
+    pub fn parse(data: &[u8]) -> Result<(Header, usize), LoadError> {
+        let mut sample_type = None;
+        let mut sizes = None;
+        let mut dimension = None;
+        let mut endian = Endian::Little;
+        let mut encoding = None;
+        let mut data_file = None;
+        let mut byte_skip = 0;
+        let mut line_skip = 0;
+        let mut directions = None;
+        let mut origin = None;
+        let mut spacings = None;
+        let mut units = None;
+
+        let mut offset = 0;
+        let mut first = true;
+        while offset < data.len() {
+            let end = data[offset..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map(|i| offset + i)
+                .unwrap_or_else(|| data.len());
+            let line = std::str::from_utf8(&data[offset..end])
+                .map_err(|e| format!("Parse error: {}", e))?
+                .trim_end_matches('\r');
+            offset = (end + 1).min(data.len());
+
+            if first {
+                if !line.starts_with("NRRD") {
+                    return Err(LoadError::Format(
+                        "Not a NRRD file: missing magic.".to_owned(),
+                    ));
+                }
+                first = false;
+                continue;
+            }
+
+            // An empty line separates the header from attached data.
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with('#') || line.contains(":=") {
+                continue;
+            }
+
+            let mut split = line.splitn(2, ':');
+            let field = split.next().unwrap_or("").trim().to_lowercase();
+            let value = split
+                .next()
+                .ok_or_else(|| format!("Malformed NRRD header line: {}", line))?
+                .trim();
+
+            match field.as_ref() {
+                "type" => sample_type = Some(parse_type(value)?),
+                "dimension" => dimension = Some(parse_number::<usize>(value)?),
+                "sizes" => {
+                    sizes = Some(
+                        value
+                            .split_whitespace()
+                            .map(parse_number)
+                            .collect::<Result<Vec<usize>, String>>()?,
+                    )
+                }
+                "endian" => {
+                    endian = match value {
+                        "little" => Endian::Little,
+                        "big" => Endian::Big,
+                        _ => {
+                            return Err(LoadError::Format(format!(
+                                "Unknown NRRD endian: {}",
+                                value
+                            )))
+                        }
+                    }
+                }
+                "encoding" => {
+                    encoding = Some(match value {
+                        "raw" => Encoding::Raw,
+                        "gzip" | "gz" => Encoding::Gzip,
+                        "ascii" | "text" | "txt" => Encoding::Ascii,
+                        _ => {
+                            return Err(LoadError::Format(format!(
+                                "Unsupported NRRD encoding: {}",
+                                value
+                            )))
+                        }
+                    })
+                }
+                "data file" | "datafile" => {
+                    if value.starts_with("LIST") || value.split_whitespace().count() > 1 {
+                        return Err(LoadError::Format(
+                            "Multi-file NRRD data is not supported.".to_owned(),
+                        ));
+                    }
+                    data_file = Some(value.to_owned());
+                }
+                "byte skip" | "byteskip" => byte_skip = parse_number(value)?,
+                "line skip" | "lineskip" => line_skip = parse_number(value)?,
+                "space directions" => {
+                    // Non-spatial axes, such as the vector components, are marked with "none".
+                    directions = Some(
+                        value
+                            .split_whitespace()
+                            .filter(|v| *v != "none")
+                            .map(parse_vector)
+                            .collect::<Result<Vec<_>, String>>()?,
+                    )
+                }
+                "space origin" => origin = Some(parse_vector(value)?),
+                "space units" => {
+                    units = value
+                        .split_whitespace()
+                        .next()
+                        .map(|unit| unit.trim_matches('"').to_owned())
+                }
+                "spacings" => {
+                    spacings = Some(
+                        value
+                            .split_whitespace()
+                            .filter(|v| !v.eq_ignore_ascii_case("nan"))
+                            .map(parse_number)
+                            .collect::<Result<Vec<f32>, String>>()?,
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        if first {
+            return Err(LoadError::Format("Empty NRRD header.".to_owned()));
+        }
+
+        let sizes = sizes.ok_or_else(|| "NRRD header is missing \"sizes\".".to_owned())?;
+        if let Some(dimension) = dimension {
+            if dimension != sizes.len() {
+                return Err(LoadError::Format(
+                    "NRRD \"dimension\" does not match \"sizes\".".to_owned(),
+                ));
+            }
+        }
+
+        let mut affine = Affine::default();
+        if let Some(directions) = directions {
+            match directions[..] {
+                [x, y, z] => affine.directions = [x, y, z],
+                _ => {
+                    return Err(LoadError::Format(
+                        "NRRD \"space directions\" must have 3 spatial axes.".to_owned(),
+                    ))
+                }
+            }
+        } else if let Some(spacings) = spacings {
+            match spacings[..] {
+                [x, y, z] => affine = Affine::from_spacing((x, y, z)),
+                _ => {
+                    return Err(LoadError::Format(
+                        "NRRD \"spacings\" must have 3 spatial axes.".to_owned(),
+                    ))
+                }
+            }
+        }
+        if let Some(origin) = origin {
+            affine.origin = origin;
+        }
+
+        let header = Header {
+            sample_type: sample_type
+                .ok_or_else(|| "NRRD header is missing \"type\".".to_owned())?,
+            sizes,
+            endian,
+            encoding: encoding.ok_or_else(|| "NRRD header is missing \"encoding\".".to_owned())?,
+            data_file,
+            byte_skip,
+            line_skip,
+            affine,
+            units,
+        };
+        Ok((header, offset))
+    }
+
+    /// The total number of samples described by the header.
+    pub fn sample_count(&self) -> usize {
+        self.sizes.iter().product()
+    }
+
+    /// Decodes the data belonging to this header into a flat list of samples,
+    /// with the fastest axis first.
+    pub fn decode(&self, data: &[u8]) -> Result<Vec<f32>, LoadError> {
+        let data = skip_lines(data, self.line_skip);
+        let count = self.sample_count();
+
+        if self.encoding == Encoding::Ascii {
+            let text = std::str::from_utf8(data).map_err(|e| format!("Parse error: {}", e))?;
+            let samples = text
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|s| !s.is_empty())
+                .take(count)
+                .map(parse_number)
+                .collect::<Result<Vec<f32>, String>>()?;
+            if samples.len() != count {
+                return Err(LoadError::DimensionMismatch {
+                    expected: count,
+                    found: samples.len(),
+                });
+            }
+            return Ok(samples);
+        }
+
+        let decompressed;
+        let data = match self.encoding {
+            Encoding::Gzip => {
+                let mut out = Vec::new();
+                GzDecoder::new(data)
+                    .read_to_end(&mut out)
+                    .map_err(|e| format!("Failed to decompress NRRD data: {}", e))?;
+                decompressed = out;
+                &decompressed[..]
+            }
+            _ => data,
+        };
+
+        let byte_count = count * self.sample_type.size();
+        let data = if self.byte_skip < 0 {
+            // Data is located at the end of the file.
+            if data.len() < byte_count {
+                return Err(LoadError::Format("Not enough NRRD data.".to_owned()));
+            }
+            &data[data.len() - byte_count..]
+        } else {
+            let skip = self.byte_skip as usize;
+            if data.len() < skip + byte_count {
+                return Err(LoadError::Format("Not enough NRRD data.".to_owned()));
+            }
+            &data[skip..skip + byte_count]
+        };
+
+        Ok(read_samples(self.sample_type, self.endian, data))
+    }
+
+    /// Converts decoded samples into a vector field.
+    /// The first axis is expected to hold the components of each voxel, which can be:
+    /// - 3 components: a vector, with its magnitude used as the fourth channel.
+    /// - 4 components: a vector and a scalar.
+    /// - 6 components: a symmetric diffusion tensor (`xx, xy, xz, yy, yz, zz`).
+    /// - 7 components: a confidence value followed by a diffusion tensor, as written by Teem.
+    pub fn to_vector_field(&self, samples: &[f32]) -> Result<VectorField, LoadError> {
+        let (components, width, height, depth) = match self.sizes[..] {
+            [c, w, h, d] if c == 3 || c == 4 || c == 6 || c == 7 => (c, w, h, d),
+            _ => {
+                return Err(LoadError::Format(format!(
+                    "Unsupported NRRD layout, expected 3, 4, 6 or 7 components per voxel: {:?}",
+                    self.sizes
+                )))
+            }
+        };
+
+        let chunks = samples.chunks_exact(components);
+        let mut field = match components {
+            3 => VectorField::from_vectors(width, height, depth, samples),
+            4 => {
+                let data = chunks.map(|c| (c[0], c[1], c[2], c[3])).collect();
+                VectorField::new(width, height, depth, data)
+            }
+            _ => {
+                let tensors: Vec<SymmetricTensor> = chunks
+                    .map(|c| match components {
+                        6 => [c[0], c[1], c[2], c[3], c[4], c[5]],
+                        // Voxels with low confidence are outside the scanned object.
+                        _ if c[0] < 0.5 => [0.0; 6],
+                        _ => [c[1], c[2], c[3], c[4], c[5], c[6]],
+                    })
+                    .collect();
+                VectorField::from_tensors(width, height, depth, &tensors)
+            }
+        };
+        field.set_affine(self.affine.clone());
+        field.set_metadata(Metadata {
+            units: self.units.clone(),
+            ..Metadata::default()
+        });
+        Ok(field)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid number in NRRD file: {}", value))
+}
+
+/// Parses a vector of the form `(x,y,z)`.
+fn parse_vector(value: &str) -> Result<(f32, f32, f32), String> {
+    let components = value
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map(parse_number)
+        .collect::<Result<Vec<f32>, String>>()?;
+    match components[..] {
+        [x, y, z] => Ok((x, y, z)),
+        _ => Err(format!("Expected a 3D vector in NRRD file: {}", value)),
+    }
+}
+
+fn skip_lines(data: &[u8], lines: usize) -> &[u8] {
+    let mut data = data;
+    for _ in 0..lines {
+        match data.iter().position(|&b| b == b'\n') {
+            Some(i) => data = &data[i + 1..],
+            None => return &[],
+        }
+    }
+    data
+}
@@ -0,0 +1,67 @@
+//! Binary sample types shared by the file formats.
+
+/// The data type of a single sample.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SampleType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float,
+    Double,
+}
+
+impl SampleType {
+    /// Size of a single sample in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            SampleType::Int8 | SampleType::UInt8 => 1,
+            SampleType::Int16 | SampleType::UInt16 => 2,
+            SampleType::Int32 | SampleType::UInt32 | SampleType::Float => 4,
+            SampleType::Int64 | SampleType::UInt64 | SampleType::Double => 8,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// Reads a single sample from exactly `sample_type.size()` bytes.
+pub fn read_sample(sample_type: SampleType, endian: Endian, b: &[u8]) -> f32 {
+    macro_rules! read {
+        ($t:ty, $n:expr) => {{
+            let mut bytes = [0u8; $n];
+            bytes.copy_from_slice(b);
+            match endian {
+                Endian::Little => <$t>::from_le_bytes(bytes),
+                Endian::Big => <$t>::from_be_bytes(bytes),
+            }
+        }};
+    }
+    match sample_type {
+        SampleType::Int8 => f32::from(b[0] as i8),
+        SampleType::UInt8 => f32::from(b[0]),
+        SampleType::Int16 => f32::from(read!(i16, 2)),
+        SampleType::UInt16 => f32::from(read!(u16, 2)),
+        SampleType::Int32 => read!(i32, 4) as f32,
+        SampleType::UInt32 => read!(u32, 4) as f32,
+        SampleType::Int64 => read!(i64, 8) as f32,
+        SampleType::UInt64 => read!(u64, 8) as f32,
+        SampleType::Float => read!(f32, 4),
+        SampleType::Double => read!(f64, 8) as f32,
+    }
+}
+
+/// Reads consecutive samples, ignoring any trailing partial sample.
+pub fn read_samples(sample_type: SampleType, endian: Endian, data: &[u8]) -> Vec<f32> {
+    data.chunks_exact(sample_type.size())
+        .map(|b| read_sample(sample_type, endian, b))
+        .collect()
+}
@@ -0,0 +1,588 @@
+//! Readers for VTK files with a 3-component vector array as point data:
+//! legacy `.vtk` files with `STRUCTURED_POINTS` and XML `.vti` ImageData files.
+//! Reference: https://vtk.org/wp-content/uploads/2015/04/file-formats.pdf
+
+use super::samples::{read_samples, Endian, SampleType};
+use super::LoadError;
+use crate::particles::{Affine, VectorField};
+
+/// Loads a legacy VTK file with a `STRUCTURED_POINTS` dataset, in ASCII or binary form.
+pub fn load_legacy(data: &[u8]) -> Result<VectorField, LoadError> {
+    let mut reader = Reader { data, pos: 0 };
+
+    let version = reader.line().unwrap_or("");
+    if !version.starts_with("# vtk DataFile") {
+        return Err(LoadError::Format(
+            "Not a VTK file: missing version line.".to_owned(),
+        ));
+    }
+    let _title = reader.line();
+    let binary = match reader.line().map(str::trim) {
+        Some("ASCII") => false,
+        Some("BINARY") => true,
+        _ => {
+            return Err(LoadError::Format(
+                "VTK file must be ASCII or BINARY.".to_owned(),
+            ))
+        }
+    };
+
+    let mut dimensions = None;
+    let mut origin = (0.0, 0.0, 0.0);
+    let mut spacing = (1.0, 1.0, 1.0);
+    let mut point_count = 0;
+    let mut count = 0;
+    let mut in_point_data = false;
+    let mut vectors = None;
+    let mut field_vectors = None;
+
+    while let Some(keyword) = reader.token() {
+        match keyword.to_uppercase().as_ref() {
+            "DATASET" => {
+                let kind = reader.expect_token()?;
+                if !kind.eq_ignore_ascii_case("STRUCTURED_POINTS") {
+                    return Err(LoadError::Format(format!(
+                        "Unsupported VTK dataset: {}",
+                        kind
+                    )));
+                }
+            }
+            "DIMENSIONS" => {
+                dimensions = Some((
+                    reader.number::<usize>()?,
+                    reader.number::<usize>()?,
+                    reader.number::<usize>()?,
+                ))
+            }
+            "ORIGIN" => origin = (reader.number()?, reader.number()?, reader.number()?),
+            "SPACING" | "ASPECT_RATIO" => {
+                spacing = (reader.number()?, reader.number()?, reader.number()?)
+            }
+            "POINT_DATA" => {
+                point_count = reader.number()?;
+                count = point_count;
+                in_point_data = true;
+            }
+            "CELL_DATA" => {
+                count = reader.number()?;
+                in_point_data = false;
+            }
+            "SCALARS" => {
+                let _name = reader.expect_token()?;
+                let sample_type = parse_type(reader.expect_token()?)?;
+                let mut next = reader.expect_token()?;
+                let mut components = 1;
+                if next != "LOOKUP_TABLE" {
+                    components = parse_number(next)?;
+                    next = reader.expect_token()?;
+                }
+                if next != "LOOKUP_TABLE" {
+                    return Err(LoadError::Format(
+                        "VTK SCALARS must be followed by LOOKUP_TABLE.".to_owned(),
+                    ));
+                }
+                let _table = reader.expect_token()?;
+                reader.values(binary, sample_type, product(&[count, components])?)?;
+            }
+            "VECTORS" | "NORMALS" => {
+                let _name = reader.expect_token()?;
+                let sample_type = parse_type(reader.expect_token()?)?;
+                let values = reader.values(binary, sample_type, product(&[count, 3])?)?;
+                if keyword.eq_ignore_ascii_case("VECTORS") && in_point_data && vectors.is_none() {
+                    vectors = Some(values);
+                }
+            }
+            "TENSORS" => {
+                let _name = reader.expect_token()?;
+                let sample_type = parse_type(reader.expect_token()?)?;
+                reader.values(binary, sample_type, product(&[count, 9])?)?;
+            }
+            "COLOR_SCALARS" => {
+                let _name = reader.expect_token()?;
+                let components: usize = reader.number()?;
+                let sample_type = if binary {
+                    SampleType::UInt8
+                } else {
+                    SampleType::Float
+                };
+                reader.values(binary, sample_type, product(&[count, components])?)?;
+            }
+            "TEXTURE_COORDINATES" => {
+                let _name = reader.expect_token()?;
+                let components: usize = reader.number()?;
+                let sample_type = parse_type(reader.expect_token()?)?;
+                reader.values(binary, sample_type, product(&[count, components])?)?;
+            }
+            "LOOKUP_TABLE" => {
+                let _name = reader.expect_token()?;
+                let size: usize = reader.number()?;
+                let sample_type = if binary {
+                    SampleType::UInt8
+                } else {
+                    SampleType::Float
+                };
+                reader.values(binary, sample_type, product(&[size, 4])?)?;
+            }
+            "FIELD" => {
+                let _name = reader.expect_token()?;
+                let arrays: usize = reader.number()?;
+                for _ in 0..arrays {
+                    let _name = reader.expect_token()?;
+                    let components: usize = reader.number()?;
+                    let tuples: usize = reader.number()?;
+                    let sample_type = parse_type(reader.expect_token()?)?;
+                    let values =
+                        reader.values(binary, sample_type, product(&[components, tuples])?)?;
+                    // Used if the file has no VECTORS attribute.
+                    if components == 3
+                        && in_point_data
+                        && tuples == point_count
+                        && field_vectors.is_none()
+                    {
+                        field_vectors = Some(values);
+                    }
+                }
+            }
+            "METADATA" => {
+                // Metadata ends with an empty line.
+                while let Some(line) = reader.line() {
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+            }
+            _ => {
+                return Err(LoadError::Format(format!(
+                    "Unexpected keyword in VTK file: {}",
+                    keyword
+                )))
+            }
+        }
+    }
+
+    let (width, height, depth) =
+        dimensions.ok_or_else(|| "VTK file is missing DIMENSIONS.".to_owned())?;
+    let vectors = vectors
+        .or(field_vectors)
+        .ok_or_else(|| "VTK file has no 3-component vector point data.".to_owned())?;
+    let expected = product(&[width, height, depth])?;
+    if point_count != expected {
+        return Err(LoadError::DimensionMismatch {
+            expected,
+            found: point_count,
+        });
+    }
+
+    let mut field = VectorField::from_vectors(width, height, depth, &vectors);
+    let mut affine = Affine::from_spacing(spacing);
+    affine.origin = origin;
+    field.set_affine(affine);
+    Ok(field)
+}
+
+/// Loads a VTK XML ImageData file, with ASCII, base64 or appended data.
+pub fn load_image_data(data: &[u8]) -> Result<VectorField, LoadError> {
+    // Appended raw data is not valid UTF-8, so only the part before it is treated as XML.
+    let (xml, appended) = match find(data, b"<AppendedData") {
+        Some(start) => {
+            let tag_end = data[start..]
+                .iter()
+                .position(|&b| b == b'>')
+                .map(|i| start + i + 1)
+                .ok_or_else(|| "Malformed AppendedData element.".to_owned())?;
+            let (tag, rest) = data[start..].split_at(tag_end - start);
+            // Data starts after the first underscore.
+            let underscore = rest
+                .iter()
+                .position(|&b| b == b'_')
+                .ok_or_else(|| "AppendedData is missing the leading underscore.".to_owned())?;
+            let encoding = Element::parse(&String::from_utf8_lossy(tag), "AppendedData")
+                .and_then(|e| e.attribute("encoding").map(str::to_owned))
+                .unwrap_or_else(|| "raw".to_owned());
+            (&data[..start], Some((encoding, &rest[underscore + 1..])))
+        }
+        None => (data, None),
+    };
+    let xml = std::str::from_utf8(xml).map_err(|e| format!("Parse error: {}", e))?;
+
+    let file = Element::find(xml, "VTKFile")
+        .ok_or_else(|| "Not a VTK XML file: missing VTKFile element.".to_owned())?;
+    if file.attribute("type") != Some("ImageData") {
+        return Err(LoadError::Format(
+            "Only VTK ImageData files are supported.".to_owned(),
+        ));
+    }
+    if file.attribute("compressor").is_some() {
+        return Err(LoadError::Format(
+            "Compressed VTK data is not supported.".to_owned(),
+        ));
+    }
+    let endian = match file.attribute("byte_order") {
+        Some("BigEndian") => Endian::Big,
+        _ => Endian::Little,
+    };
+    let header_type = match file.attribute("header_type") {
+        Some("UInt64") => SampleType::UInt64,
+        _ => SampleType::UInt32,
+    };
+
+    let image = Element::find(file.content, "ImageData")
+        .ok_or_else(|| "VTK file is missing the ImageData element.".to_owned())?;
+    let extent: Vec<isize> = parse_list(
+        image
+            .attribute("WholeExtent")
+            .ok_or_else(|| "ImageData is missing WholeExtent.".to_owned())?,
+    )?;
+    let origin: Vec<f32> = parse_list(image.attribute("Origin").unwrap_or("0 0 0"))?;
+    let spacing: Vec<f32> = parse_list(image.attribute("Spacing").unwrap_or("1 1 1"))?;
+    let direction: Vec<f32> =
+        parse_list(image.attribute("Direction").unwrap_or("1 0 0 0 1 0 0 0 1"))?;
+    if extent.len() != 6 || origin.len() != 3 || spacing.len() != 3 || direction.len() != 9 {
+        return Err(LoadError::Format(
+            "Malformed ImageData attributes.".to_owned(),
+        ));
+    }
+    // The number of points between the first and last index of an axis of the extent.
+    let size = |axis: usize| {
+        extent[2 * axis + 1]
+            .checked_sub(extent[2 * axis])
+            .filter(|&span| span >= 0)
+            .and_then(|span| (span as usize).checked_add(1))
+            .ok_or_else(|| "ImageData WholeExtent is reversed or too large.".to_owned())
+    };
+    let (width, height, depth) = (size(0)?, size(1)?, size(2)?);
+
+    if Element::find_all(image.content, "Piece").len() != 1 {
+        return Err(LoadError::Format(
+            "Only VTK files with a single piece are supported.".to_owned(),
+        ));
+    }
+    let point_data = Element::find(image.content, "PointData")
+        .ok_or_else(|| "VTK file has no point data.".to_owned())?;
+    let arrays = Element::find_all(point_data.content, "DataArray");
+    let array = point_data
+        .attribute("Vectors")
+        .and_then(|name| arrays.iter().find(|a| a.attribute("Name") == Some(name)))
+        .or_else(|| {
+            arrays
+                .iter()
+                .find(|a| a.attribute("NumberOfComponents") == Some("3"))
+        })
+        .ok_or_else(|| "VTK file has no 3-component vector point data.".to_owned())?;
+    if array.attribute("NumberOfComponents") != Some("3") {
+        return Err(LoadError::Format(
+            "The VTK vector array must have 3 components.".to_owned(),
+        ));
+    }
+
+    let sample_type = parse_type(
+        array
+            .attribute("type")
+            .ok_or_else(|| "DataArray is missing its type.".to_owned())?,
+    )?;
+    let values = match array.attribute("format").unwrap_or("ascii") {
+        "ascii" => parse_list(array.content)?,
+        "binary" => {
+            let bytes = decode_base64(array.content)?;
+            read_block(&bytes, header_type, sample_type, endian)?
+        }
+        "appended" => {
+            let offset: usize = parse_number(array.attribute("offset").unwrap_or("0"))?;
+            let (encoding, appended) =
+                appended.ok_or_else(|| "VTK file has no AppendedData.".to_owned())?;
+            let block = appended
+                .get(offset..)
+                .ok_or_else(|| "VTK appended data offset is out of range.".to_owned())?;
+            match encoding.as_ref() {
+                "raw" => read_block(block, header_type, sample_type, endian)?,
+                "base64" => {
+                    let bytes = decode_appended_base64(block, header_type, endian)?;
+                    read_block(&bytes, header_type, sample_type, endian)?
+                }
+                _ => {
+                    return Err(LoadError::Format(format!(
+                        "Unsupported VTK encoding: {}",
+                        encoding
+                    )))
+                }
+            }
+        }
+        format => {
+            return Err(LoadError::Format(format!(
+                "Unsupported VTK data format: {}",
+                format
+            )))
+        }
+    };
+
+    let expected = product(&[width, height, depth, 3])?;
+    if values.len() != expected {
+        return Err(LoadError::DimensionMismatch {
+            expected,
+            found: values.len(),
+        });
+    }
+
+    // The direction matrix is stored row by row, with one axis per column.
+    let axis = |i: usize| {
+        (
+            direction[i] * spacing[i],
+            direction[3 + i] * spacing[i],
+            direction[6 + i] * spacing[i],
+        )
+    };
+    let directions = [axis(0), axis(1), axis(2)];
+    // The origin refers to index 0, which is not necessarily the first point of the extent.
+    let mut origin = (origin[0], origin[1], origin[2]);
+    for (&(dx, dy, dz), &start) in directions.iter().zip(&[extent[0], extent[2], extent[4]]) {
+        origin.0 += dx * start as f32;
+        origin.1 += dy * start as f32;
+        origin.2 += dz * start as f32;
+    }
+    let affine = Affine { origin, directions };
+
+    let mut field = VectorField::from_vectors(width, height, depth, &values);
+    field.set_affine(affine);
+    Ok(field)
+}
+
+/// Reads a block of binary data, prefixed with its size in bytes.
+fn read_block(
+    data: &[u8],
+    header_type: SampleType,
+    sample_type: SampleType,
+    endian: Endian,
+) -> Result<Vec<f32>, String> {
+    let header_size = header_type.size();
+    let size = block_size(data, header_type, endian)?;
+    let block = header_size
+        .checked_add(size)
+        .and_then(|end| data.get(header_size..end))
+        .ok_or_else(|| "Not enough VTK data.".to_owned())?;
+    Ok(read_samples(sample_type, endian, block))
+}
+
+fn block_size(data: &[u8], header_type: SampleType, endian: Endian) -> Result<usize, String> {
+    let header = data
+        .get(..header_type.size())
+        .ok_or_else(|| "Not enough VTK data.".to_owned())?;
+    let mut bytes = [0u8; 8];
+    match endian {
+        Endian::Little => bytes[..header.len()].copy_from_slice(header),
+        Endian::Big => bytes[8 - header.len()..].copy_from_slice(header),
+    }
+    let size = match endian {
+        Endian::Little => u64::from_le_bytes(bytes),
+        Endian::Big => u64::from_be_bytes(bytes),
+    };
+    Ok(size as usize)
+}
+
+/// Decodes a base64 block in the appended section, where the header and data are encoded as a
+/// single stream and the end of the block is only known from the header.
+fn decode_appended_base64(
+    data: &[u8],
+    header_type: SampleType,
+    endian: Endian,
+) -> Result<Vec<u8>, String> {
+    // Every started group of 3 bytes is encoded as 4 characters.
+    let encoded_len = |bytes: usize| bytes.checked_add(2).and_then(|n| (n / 3).checked_mul(4));
+    let header_size = header_type.size();
+    let header = encoded_len(header_size)
+        .and_then(|len| data.get(..len))
+        .ok_or_else(|| "Not enough VTK data.".to_owned())?;
+    let size = block_size(&decode_base64_bytes(header)?, header_type, endian)?;
+    let block = header_size
+        .checked_add(size)
+        .and_then(encoded_len)
+        .and_then(|len| data.get(..len))
+        .ok_or_else(|| "Not enough VTK data.".to_owned())?;
+    decode_base64_bytes(block)
+}
+
+/// Multiplies sizes read from a file, which may be too large to multiply.
+fn product(factors: &[usize]) -> Result<usize, String> {
+    factors
+        .iter()
+        .try_fold(1usize, |product, &factor| product.checked_mul(factor))
+        .ok_or_else(|| "VTK dimensions are too large.".to_owned())
+}
+
+fn decode_base64(data: &str) -> Result<Vec<u8>, String> {
+    decode_base64_bytes(data.as_bytes())
+}
+
+fn decode_base64_bytes(data: &[u8]) -> Result<Vec<u8>, String> {
+    let data: Vec<u8> = data
+        .iter()
+        .cloned()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    base64::decode(&data).map_err(|e| format!("Failed to decode base64 content: {}", e))
+}
+
+fn parse_type(value: &str) -> Result<SampleType, String> {
+    match value.to_lowercase().as_ref() {
+        "char" | "signed_char" | "int8" => Ok(SampleType::Int8),
+        "unsigned_char" | "uint8" => Ok(SampleType::UInt8),
+        "short" | "int16" => Ok(SampleType::Int16),
+        "unsigned_short" | "uint16" => Ok(SampleType::UInt16),
+        "int" | "int32" => Ok(SampleType::Int32),
+        "unsigned_int" | "uint32" => Ok(SampleType::UInt32),
+        "long" | "vtktypeint64" | "int64" => Ok(SampleType::Int64),
+        "unsigned_long" | "vtktypeuint64" | "uint64" => Ok(SampleType::UInt64),
+        "float" | "float32" => Ok(SampleType::Float),
+        "double" | "float64" => Ok(SampleType::Double),
+        _ => Err(format!("Unsupported VTK type: {}", value)),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid number in VTK file: {}", value))
+}
+
+fn parse_list<T: std::str::FromStr>(value: &str) -> Result<Vec<T>, String> {
+    value.split_whitespace().map(parse_number).collect()
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Sequential reader for the legacy format, which mixes text and binary data.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Reads the rest of the current line.
+    fn line(&mut self) -> Option<&'a str> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let rest = &self.data[self.pos..];
+        let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        self.pos += (end + 1).min(rest.len());
+        std::str::from_utf8(&rest[..end])
+            .ok()
+            .map(|l| l.trim_end_matches('\r'))
+    }
+
+    /// Reads the next whitespace-separated token.
+    fn token(&mut self) -> Option<&'a str> {
+        while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return None;
+        }
+        std::str::from_utf8(&self.data[start..self.pos]).ok()
+    }
+
+    fn expect_token(&mut self) -> Result<&'a str, String> {
+        self.token()
+            .ok_or_else(|| "Unexpected end of VTK file.".to_owned())
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, String> {
+        parse_number(self.expect_token()?)
+    }
+
+    /// Reads `count` values. Binary data starts on the line after the keyword and is big-endian.
+    fn values(
+        &mut self,
+        binary: bool,
+        sample_type: SampleType,
+        count: usize,
+    ) -> Result<Vec<f32>, String> {
+        if !binary {
+            return (0..count).map(|_| self.number()).collect();
+        }
+        self.line();
+        let end = count
+            .checked_mul(sample_type.size())
+            .and_then(|size| self.pos.checked_add(size));
+        let bytes = end
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| "Not enough VTK data.".to_owned())?;
+        self.pos += bytes.len();
+        Ok(read_samples(sample_type, Endian::Big, bytes))
+    }
+}
+
+/// An XML element found by simple scanning, which is enough for the VTK layout.
+struct Element<'a> {
+    attributes: &'a str,
+    content: &'a str,
+}
+
+impl<'a> Element<'a> {
+    /// Parses an element starting at the beginning of `xml`.
+    fn parse(xml: &'a str, name: &str) -> Option<Self> {
+        let rest = xml.get(1 + name.len()..)?;
+        let tag_end = rest.find('>')?;
+        let self_closing = rest[..tag_end].ends_with('/');
+        let attributes = rest[..tag_end].trim_end_matches('/');
+        let body = &rest[tag_end + 1..];
+        let content = if self_closing {
+            ""
+        } else {
+            let close = format!("</{}>", name);
+            &body[..body.find(&close).unwrap_or(body.len())]
+        };
+        Some(Element {
+            attributes,
+            content,
+        })
+    }
+
+    /// Finds the first element with the given name.
+    fn find(xml: &'a str, name: &str) -> Option<Self> {
+        Element::find_all(xml, name).into_iter().next()
+    }
+
+    /// Finds all elements with the given name, in order.
+    fn find_all(xml: &'a str, name: &str) -> Vec<Self> {
+        let open = format!("<{}", name);
+        let mut elements = Vec::new();
+        let mut offset = 0;
+        while let Some(i) = xml[offset..].find(&open) {
+            let start = offset + i;
+            offset = start + open.len();
+            // Make sure we did not match a longer name, such as `PointDataArray`.
+            match xml[offset..].chars().next() {
+                Some(c) if c.is_whitespace() || c == '>' || c == '/' => {}
+                _ => continue,
+            }
+            if let Some(element) = Element::parse(&xml[start..], name) {
+                offset = start + open.len() + element.attributes.len();
+                elements.push(element);
+            }
+        }
+        elements
+    }
+
+    fn attribute(&self, key: &str) -> Option<&'a str> {
+        let attributes = self.attributes;
+        let pattern = format!("{}=\"", key);
+        let mut offset = 0;
+        while let Some(i) = attributes[offset..].find(&pattern) {
+            let start = offset + i;
+            offset = start + pattern.len();
+            if start > 0 && !attributes[..start].ends_with(char::is_whitespace) {
+                continue;
+            }
+            let end = attributes[offset..].find('"')?;
+            return Some(&attributes[offset..offset + end]);
+        }
+        None
+    }
+}
@@ -2,7 +2,7 @@ use na::Matrix4;
 use std::{cell::RefCell, rc::Rc};
 
 use crate::graphics::{position, position::WindowCorner, Drawable, Font, Rectangle};
-use crate::gui::{Label, UiElement};
+use crate::gui::{Label, Setting, UiElement};
 use crate::State;
 
 /// Vertical offset divisor for the label.
@@ -18,6 +18,8 @@ pub struct Button {
     color: (f32, f32, f32),
     color_toggled: (f32, f32, f32),
     func: Box<dyn FnMut(&mut State, bool)>,
+    /// Reads the toggle state from the state, for toggles of settings that can change elsewhere.
+    is_on: Option<Box<dyn Fn(&State) -> bool>>,
     label: Label<'static>,
 }
 
@@ -68,6 +70,7 @@ impl Button {
             pos_abs,
             pos_rel,
             func,
+            is_on: None,
             button_toggles,
             toggle_state: false,
             color,
@@ -82,6 +85,12 @@ impl Button {
     pub fn toggle_state(&self) -> bool {
         self.toggle_state
     }
+
+    /// Makes the toggle state follow the state with `is_on`.
+    pub fn synced(mut self, is_on: Box<dyn Fn(&State) -> bool>) -> Self {
+        self.is_on = Some(is_on);
+        self
+    }
 }
 
 impl UiElement for Button {
@@ -115,6 +124,36 @@ impl UiElement for Button {
         self.rect = Rectangle::new(self.pos_rel.get_coordinates(), color);
         self.label.resize(screensize);
     }
+
+    fn setting(&self) -> Option<Setting> {
+        if self.button_toggles {
+            Some(Setting::Toggle(self.toggle_state))
+        } else {
+            None
+        }
+    }
+
+    fn restore_setting(&mut self, setting: Setting) {
+        if let (true, Setting::Toggle(toggle_state)) = (self.button_toggles, setting) {
+            self.toggle_state = toggle_state;
+            let color = if toggle_state {
+                self.color
+            } else {
+                self.color_toggled
+            };
+            self.rect = Rectangle::new(self.pos_rel.get_coordinates(), color);
+        }
+    }
+
+    fn sync(&mut self, state: &State) {
+        let toggle_state = match &self.is_on {
+            Some(is_on) => is_on(state),
+            None => return,
+        };
+        if toggle_state != self.toggle_state {
+            self.restore_setting(Setting::Toggle(toggle_state));
+        }
+    }
 }
 
 impl Drawable for Button {
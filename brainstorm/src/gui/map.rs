@@ -2,6 +2,7 @@ use std::{rc::Rc, str};
 
 use crate::graphics::{position, Drawable, Rectangle};
 use crate::gui::UiElement;
+use crate::particles::WorldTransform;
 use crate::State;
 use gl_bindings::{shaders::OurShader, shaders::ShaderAttribute, Texture};
 use gl_bindings::{AbstractContext, Context};
@@ -15,6 +16,7 @@ pub struct Map {
     sectionzy: MapSection,
     shader: Rc<OurShader>,
     target: (f32, f32, f32),
+    transform: WorldTransform,
     clicked: bool,
     selected: i32,
 }
@@ -56,6 +58,7 @@ impl Map {
             sectionzy,
             shader,
             target: (0.0, 0.0, 0.0),
+            transform: WorldTransform::default(),
             clicked: false,
             selected: -1,
         }
@@ -75,10 +78,17 @@ impl Map {
         self.target
     }
 
+    /// Sets the transform used to map the world-space target onto the textures.
+    pub fn set_transform(&mut self, transform: WorldTransform) {
+        self.transform = transform;
+    }
+
+    /// Sets the target, in world space.
     pub fn set_target(&mut self, target: (f32, f32, f32)) {
-        self.sectionxy.set_target((target.0, target.1));
-        self.sectionxz.set_target((target.0, target.2));
-        self.sectionzy.set_target((target.1, target.2));
+        let (tx, ty, tz) = self.transform.to_texture(target);
+        self.sectionxy.set_target((tx - 0.5, ty - 0.5));
+        self.sectionxz.set_target((tx - 0.5, tz - 0.5));
+        self.sectionzy.set_target((tz - 0.5, ty - 0.5));
         self.target = target;
     }
 }
@@ -110,31 +120,36 @@ impl UiElement for Map {
 
     fn mouse_moved(&mut self, x: f64, y: f64, state: &mut State) {
         if self.clicked {
+            // Sections work in texture coordinates centered on 0.
+            let (mut tx, mut ty, mut tz) = self.transform.to_texture(self.target);
             if self.sectionxy.is_within(x, y) && self.selected == 0 {
                 self.sectionxy.mouse_moved(x, y, state);
 
                 let starget = self.sectionxy.get_target();
-                self.target.0 = starget.0;
-                self.target.1 = starget.1;
+                tx = starget.0 + 0.5;
+                ty = starget.1 + 0.5;
             }
 
             if self.sectionxz.is_within(x, y) && self.selected == 1 {
                 self.sectionxz.mouse_moved(x, y, state);
                 let starget = self.sectionxz.get_target();
-                self.target.0 = starget.0;
-                self.target.2 = starget.1;
+                tx = starget.0 + 0.5;
+                tz = starget.1 + 0.5;
             }
 
             if self.sectionzy.is_within(x, y) && self.selected == 2 {
                 self.sectionzy.mouse_moved(x, y, state);
                 let starget = self.sectionzy.get_target();
-                self.target.2 = starget.0;
-                self.target.1 = starget.1;
+                tz = starget.0 + 0.5;
+                ty = starget.1 + 0.5;
             }
+            self.target = self.transform.texture_to_world((tx, ty, tz));
         }
 
-        self.shader
-            .uniform1f("u_size", state.seeding_size * 0.6 + 0.01);
+        self.shader.uniform1f(
+            "u_size",
+            state.emitter().region().radius / self.transform.texture_scale(),
+        );
     }
 
     fn resize(&mut self, screensize: (f32, f32)) {
@@ -147,20 +162,18 @@ impl UiElement for Map {
 impl Drawable for Map {
     fn draw_transformed(&self, _view_matrix: &Matrix4<f32>) {
         Context::get_context().disable(Context::DEPTH_TEST);
+        let (tx, ty, tz) = self.transform.to_texture(self.target);
         self.shader.uniform3f("u_up", 0.0, 0.0, 1.0);
-        self.shader.uniform1f("u_progress", self.target.2 + 0.5);
-        self.shader
-            .uniform2f("u_test", self.target.0 + 0.5, self.target.1 + 0.5);
+        self.shader.uniform1f("u_progress", tz);
+        self.shader.uniform2f("u_test", tx, ty);
         self.sectionxy.draw();
         self.shader.uniform3f("u_up", 0.0, 1.0, 0.0);
-        self.shader.uniform1f("u_progress", self.target.1 + 0.5);
-        self.shader
-            .uniform2f("u_test", self.target.0 + 0.5, self.target.2 + 0.5);
+        self.shader.uniform1f("u_progress", ty);
+        self.shader.uniform2f("u_test", tx, tz);
         self.sectionxz.draw();
         self.shader.uniform3f("u_up", 1.0, 0.0, 0.0);
-        self.shader.uniform1f("u_progress", self.target.0 + 0.5);
-        self.shader
-            .uniform2f("u_test", self.target.2 + 0.5, self.target.1 + 0.5);
+        self.shader.uniform1f("u_progress", tx);
+        self.shader.uniform2f("u_test", tz, ty);
         self.sectionzy.draw();
     }
 }
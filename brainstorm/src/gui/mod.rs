@@ -4,14 +4,14 @@ mod button;
 mod label;
 mod map;
 mod model_bound;
+mod seeding_gizmo;
 mod slider;
 mod status_label;
 mod ui_definitions;
 mod ui_element;
-mod unit_sphere;
 mod world_points;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
 
 use crate::graphics::{Drawable, Font};
 use crate::State;
@@ -20,24 +20,33 @@ use resources::fonts;
 use window::{Event, Key, ModifierKeys, MouseButton};
 
 use self::{
-    button::Button, label::Label, map::Map, model_bound::ModelBound, slider::Slider,
-    status_label::StatusLabel, ui_element::UiElement, unit_sphere::UnitSphere,
-    world_points::WorldPoints,
+    button::Button, label::Label, map::Map, model_bound::ModelBound, seeding_gizmo::SeedingGizmo,
+    slider::Slider, status_label::StatusLabel, ui_element::UiElement, world_points::WorldPoints,
 };
 
+pub use self::ui_element::Setting;
+
+/// A UI element with the name its setting is saved under in sessions.
+type Named = (&'static str, Box<dyn ui_element::UiElement>);
+/// The functions in `ui_definitions` creating the elements.
+type Definition = fn((f32, f32), Rc<RefCell<Font<'static>>>) -> Box<dyn ui_element::UiElement>;
+
 /// Represents the GUI for the application.
 pub struct Gui {
-    pub seeding_sphere: UnitSphere,
+    pub seeding_gizmo: SeedingGizmo,
     pub model_bound: ModelBound,
     pub status: StatusLabel,
     pub ui_visible_button: Button,
     pub map: Map,
     pub world_points: WorldPoints,
     pub world_points_toggle: Button,
-    ui_elements: Vec<Box<dyn ui_element::UiElement>>,
-    ui_elements_cpu: Vec<Box<dyn ui_element::UiElement>>,
-    ui_elements_gpu: Vec<Box<dyn ui_element::UiElement>>,
+    pub timeline: Slider,
+    ui_elements: Vec<Named>,
+    ui_elements_cpu: Vec<Named>,
+    ui_elements_gpu: Vec<Named>,
+    ui_elements_timeline: Vec<Named>,
     show_cpu: bool,
+    show_timeline: bool,
 }
 
 impl Gui {
@@ -46,43 +55,98 @@ impl Gui {
         let map = ui_definitions::map(screensize);
         let font = Rc::from(RefCell::from(Font::from_bytes(fonts::DEFAULT)));
 
-        let ui_elements: Vec<Box<dyn ui_element::UiElement>> = vec![
-            ui_definitions::lowpass_filter(screensize, font.clone()),
-            ui_definitions::highpass_filter(screensize, font.clone()),
-            ui_definitions::speed_multiplier(screensize, font.clone()),
-            ui_definitions::seeding_size(screensize, font.clone()),
-            ui_definitions::mesh_transparency(screensize, font.clone()),
-            ui_definitions::load_file(screensize, font.clone()),
-            ui_definitions::credits_label(screensize, font.clone()),
-            ui_definitions::cpu_gpu_particles_toggle(screensize, font.clone()),
+        let named = |name, definition: Definition| -> Named {
+            (name, definition(screensize, font.clone()))
+        };
+
+        #[allow(unused_mut)]
+        let mut ui_elements: Vec<Named> = vec![
+            named("lowpass_filter", ui_definitions::lowpass_filter),
+            named("highpass_filter", ui_definitions::highpass_filter),
+            named("speed_multiplier", ui_definitions::speed_multiplier),
+            named("seeding_size", ui_definitions::seeding_size),
+            named("seeding_shape", ui_definitions::seeding_shape),
+            named("seeding_axis", ui_definitions::seeding_axis),
+            named("emitter_speed", ui_definitions::emitter_speed),
+            named("emitter_color", ui_definitions::emitter_color),
+            named("color_mode", ui_definitions::color_mode),
+            named("colormap", ui_definitions::colormap),
+            named("add_emitter", ui_definitions::add_emitter),
+            named("next_emitter", ui_definitions::next_emitter),
+            named("remove_emitter", ui_definitions::remove_emitter),
+            named("show_streamlines", ui_definitions::show_streamlines),
+            named("streamline_tubes", ui_definitions::streamline_tubes),
+            named("streamline_length", ui_definitions::streamline_length),
+            named("tractography", ui_definitions::tractography),
+            named(
+                "streamline_min_length",
+                ui_definitions::streamline_min_length,
+            ),
+            named("mesh_transparency", ui_definitions::mesh_transparency),
+            named("load_file", ui_definitions::load_file),
+            named("credits_label", ui_definitions::credits_label),
+            named(
+                "cpu_gpu_particles_toggle",
+                ui_definitions::cpu_gpu_particles_toggle,
+            ),
+            named("derived_quantity", ui_definitions::derived_quantity),
+            named("derived_level", ui_definitions::derived_level),
+            named("derived_surface", ui_definitions::derived_surface),
+            named("critical_points", ui_definitions::critical_points),
+            named("integrator", ui_definitions::integrator),
+            named("landmark_method", ui_definitions::landmark_method),
+            named("landmark_count", ui_definitions::landmark_count),
+            named("landmark_threshold", ui_definitions::landmark_threshold),
+        ];
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            ui_elements.push(named("save_session", ui_definitions::save_session));
+            ui_elements.push(named("load_session", ui_definitions::load_session));
+            ui_elements.push(named("load_mask", ui_definitions::load_mask));
+            ui_elements.push(named("mask_kills", ui_definitions::mask_kills));
+            ui_elements.push(named("load_overlay", ui_definitions::load_overlay));
+        }
+        let ui_elements_cpu: Vec<Named> = vec![
+            named("cpu_lifetime", ui_definitions::cpu_lifetime),
+            named("cpu_particle_size", ui_definitions::cpu_particle_size),
+            named(
+                "cpu_particle_spawn_rate",
+                ui_definitions::cpu_particle_spawn_rate,
+            ),
+            named("cpu_interpolation", ui_definitions::cpu_interpolation),
+            named("cpu_keep_empty_cells", ui_definitions::cpu_keep_empty_cells),
+            named("cpu_particle_count", ui_definitions::cpu_particle_count),
         ];
-        let ui_elements_cpu: Vec<Box<dyn ui_element::UiElement>> = vec![
-            ui_definitions::cpu_lifetime(screensize, font.clone()),
-            ui_definitions::cpu_particle_size(screensize, font.clone()),
-            ui_definitions::cpu_particle_spawn_rate(screensize, font.clone()),
+        let ui_elements_gpu: Vec<Named> =
+            vec![named("gpu_transparency", ui_definitions::gpu_transparency)];
+        let ui_elements_timeline: Vec<Named> = vec![
+            named("play", ui_definitions::play),
+            named("play_once", ui_definitions::play_once),
         ];
-        let ui_elements_gpu: Vec<Box<dyn ui_element::UiElement>> =
-            vec![ui_definitions::gpu_transparency(screensize, font.clone())];
+        let timeline = ui_definitions::timeline(screensize, font.clone());
 
         let ui_visible_button = ui_definitions::toggle_ui(screensize, font.clone());
         let status = ui_definitions::status_label(screensize, font.clone());
-        let seeding_sphere = UnitSphere::new((0.0, 0.0, 0.0), state.seeding_size);
+        let seeding_gizmo = SeedingGizmo::new();
         let model_bound = ModelBound::new();
         let world_points = ui_definitions::world_points(screensize, font.clone());
         let world_points_toggle = ui_definitions::toggle_world_points(screensize, font.clone());
 
         Gui {
             model_bound,
-            seeding_sphere,
+            seeding_gizmo,
             status,
             ui_elements,
             ui_visible_button,
             ui_elements_cpu,
             ui_elements_gpu,
+            ui_elements_timeline,
+            timeline,
             map,
             world_points,
             world_points_toggle,
             show_cpu: state.use_cpu_particles,
+            show_timeline: state.frame_count > 1,
         }
     }
 
@@ -90,12 +154,14 @@ impl Gui {
     /// Returns whether or not the event was "consumed".
     pub fn handle_event(&mut self, event: &Event, state: &mut State, size: (u32, u32)) -> bool {
         self.show_cpu = state.use_cpu_particles;
+        self.show_timeline = state.frame_count > 1;
         match event {
             Event::Resized(x, y) => {
                 self.ui_visible_button.resize((*x, *y));
                 self.world_points_toggle.resize((*x, *y));
                 self.status.resize((*x, *y));
                 self.map.resize((*x, *y));
+                self.timeline.resize((*x, *y));
                 for element in self.iter_ui_mut() {
                     element.resize((*x, *y));
                 }
@@ -147,6 +213,10 @@ impl Gui {
                     // TODO: Set camera position
                     state.camera_target = self.map.get_target();
                 }
+                if self.show_timeline {
+                    self.timeline
+                        .mouse_moved(state.mouse_x, state.mouse_y, state);
+                }
 
                 for element in self.iter_ui_mut() {
                     element.mouse_moved(state.mouse_x, state.mouse_y, state);
@@ -187,6 +257,12 @@ impl Gui {
                                 .click(state.mouse_x, state.mouse_y, state);
                             handled = true;
                         }
+                        if self.show_timeline
+                            && self.timeline.is_within(state.mouse_x, state.mouse_y)
+                        {
+                            self.timeline.click(state.mouse_x, state.mouse_y, state);
+                            handled = true;
+                        }
                         for element in self.iter_ui_mut() {
                             if element.is_within(state.mouse_x, state.mouse_y) {
                                 element.click(state.mouse_x, state.mouse_y, state);
@@ -200,6 +276,8 @@ impl Gui {
                         .click_release(state.mouse_x, state.mouse_y, state);
                     self.world_points_toggle
                         .click_release(state.mouse_x, state.mouse_y, state);
+                    self.timeline
+                        .click_release(state.mouse_x, state.mouse_y, state);
                     for element in self.iter_ui_mut() {
                         element.click_release(state.mouse_x, state.mouse_y, state);
                     }
@@ -210,11 +288,55 @@ impl Gui {
         }
     }
 
+    /// Returns the settings of all elements by their names, for saving a session.
+    pub fn settings(&mut self) -> BTreeMap<String, Setting> {
+        self.all_elements_mut()
+            .into_iter()
+            .filter_map(|(name, element)| Some((name.to_owned(), element.setting()?)))
+            .collect()
+    }
+
+    /// Restores settings returned by `settings`, without changing the application state.
+    /// Elements missing from `settings` keep theirs.
+    pub fn restore_settings(&mut self, settings: &BTreeMap<String, Setting>) {
+        for (name, element) in self.all_elements_mut() {
+            if let Some(setting) = settings.get(name) {
+                element.restore_setting(*setting);
+            }
+        }
+    }
+
+    /// Updates the elements showing settings that can change elsewhere, like the integrator
+    /// chosen on the command line or the settings of the selected emitter, to match the state.
+    pub fn sync(&mut self, state: &State) {
+        for (_, element) in self.all_elements_mut() {
+            element.sync(state);
+        }
+    }
+
+    /// Collects every element with its name, including the hidden ones.
+    fn all_elements_mut(&mut self) -> Vec<(&'static str, &mut dyn UiElement)> {
+        let mut elements: Vec<(&'static str, &mut dyn UiElement)> = vec![
+            ("toggle_ui", &mut self.ui_visible_button),
+            ("toggle_world_points", &mut self.world_points_toggle),
+            ("timeline", &mut self.timeline),
+        ];
+        elements.extend(
+            self.ui_elements
+                .iter_mut()
+                .chain(self.ui_elements_cpu.iter_mut())
+                .chain(self.ui_elements_gpu.iter_mut())
+                .chain(self.ui_elements_timeline.iter_mut())
+                .map(|(name, element)| (*name, element.as_mut() as &mut dyn UiElement)),
+        );
+        elements
+    }
+
     /// Draws the 3D elements of the UI
     pub fn draw_3d_elements(&self, view_matrix: &Matrix4<f32>) {
         if self.ui_visible_button.toggle_state() {
             self.model_bound.draw_transformed(view_matrix);
-            self.seeding_sphere.draw_transformed(view_matrix);
+            self.seeding_gizmo.draw_transformed(view_matrix);
         }
         if self.world_points_toggle.toggle_state() {
             self.world_points.draw_transformed(view_matrix);
@@ -222,29 +344,51 @@ impl Gui {
     }
 
     /// Creats a mutable iterator over the UI elements, including the GPU- or GPU-specific ones
-    /// depending on the cached setting.
+    /// depending on the cached setting, and the playback controls for time-varying fields.
     #[inline]
     fn iter_ui_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn ui_element::UiElement>> {
-        if self.show_cpu {
+        let timeline_count = if self.show_timeline {
+            self.ui_elements_timeline.len()
+        } else {
+            0
+        };
+        let timeline = self.ui_elements_timeline.iter_mut().take(timeline_count);
+        let elements = if self.show_cpu {
             self.ui_elements
                 .iter_mut()
                 .chain(self.ui_elements_cpu.iter_mut())
+                .chain(timeline)
         } else {
             self.ui_elements
                 .iter_mut()
                 .chain(self.ui_elements_gpu.iter_mut())
-        }
+                .chain(timeline)
+        };
+        elements.map(|(_, element)| element)
     }
 
     /// Creats a mutable iterator over the UI elements, including the GPU- or CPU-specific ones
-    /// depending on the cached setting.
+    /// depending on the cached setting, and the playback controls for time-varying fields.
     #[inline]
     fn iter_ui(&self) -> impl Iterator<Item = &Box<dyn ui_element::UiElement>> {
-        if self.show_cpu {
-            self.ui_elements.iter().chain(self.ui_elements_cpu.iter())
+        let timeline_count = if self.show_timeline {
+            self.ui_elements_timeline.len()
         } else {
-            self.ui_elements.iter().chain(self.ui_elements_gpu.iter())
-        }
+            0
+        };
+        let timeline = self.ui_elements_timeline.iter().take(timeline_count);
+        let elements = if self.show_cpu {
+            self.ui_elements
+                .iter()
+                .chain(self.ui_elements_cpu.iter())
+                .chain(timeline)
+        } else {
+            self.ui_elements
+                .iter()
+                .chain(self.ui_elements_gpu.iter())
+                .chain(timeline)
+        };
+        elements.map(|(_, element)| element)
     }
 }
 
@@ -256,6 +400,9 @@ impl Drawable for Gui {
 
         if self.ui_visible_button.toggle_state() {
             self.world_points_toggle.draw_transformed(view_matrix);
+            if self.show_timeline {
+                self.timeline.draw_transformed(view_matrix);
+            }
             for element in self.iter_ui() {
                 element.draw_transformed(view_matrix);
             }
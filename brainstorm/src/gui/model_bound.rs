@@ -15,6 +15,11 @@ impl ModelBound {
             bound: Cube::new((-0.5, -0.5, -0.5), (1.0, 1.0, 1.0), (1.0, 1.0, 1.0)),
         }
     }
+
+    /// Resizes the bound to the given world-space box.
+    pub fn set_bounds(&mut self, (x1, y1, z1): (f32, f32, f32), (x2, y2, z2): (f32, f32, f32)) {
+        self.bound = Cube::new((x1, y1, z1), (x2 - x1, y2 - y1, z2 - z1), (1.0, 1.0, 1.0));
+    }
 }
 
 impl UiElement for ModelBound {}
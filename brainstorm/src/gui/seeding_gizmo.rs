@@ -0,0 +1,199 @@
+use na::Matrix4;
+
+use crate::graphics::{render_target, DrawMode, Drawable};
+use crate::gui::UiElement;
+use crate::particles::{rake_offset, Emitter, SeedRegion, SeedShape, RAKE_SEEDS};
+use gl_bindings::{AbstractContext, Buffer, BufferType, Context};
+use std::f32;
+
+type Vector3 = (f32, f32, f32);
+
+/// Line segments in each circle.
+const CIRCLE_SEGMENTS: usize = 40;
+/// The colors of lines along, or circles around, the x, y and z axes.
+const AXIS_COLORS: [Vector3; 3] = [(1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, 1.0)];
+
+/// Outlines the regions where the emitters spawn particles. The selected emitter is colored by
+/// the axes, and the others by their own color. Nothing is drawn for the shapes covering the
+/// whole volume, which the model bound already shows.
+pub struct SeedingGizmo {
+    /// The regions outlined and their colors, with `None` for the selected emitter.
+    regions: Vec<(SeedRegion, Option<Vector3>)>,
+    vertices: Buffer<f32>,
+    indices: Buffer<u16>,
+}
+
+impl SeedingGizmo {
+    pub fn new() -> Self {
+        Self {
+            regions: Vec::new(),
+            vertices: Buffer::new(BufferType::Array),
+            indices: Buffer::new(BufferType::IndexArray),
+        }
+    }
+
+    /// Moves, resizes or reshapes the outlines, if any of the emitters have changed.
+    pub fn set_emitters(&mut self, emitters: &[Emitter], selected: usize) {
+        let regions: Vec<_> = emitters
+            .iter()
+            .enumerate()
+            .map(|(i, emitter)| {
+                (
+                    emitter.region(),
+                    Some(emitter.color).filter(|_| i != selected),
+                )
+            })
+            .collect();
+        if self.regions == regions {
+            return;
+        }
+
+        let mut lines = Lines::default();
+        for &(region, tint) in &regions {
+            outline(&mut lines, region, tint);
+        }
+        self.regions = regions;
+
+        self.vertices.set_data(&lines.vertices);
+        self.indices.set_data(&lines.indices);
+
+        self.vertices.bind();
+        let len = self.vertices.len();
+        self.vertices.upload_data(0, len, true);
+
+        self.indices.bind();
+        let len = self.indices.len();
+        self.indices.upload_data(0, len, true);
+    }
+}
+
+/// Adds the outline of a region, in a single color if `tint` is given.
+fn outline(lines: &mut Lines, region: SeedRegion, tint: Option<Vector3>) {
+    let colors = match tint {
+        Some(tint) => [tint; 3],
+        None => AXIS_COLORS,
+    };
+    let white = tint.unwrap_or((1.0, 1.0, 1.0));
+    let c = region.center;
+    let r = region.radius;
+    let axis = region.axis.index();
+    let (n, u, v) = region.axis.frame();
+    let normal_color = colors[axis];
+    match region.shape {
+        SeedShape::Sphere => {
+            for (i, &color) in colors.iter().enumerate() {
+                let (u, v) = (unit(i + 1), unit(i + 2));
+                lines.circle(c, u, v, r, color);
+            }
+        }
+        SeedShape::Box => {
+            // Each edge is colored by the axis it follows.
+            for (i, &color) in colors.iter().enumerate() {
+                let (a, b) = (unit(i + 1), unit(i + 2));
+                for &(sa, sb) in &[(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)] {
+                    let corner = offset(offset(c, a, sa * r), b, sb * r);
+                    let d = unit(i);
+                    lines.line(offset(corner, d, -r), offset(corner, d, r), color);
+                }
+            }
+        }
+        SeedShape::Plane => {
+            let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+            let corner = |(su, sv): (f32, f32)| offset(offset(c, u, su * r), v, sv * r);
+            for i in 0..corners.len() {
+                let (a, b) = (corners[i], corners[(i + 1) % corners.len()]);
+                lines.line(corner(a), corner(b), white);
+            }
+            lines.line(c, offset(c, n, r / 2.0), normal_color);
+        }
+        SeedShape::Rake => {
+            lines.line(offset(c, n, -r), offset(c, n, r), normal_color);
+            let tick = r / RAKE_SEEDS as f32;
+            for seed in 0..RAKE_SEEDS {
+                let position = offset(c, n, rake_offset(seed) * r);
+                lines.line(
+                    offset(position, u, -tick),
+                    offset(position, u, tick),
+                    normal_color,
+                );
+                lines.line(
+                    offset(position, v, -tick),
+                    offset(position, v, tick),
+                    normal_color,
+                );
+            }
+        }
+        SeedShape::Disk => {
+            lines.circle(c, u, v, r, white);
+            lines.line(c, offset(c, n, r / 2.0), normal_color);
+        }
+        SeedShape::Volume | SeedShape::MagnitudeWeighted | SeedShape::ChannelWeighted => {}
+    }
+}
+
+impl UiElement for SeedingGizmo {}
+
+impl Drawable for SeedingGizmo {
+    fn draw_transformed(&self, view_matrix: &Matrix4<f32>) {
+        if self.indices.is_empty() {
+            return;
+        }
+        Context::get_context().enable(Context::DEPTH_TEST);
+        render_target::draw_indices(
+            DrawMode::LINES,
+            &self.vertices,
+            &self.indices,
+            &self.render_states(),
+            view_matrix,
+        );
+        Context::get_context().disable(Context::DEPTH_TEST);
+    }
+}
+
+/// Vertex and index data of line segments.
+#[derive(Default)]
+struct Lines {
+    vertices: Vec<f32>,
+    indices: Vec<u16>,
+}
+
+impl Lines {
+    fn vertex(&mut self, (x, y, z): Vector3, (r, g, b): Vector3) -> u16 {
+        let index = (self.vertices.len() / 8) as u16;
+        self.vertices
+            .extend_from_slice(&[x, y, z, r, g, b, x / 2.0 + 0.5, y / 2.0 + 0.5]);
+        index
+    }
+
+    fn line(&mut self, from: Vector3, to: Vector3, color: Vector3) {
+        let a = self.vertex(from, color);
+        let b = self.vertex(to, color);
+        self.indices.extend_from_slice(&[a, b]);
+    }
+
+    /// Adds a circle in the plane spanned by the unit vectors `u` and `v`.
+    fn circle(&mut self, center: Vector3, u: Vector3, v: Vector3, radius: f32, color: Vector3) {
+        let first = self.vertices.len() / 8;
+        for i in 0..CIRCLE_SEGMENTS {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * f32::consts::PI * 2.0;
+            let position = offset(center, u, radius * angle.cos());
+            self.vertex(offset(position, v, radius * angle.sin()), color);
+            let next = (i + 1) % CIRCLE_SEGMENTS;
+            self.indices
+                .extend_from_slice(&[(first + i) as u16, (first + next) as u16]);
+        }
+    }
+}
+
+/// The unit vector along an axis, wrapping around after z.
+fn unit(axis: usize) -> Vector3 {
+    match axis % 3 {
+        0 => (1.0, 0.0, 0.0),
+        1 => (0.0, 1.0, 0.0),
+        _ => (0.0, 0.0, 1.0),
+    }
+}
+
+fn offset((x, y, z): Vector3, (dx, dy, dz): Vector3, t: f32) -> Vector3 {
+    (x + dx * t, y + dy * t, z + dz * t)
+}
@@ -2,7 +2,7 @@ use na::Matrix4;
 use std::{cell::RefCell, rc::Rc};
 
 use crate::graphics::{position, position::WindowCorner, Drawable, Font, Rectangle};
-use crate::gui::{Label, UiElement};
+use crate::gui::{Label, Setting, UiElement};
 use crate::State;
 
 /// Vertical offset factor for the label.
@@ -21,6 +21,8 @@ pub struct Slider {
     rect_track: Rectangle,
     rect_slider: Rectangle,
     func: Box<dyn FnMut(&mut State, f32)>,
+    /// Gives the value showing the state, for sliders whose setting can change elsewhere.
+    value_of: Option<Box<dyn Fn(&State) -> f32>>,
     label: Label<'static>,
 }
 
@@ -89,6 +91,7 @@ impl Slider {
             track_pos,
             slider_pos,
             func,
+            value_of: None,
             cached_screensize: screensize,
             is_clicked: false,
             value: initial_value,
@@ -116,7 +119,23 @@ impl Slider {
         let offset = (c.x2 - c.x1) / self.steps;
         let fraction = (x as f32 - c.x1) / (c.x2 - c.x1) + offset;
         self.value = (fraction * self.steps) as u32 as f32 / self.steps;
+        self.update_slider_rect();
+    }
+
+    /// Makes the slider follow the state with `value_of`, which is the inverse of its function.
+    pub fn synced(mut self, value_of: Box<dyn Fn(&State) -> f32>) -> Self {
+        self.value_of = Some(value_of);
+        self
+    }
 
+    /// Moves the slider to `value`, between 0.0 and 1.0, without calling its function.
+    pub fn set_value(&mut self, value: f32) {
+        self.value = value.clamp(0.0, 1.0);
+        self.update_slider_rect();
+    }
+
+    /// Moves the visible slider to match the current value.
+    fn update_slider_rect(&mut self) {
         // Calculate new slider position
         let mut margin = self.pos_abs.margin_horizontal - self.slider_pos.width / 2;
         let m = (self.pos_abs.width as f32 * self.value) as u32;
@@ -175,6 +194,27 @@ impl UiElement for Slider {
         );
         self.label.resize(screensize);
     }
+
+    fn setting(&self) -> Option<Setting> {
+        Some(Setting::Value(self.value))
+    }
+
+    fn restore_setting(&mut self, setting: Setting) {
+        if let Setting::Value(value) = setting {
+            self.set_value(value);
+        }
+    }
+
+    fn sync(&mut self, state: &State) {
+        let value = match &self.value_of {
+            Some(value_of) => value_of(state),
+            None => return,
+        };
+        // Only move for changes of at least half a step, so that dragging is not undone.
+        if (value - self.value).abs() * self.steps >= 0.5 {
+            self.set_value(value);
+        }
+    }
 }
 
 impl Drawable for Slider {
@@ -10,6 +10,10 @@ use stdweb::*;
 
 use super::{Button, Label, Map, Slider, StatusLabel, UiElement, WorldPoints};
 use crate::graphics::{position, Font};
+use crate::particles::{
+    EmptyCells, COLORMAPS, COLOR_MODES, EMITTER_COLORS, INTEGRATORS, INTERPOLATION_MODES,
+    LANDMARK_METHODS, QUANTITIES, SEED_AXES, SEED_SHAPES,
+};
 
 /// A slider acting as a low-pass filter.
 pub fn lowpass_filter(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Box<dyn UiElement> {
@@ -53,7 +57,7 @@ pub fn highpass_filter(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>)
     ))
 }
 
-/// A slider controlling the particle speeds.
+/// A slider controlling the overall particle speed, which also sets the playback speed.
 pub fn speed_multiplier(
     screensize: (f32, f32),
     font: Rc<RefCell<Font<'static>>>,
@@ -77,29 +81,594 @@ pub fn speed_multiplier(
     ))
 }
 
-/// A slider controlling the size of the seeding area.
+/// A slider controlling the size of the seeding area of the selected emitter.
 pub fn seeding_size(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Box<dyn UiElement> {
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 225,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 120,
+            margin_horizontal: 285,
+        },
+        80,
+        1.0,
+        screensize,
+        Box::new(|ref mut context, value| {
+            context.emitter_mut().size = value;
+        }),
+        "Seeding size".to_owned(),
+        font,
+    );
+    Box::new(slider.synced(Box::new(|state| state.emitter().size)))
+}
+
+/// A slider choosing the shape of the seeding area of the selected emitter: a sphere, box,
+/// plane, rake or disk, the whole volume, or the volume weighted by the velocity or the
+/// fourth channel.
+pub fn seeding_shape(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    let steps = SEED_SHAPES.len() - 1;
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 225,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 440,
+            margin_horizontal: 285,
+        },
+        steps as u32,
+        0.0,
+        screensize,
+        Box::new(move |ref mut context, value| {
+            context.emitter_mut().shape = SEED_SHAPES[(value * steps as f32).round() as usize];
+        }),
+        "Seeding shape".to_owned(),
+        font,
+    );
+    Box::new(slider.synced(Box::new(move |state| {
+        let index = SEED_SHAPES.iter().position(|&s| s == state.emitter().shape);
+        index.unwrap_or(0) as f32 / steps as f32
+    })))
+}
+
+/// A slider choosing the axis of the seeding rake of the selected emitter, and the normal of
+/// its seeding plane and disk.
+pub fn seeding_axis(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    let steps = SEED_AXES.len() - 1;
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 100,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 360,
+            margin_horizontal: 285,
+        },
+        steps as u32,
+        1.0,
+        screensize,
+        Box::new(move |ref mut context, value| {
+            context.emitter_mut().axis = SEED_AXES[(value * steps as f32).round() as usize];
+        }),
+        "Seeding axis".to_owned(),
+        font,
+    );
+    Box::new(slider.synced(Box::new(move |state| {
+        state.emitter().axis.index() as f32 / steps as f32
+    })))
+}
+
+/// A slider controlling the lifetime of the particles of the selected emitter.
+pub fn cpu_lifetime(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Box<dyn UiElement> {
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 225,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 200,
+            margin_horizontal: 40,
+        },
+        80,
+        0.2,
+        screensize,
+        Box::new(|ref mut context, value| {
+            context.emitter_mut().lifetime = value * 500.0;
+        }),
+        "Lifetime".to_owned(),
+        font,
+    );
+    Box::new(slider.synced(Box::new(|state| state.emitter().lifetime / 500.0)))
+}
+
+/// A slider controlling the transparency of the marching cubes mesh.
+pub fn mesh_transparency(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    Box::new(Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 225,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 200,
+            margin_horizontal: 285,
+        },
+        50,
+        0.02,
+        screensize,
+        Box::new(|ref mut context, value| {
+            context.mesh_transparency = value;
+        }),
+        "Mesh transparency".to_owned(),
+        font,
+    ))
+}
+
+/// A slider controlling the size of the rendered particles on the CPU.
+pub fn cpu_particle_size(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    Box::new(Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 225,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 280,
+            margin_horizontal: 40,
+        },
+        20,
+        0.5,
+        screensize,
+        Box::new(|ref mut context, value| {
+            context.particle_size = value * 16.0;
+        }),
+        "Particle size".to_owned(),
+        font,
+    ))
+}
+
+/// A slider controlling the spawn speed of the CPU particles of the selected emitter.
+pub fn cpu_particle_spawn_rate(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 225,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 280,
+            margin_horizontal: 285,
+        },
+        50,
+        0.5,
+        screensize,
+        Box::new(|ref mut context, value| {
+            context.emitter_mut().spawn_rate = (value * 2000.0) as u32;
+        }),
+        "Particle spawn rate".to_owned(),
+        font,
+    );
+    Box::new(slider.synced(Box::new(|state| state.emitter().spawn_rate as f32 / 2000.0)))
+}
+
+/// A slider controlling the speed of the particles of the selected emitter, relative to the
+/// overall speed.
+pub fn emitter_speed(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 520,
+            margin_horizontal: 180,
+        },
+        20,
+        0.5,
+        screensize,
+        Box::new(|ref mut context, value| {
+            context.emitter_mut().speed = value * 2.0;
+        }),
+        "Emitter speed".to_owned(),
+        font,
+    );
+    Box::new(slider.synced(Box::new(|state| state.emitter().speed / 2.0)))
+}
+
+/// A slider choosing the color of the particles of the selected emitter.
+pub fn emitter_color(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    let steps = EMITTER_COLORS.len() - 1;
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 225,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 600,
+            margin_horizontal: 40,
+        },
+        steps as u32,
+        0.0,
+        screensize,
+        Box::new(move |ref mut context, value| {
+            context.emitter_mut().color = EMITTER_COLORS[(value * steps as f32).round() as usize];
+        }),
+        "Emitter color".to_owned(),
+        font,
+    );
+    Box::new(slider.synced(Box::new(move |state| {
+        let index = EMITTER_COLORS
+            .iter()
+            .position(|&c| c == state.emitter().color);
+        index.unwrap_or(0) as f32 / steps as f32
+    })))
+}
+
+/// A button adding an emitter, which starts as a copy of the selected one.
+pub fn add_emitter(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 360,
+            margin_horizontal: 40,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        false,
+        Box::new(|ref mut context, _toggle_state| context.add_emitter()),
+        "   Add emitter".to_owned(),
+        font,
+    ))
+}
+
+/// A button selecting the next emitter, and moving the camera target to it.
+pub fn next_emitter(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 360,
+            margin_horizontal: 180,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        false,
+        Box::new(|ref mut context, _toggle_state| context.select_next_emitter()),
+        "  Next emitter".to_owned(),
+        font,
+    ))
+}
+
+/// A button deleting the selected emitter, unless it is the only one.
+pub fn remove_emitter(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 360,
+            margin_horizontal: 320,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        false,
+        Box::new(|ref mut context, _toggle_state| context.remove_emitter()),
+        " Delete emitter".to_owned(),
+        font,
+    ))
+}
+
+/// A slider choosing what the particle colors show: the direction, the speed, the age,
+/// the emitter or the overlay.
+pub fn color_mode(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Box<dyn UiElement> {
+    let steps = COLOR_MODES.len() - 1;
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 225,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 680,
+            margin_horizontal: 285,
+        },
+        steps as u32,
+        0.0,
+        screensize,
+        Box::new(move |ref mut context, value| {
+            context.color_mode = COLOR_MODES[(value * steps as f32).round() as usize];
+        }),
+        "Color by".to_owned(),
+        font,
+    );
+    Box::new(slider.synced(Box::new(move |state| {
+        let index = COLOR_MODES.iter().position(|&m| m == state.color_mode);
+        index.unwrap_or(0) as f32 / steps as f32
+    })))
+}
+
+/// A slider choosing the colormap of the speed, age and overlay color modes.
+pub fn colormap(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Box<dyn UiElement> {
+    let steps = COLORMAPS.len() - 1;
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 225,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 680,
+            margin_horizontal: 40,
+        },
+        steps as u32,
+        0.0,
+        screensize,
+        Box::new(move |ref mut context, value| {
+            context.colormap = COLORMAPS[(value * steps as f32).round() as usize];
+        }),
+        "Colormap".to_owned(),
+        font,
+    );
+    Box::new(slider.synced(Box::new(move |state| {
+        let index = COLORMAPS.iter().position(|&c| c == state.colormap);
+        index.unwrap_or(0) as f32 / steps as f32
+    })))
+}
+
+/// A button toggling whether static streamlines are traced from the seeding regions and shown.
+pub fn show_streamlines(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    let button = Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 440,
+            margin_horizontal: 40,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        true,
+        Box::new(|ref mut context, toggle_state| context.show_streamlines = toggle_state),
+        "  Streamlines".to_owned(),
+        font,
+    );
+    Box::new(button.synced(Box::new(|state| state.show_streamlines)))
+}
+
+/// A button toggling whether streamlines are drawn as tubes instead of lines.
+pub fn streamline_tubes(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    let button = Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 440,
+            margin_horizontal: 180,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        true,
+        Box::new(|ref mut context, toggle_state| context.streamlines.tubes = toggle_state),
+        "       Tubes".to_owned(),
+        font,
+    );
+    Box::new(button.synced(Box::new(|state| state.streamlines.tubes)))
+}
+
+/// A button toggling whether the streamlines track fibers, following the axis of the field
+/// whatever the sign of its vectors.
+pub fn tractography(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    let button = Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 40,
+            margin_horizontal: 180,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        true,
+        Box::new(|ref mut context, toggle_state| {
+            context.streamlines.tractography = toggle_state;
+        }),
+        "  Tractography".to_owned(),
+        font,
+    );
+    Box::new(button.synced(Box::new(|state| state.streamlines.tractography)))
+}
+
+/// A slider controlling the longest a streamline gets, from 0.1 to 2.1 world units.
+pub fn streamline_length(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 150,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 440,
+            margin_horizontal: 320,
+        },
+        20,
+        0.2,
+        screensize,
+        Box::new(|ref mut context, value| {
+            context.streamlines.max_length = 0.1 + value * 2.0;
+        }),
+        "Streamline length".to_owned(),
+        font,
+    );
+    Box::new(slider.synced(Box::new(|state| (state.streamlines.max_length - 0.1) / 2.0)))
+}
+
+/// A slider controlling the shortest streamline or fiber kept, from 0 to 1 world unit.
+pub fn streamline_min_length(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 150,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 40,
+            margin_horizontal: 320,
+        },
+        20,
+        0.0,
+        screensize,
+        Box::new(|ref mut context, value| {
+            context.streamlines.min_length = value;
+        }),
+        "Min length".to_owned(),
+        font,
+    );
+    Box::new(slider.synced(Box::new(|state| state.streamlines.min_length)))
+}
+
+/// A slider choosing how the CPU particles interpolate the field: nearest, trilinear,
+/// Catmull-Rom or monotone cubic.
+pub fn cpu_interpolation(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    let steps = INTERPOLATION_MODES.len() - 1;
     Box::new(Slider::new(
         position::Absolute {
             height: 40,
             width: 225,
             anchor: position::WindowCorner::BotRight,
-            margin_vertical: 120,
-            margin_horizontal: 285,
+            margin_vertical: 360,
+            margin_horizontal: 40,
+        },
+        steps as u32,
+        1.0 / steps as f32,
+        screensize,
+        Box::new(move |ref mut context, value| {
+            context.interpolation = INTERPOLATION_MODES[(value * steps as f32).round() as usize];
+        }),
+        "Interpolation".to_owned(),
+        font,
+    ))
+}
+
+/// A button toggling whether CPU particles survive in cells where every voxel is zero.
+pub fn cpu_keep_empty_cells(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 360,
+            margin_horizontal: 390,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        true,
+        Box::new(|ref mut context, toggle_state| {
+            context.empty_cells = if toggle_state {
+                EmptyCells::Keep
+            } else {
+                EmptyCells::Kill
+            };
+        }),
+        "    Keep empty".to_owned(),
+        font,
+    ))
+}
+
+/// A slider controlling the number of CPU particles, from 10 000 to 1 000 000 on a
+/// logarithmic scale.
+pub fn cpu_particle_count(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 225,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 520,
+            margin_horizontal: 40,
+        },
+        20,
+        0.5,
+        screensize,
+        Box::new(|ref mut context, value| {
+            let count = 10f32.powf(4.0 + 2.0 * value) / 1000.0;
+            context.particle_count = count.round() as usize * 1000;
+        }),
+        "Particle count".to_owned(),
+        font,
+    );
+    // Follows counts set on the command line or by a session.
+    Box::new(slider.synced(Box::new(|state| {
+        ((state.particle_count.max(1) as f32).log10() - 4.0) / 2.0
+    })))
+}
+
+/// A slider choosing how particles are advanced: Euler, midpoint, RK4 or adaptive RK45,
+/// which the GPU particles replace with RK4.
+pub fn integrator(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Box<dyn UiElement> {
+    let steps = INTEGRATORS.len() - 1;
+    let slider = Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 225,
+            anchor: position::WindowCorner::BotRight,
+            margin_vertical: 440,
+            margin_horizontal: 40,
         },
-        80,
-        1.0,
+        steps as u32,
+        0.0,
         screensize,
-        Box::new(|ref mut context, value| {
-            context.seeding_size = value;
+        Box::new(move |ref mut context, value| {
+            context.integrator = INTEGRATORS[(value * steps as f32).round() as usize];
         }),
-        "Seeding size".to_owned(),
+        "Integrator".to_owned(),
         font,
-    ))
+    );
+    Box::new(slider.synced(Box::new(move |state| {
+        let index = INTEGRATORS.iter().position(|&i| i == state.integrator);
+        index.unwrap_or(0) as f32 / steps as f32
+    })))
 }
 
-/// A slider controlling the lifetime of particles on the CPU.
-pub fn cpu_lifetime(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Box<dyn UiElement> {
+/// A slider controlling the particle transparency on the GPU.
+pub fn gpu_transparency(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
     Box::new(Slider::new(
         position::Absolute {
             height: 40,
@@ -108,91 +677,201 @@ pub fn cpu_lifetime(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) ->
             margin_vertical: 200,
             margin_horizontal: 40,
         },
-        80,
+        100,
         0.2,
         screensize,
         Box::new(|ref mut context, value| {
-            context.lifetime = value * 500.0;
+            context.particle_transparency = value;
         }),
-        "Lifetime".to_owned(),
+        "Particle transparency".to_owned(),
         font,
     ))
 }
 
-/// A slider controlling the transparency of the marching cubes mesh.
-pub fn mesh_transparency(
+/// A slider controlling the current time of a time-varying field.
+pub fn timeline(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Slider {
+    Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 300,
+            anchor: position::WindowCorner::TopLeft,
+            margin_vertical: 60,
+            margin_horizontal: 40,
+        },
+        200,
+        0.0,
+        screensize,
+        Box::new(|ref mut context, value| {
+            context.time = value * (context.frame_count - 1) as f32;
+        }),
+        "Time".to_owned(),
+        font,
+    )
+}
+
+/// A button toggling playback of a time-varying field.
+pub fn play(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::TopLeft,
+            margin_vertical: 130,
+            margin_horizontal: 40,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        true,
+        Box::new(|ref mut context, toggle_state| context.playing = toggle_state),
+        "    Play/pause".to_owned(),
+        font,
+    ))
+}
+
+/// A button toggling whether playback stops at the end instead of starting over.
+pub fn play_once(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::TopLeft,
+            margin_vertical: 130,
+            margin_horizontal: 180,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        true,
+        Box::new(|ref mut context, toggle_state| context.loop_playback = !toggle_state),
+        "   Play once".to_owned(),
+        font,
+    ))
+}
+
+/// A slider choosing a quantity derived from the field to color by, with the first step
+/// showing the field itself.
+pub fn derived_quantity(
     screensize: (f32, f32),
     font: Rc<RefCell<Font<'static>>>,
 ) -> Box<dyn UiElement> {
+    let steps = QUANTITIES.len();
     Box::new(Slider::new(
         position::Absolute {
             height: 40,
-            width: 225,
-            anchor: position::WindowCorner::BotRight,
-            margin_vertical: 200,
-            margin_horizontal: 285,
+            width: 300,
+            anchor: position::WindowCorner::TopLeft,
+            margin_vertical: 240,
+            margin_horizontal: 40,
         },
-        50,
-        0.02,
+        steps as u32,
+        0.0,
         screensize,
-        Box::new(|ref mut context, value| {
-            context.mesh_transparency = value;
+        Box::new(move |ref mut context, value| {
+            let step = (value * steps as f32).round() as usize;
+            context.derived = step.checked_sub(1).map(|i| QUANTITIES[i]);
         }),
-        "Mesh transparency".to_owned(),
+        "Derived quantity".to_owned(),
         font,
     ))
 }
 
-/// A slider controlling the size of the rendered particles on the CPU.
-pub fn cpu_particle_size(
+/// A slider controlling the level of the derived isosurface, as a fraction of the value range.
+pub fn derived_level(
     screensize: (f32, f32),
     font: Rc<RefCell<Font<'static>>>,
 ) -> Box<dyn UiElement> {
     Box::new(Slider::new(
         position::Absolute {
             height: 40,
-            width: 225,
-            anchor: position::WindowCorner::BotRight,
-            margin_vertical: 280,
+            width: 300,
+            anchor: position::WindowCorner::TopLeft,
+            margin_vertical: 340,
             margin_horizontal: 40,
         },
-        20,
+        100,
         0.5,
         screensize,
         Box::new(|ref mut context, value| {
-            context.particle_size = value * 16.0;
+            context.derived_level = value;
         }),
-        "Particle size".to_owned(),
+        "Isosurface level".to_owned(),
         font,
     ))
 }
 
-/// A slider controlling the spawn speed of particles on the CPU.
-pub fn cpu_particle_spawn_rate(
+/// A button toggling whether the mesh shows an isosurface of the derived quantity.
+pub fn derived_surface(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::TopLeft,
+            margin_vertical: 410,
+            margin_horizontal: 40,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        true,
+        Box::new(|ref mut context, toggle_state| context.derived_surface = toggle_state),
+        "     Isosurface".to_owned(),
+        font,
+    ))
+}
+
+/// A slider choosing how the landmarks shown as world points are found.
+pub fn landmark_method(
     screensize: (f32, f32),
     font: Rc<RefCell<Font<'static>>>,
 ) -> Box<dyn UiElement> {
+    let steps = LANDMARK_METHODS.len() - 1;
     Box::new(Slider::new(
         position::Absolute {
             height: 40,
             width: 225,
-            anchor: position::WindowCorner::BotRight,
-            margin_vertical: 280,
-            margin_horizontal: 285,
+            anchor: position::WindowCorner::TopLeft,
+            margin_vertical: 240,
+            margin_horizontal: 380,
         },
-        50,
-        0.5,
+        steps as u32,
+        0.0,
+        screensize,
+        Box::new(move |ref mut context, value| {
+            context.landmarks.method = LANDMARK_METHODS[(value * steps as f32).round() as usize];
+        }),
+        "Landmarks".to_owned(),
+        font,
+    ))
+}
+
+/// A slider controlling how many landmarks are shown, from 1 to 50.
+pub fn landmark_count(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    Box::new(Slider::new(
+        position::Absolute {
+            height: 40,
+            width: 225,
+            anchor: position::WindowCorner::TopLeft,
+            margin_vertical: 340,
+            margin_horizontal: 380,
+        },
+        49,
+        9.0 / 49.0,
         screensize,
         Box::new(|ref mut context, value| {
-            context.particle_respawn_per_tick = (value * 2000.0) as u32;
+            context.landmarks.count = 1 + (value * 49.0).round() as usize;
         }),
-        "Particle spawn rate".to_owned(),
+        "Landmark count".to_owned(),
         font,
     ))
 }
 
-/// A slider controlling the particle transparency on the GPU.
-pub fn gpu_transparency(
+/// A slider controlling the fraction of the largest value below which voxels are not
+/// considered for landmarks.
+pub fn landmark_threshold(
     screensize: (f32, f32),
     font: Rc<RefCell<Font<'static>>>,
 ) -> Box<dyn UiElement> {
@@ -200,17 +879,17 @@ pub fn gpu_transparency(
         position::Absolute {
             height: 40,
             width: 225,
-            anchor: position::WindowCorner::BotRight,
-            margin_vertical: 200,
-            margin_horizontal: 40,
+            anchor: position::WindowCorner::TopLeft,
+            margin_vertical: 440,
+            margin_horizontal: 380,
         },
         100,
-        0.2,
+        0.5,
         screensize,
         Box::new(|ref mut context, value| {
-            context.particle_transparency = value;
+            context.landmarks.threshold = value;
         }),
-        "Particle transparency".to_owned(),
+        "Landmark threshold".to_owned(),
         font,
     ))
 }
@@ -244,6 +923,133 @@ pub fn load_file(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Bo
     ))
 }
 
+/// A button letting the user save the current session.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_session(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 200,
+            margin_horizontal: 180,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        false,
+        Box::new(|ref mut context, _toggle_state| {
+            if let Ok(nfd::Response::Okay(path)) = nfd::open_save_dialog(Some("session"), None) {
+                context.save_session = Some(PathBuf::from(path));
+            }
+        }),
+        "   Save session".to_owned(),
+        font,
+    ))
+}
+
+/// A button letting the user restore a saved session.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_session(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 280,
+            margin_horizontal: 180,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        false,
+        Box::new(|ref mut context, _toggle_state| {
+            if let Ok(nfd::Response::Okay(path)) = nfd::open_file_dialog(Some("session"), None) {
+                context.load_session = Some(PathBuf::from(path));
+            }
+        }),
+        "   Load session".to_owned(),
+        font,
+    ))
+}
+
+/// A button letting the user load a mask restricting where particles spawn.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_mask(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 200,
+            margin_horizontal: 320,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        false,
+        Box::new(|ref mut context, _toggle_state| {
+            if let Ok(nfd::Response::Okay(path)) = nfd::open_file_dialog(None, None) {
+                context.mask_file = Some(PathBuf::from(path));
+                context.reload_mask = true;
+            }
+        }),
+        "      Load mask".to_owned(),
+        font,
+    ))
+}
+
+/// A button letting the user load a scalar volume coloring the particles, mesh and map.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_overlay(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 120,
+            margin_horizontal: 320,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        false,
+        Box::new(|ref mut context, _toggle_state| {
+            if let Ok(nfd::Response::Okay(path)) = nfd::open_file_dialog(None, None) {
+                context.overlay_file = Some(PathBuf::from(path));
+                context.reload_overlay = true;
+            }
+        }),
+        "   Load overlay".to_owned(),
+        font,
+    ))
+}
+
+/// A button toggling whether particles die when they leave the mask.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn mask_kills(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 280,
+            margin_horizontal: 320,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        true,
+        Box::new(|ref mut context, toggle_state| context.mask_kills = toggle_state),
+        "  Stay in mask".to_owned(),
+        font,
+    ))
+}
+
 /// A button toggling the UI visibility.
 pub fn toggle_ui(screensize: (f32, f32), font: Rc<RefCell<Font<'static>>>) -> Button {
     Button::new(
@@ -335,6 +1141,28 @@ pub fn toggle_world_points(screensize: (f32, f32), font: Rc<RefCell<Font<'static
     )
 }
 
+/// A button toggling whether the critical points of the field are shown.
+pub fn critical_points(
+    screensize: (f32, f32),
+    font: Rc<RefCell<Font<'static>>>,
+) -> Box<dyn UiElement> {
+    Box::new(Button::new(
+        position::Absolute {
+            height: 40,
+            width: 120,
+            anchor: position::WindowCorner::BotLeft,
+            margin_vertical: 120,
+            margin_horizontal: 180,
+        },
+        (0.44, 0.5, 0.56),
+        screensize,
+        true,
+        Box::new(|ref mut context, toggle_state| context.show_critical_points = toggle_state),
+        " Critical points".to_owned(),
+        font,
+    ))
+}
+
 pub fn map(screensize: (f32, f32)) -> Map {
     Map::new(
         position::Absolute {
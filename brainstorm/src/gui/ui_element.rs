@@ -1,5 +1,13 @@
 use crate::graphics::Drawable;
 use crate::State;
+use serde_derive::{Deserialize, Serialize};
+
+/// The user-adjustable setting of an element, as saved in a session.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum Setting {
+    Value(f32),
+    Toggle(bool),
+}
 
 /// Defines the basic requirements of a UI element,
 /// with default behaviour of not doing anything.
@@ -20,4 +28,15 @@ pub trait UiElement: Drawable {
 
     /// Update element to match new screen size
     fn resize(&mut self, _screensize: (f32, f32)) {}
+
+    /// Returns the setting of the element, if it has one worth saving.
+    fn setting(&self) -> Option<Setting> {
+        None
+    }
+
+    /// Restores a setting returned by `setting`, without calling the element's function.
+    fn restore_setting(&mut self, _setting: Setting) {}
+
+    /// Updates the element to show the state, for settings that can change elsewhere.
+    fn sync(&mut self, _state: &State) {}
 }
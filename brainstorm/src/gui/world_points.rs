@@ -2,6 +2,7 @@ use std::{cell::RefCell, rc::Rc};
 
 use crate::graphics::{Circle, Drawable, Font, Text};
 use crate::gui::UiElement;
+use crate::particles::CriticalPoint;
 use crate::State;
 use gl_bindings::{AbstractContext, Context};
 use na::{Isometry3, Matrix4, Point3, Translation3, Vector3, Vector4};
@@ -12,6 +13,8 @@ struct WorldPoint {
     text: Text<'static>,
     dot: Circle,
     hovered: bool,
+    /// Whether the point is a critical point of the field, rather than one of the markers.
+    critical: bool,
 }
 
 /// A simple button that can be pressed.
@@ -38,45 +41,42 @@ impl WorldPoints {
     }
 
     pub fn set_points(&mut self, points: Vec<(f32, f32, f32)>) {
-        self.points.clear();
-
-        self.points.push(WorldPoint {
-            text: Text::new(
-                "Center".to_owned(),
-                self.font.clone(),
-                0.0,
-                0.0 + 0.03,
-                0.0,
-                self.screensize,
-            ),
-            dot: Circle::new(
-                0.0,
-                0.0,
-                0.0,
-                0.005,
-                f32::consts::PI / 2.0,
-                (0.0, 1.0, 0.0),
-                true,
-            ),
+        self.points.retain(|point| point.critical);
+
+        let marker = self.point("Center".to_owned(), (0.0, 0.0, 0.0), (0.0, 1.0, 0.0));
+        self.points.push(marker);
+
+        for (id, point) in points.into_iter().enumerate() {
+            let marker = self.point(format!("point {}", id + 1), point, (0.0, 1.0, 0.0));
+            self.points.push(marker);
+        }
+    }
+
+    /// Shows critical points of the field, labeled and colored by their kind,
+    /// replacing the ones shown before.
+    pub fn set_critical_points(&mut self, points: &[CriticalPoint]) {
+        self.points.retain(|point| !point.critical);
+
+        for (i, point) in points.iter().enumerate() {
+            let number = points[..i].iter().filter(|p| p.kind == point.kind).count() + 1;
+            let label = format!("{} {}", point.kind.name(), number);
+            let mut marker = self.point(label, point.position, point.kind.color());
+            marker.critical = true;
+            self.points.push(marker);
+        }
+    }
+
+    fn point(
+        &self,
+        label: String,
+        (x, y, z): (f32, f32, f32),
+        color: (f32, f32, f32),
+    ) -> WorldPoint {
+        WorldPoint {
+            text: Text::new(label, self.font.clone(), x, y + 0.03, z, self.screensize),
+            dot: Circle::new(x, y, z, 0.005, f32::consts::PI / 2.0, color, true),
             hovered: false,
-        });
-
-        let mut id = 1;
-        for point in points {
-            let (x, y, z) = point;
-            self.points.push(WorldPoint {
-                text: Text::new(
-                    format!("point {}", id).to_owned(),
-                    self.font.clone(),
-                    x,
-                    y + 0.03,
-                    z,
-                    self.screensize,
-                ),
-                dot: Circle::new(x, y, z, 0.005, f32::consts::PI / 2.0, (0.0, 1.0, 0.0), true),
-                hovered: false,
-            });
-            id += 1;
+            critical: false,
         }
     }
 
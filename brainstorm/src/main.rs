@@ -3,6 +3,8 @@ mod file_loading;
 mod graphics;
 mod gui;
 mod particles;
+#[cfg(not(target_arch = "wasm32"))]
+mod session;
 mod state;
 
 pub use crate::state::State;
@@ -12,12 +14,16 @@ use crate::{
     graphics::Drawable,
     gui::Gui,
     particles::{
-        fieldprovider::FieldProvider, gpu_fieldprovider::GPUFieldProvider,
-        gpu_particles::GPUParticleEngine, MarchingCubes, ParticleEngine,
+        fieldprovider::FieldProvider, find_critical_points, find_landmarks,
+        gpu_fieldprovider::GPUFieldProvider, gpu_particles::GPUParticleEngine, Axis, ColorMode,
+        Colormap, FieldSequence, Generator, Integrator, Interpolation, LandmarkSettings,
+        MarchingCubes, ParticleEngine, Quantity, ScalarVolume, SeedShape, Streamlines,
     },
 };
-use gl_bindings::{AbstractContext, Context};
-use std::{f32, io::Read, path::PathBuf};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{particles::Mask, session::Session};
+use gl_bindings::{AbstractContext, Context, Texture};
+use std::{f32, path::PathBuf, rc::Rc};
 #[cfg(not(target_arch = "wasm32"))]
 use structopt::StructOpt;
 use window::{AbstractWindow, Event, Window};
@@ -35,7 +41,7 @@ const DEFAULT_WEB_GPU_PARTICLE_COUNT: usize = 512;
 /// Main entry point for the Web application.
 #[cfg(target_arch = "wasm32")]
 fn main() {
-    let mut app = App::new(None, false, DEFAULT_WEB_GPU_PARTICLE_COUNT);
+    let mut app = App::new(None, None, false, DEFAULT_WEB_GPU_PARTICLE_COUNT);
     window::Window::run_loop(move |_| app.run());
 }
 
@@ -54,17 +60,183 @@ struct Opt {
     /// of two, like 256, 512 or 1024.
     #[structopt(short = "c", long = "gpu-particle-count", default_value = "512")]
     gpu_particle_count: usize,
+
+    /// Convert FILE to the versioned field format, write it to the given path and exit.
+    #[structopt(long = "export", parse(from_os_str))]
+    export: Option<PathBuf>,
+
+    /// Generate an analytic field instead of loading FILE: abc, double-gyre, lorenz,
+    /// hills-vortex, source-sink or uniform. Parameters can be given after a colon,
+    /// like `lorenz:rho=28,sigma=10`.
+    #[structopt(long = "generate")]
+    generate: Option<Generator>,
+
+    /// Number of particles to use on the CPU. The update is spread over all cores.
+    #[structopt(short = "n", long = "particle-count", default_value = "100000")]
+    particle_count: usize,
+
+    /// How the CPU particles are advanced along the field: euler, midpoint, rk4 or rk45.
+    /// The adaptive rk45 keeps the error of each substep below `--tolerance`.
+    #[structopt(long = "integrator", default_value = "euler")]
+    integrator: Integrator,
+
+    /// Largest error per substep of the rk45 integrator, in world units,
+    /// where the field spans 1 along its largest side.
+    #[structopt(long = "tolerance", default_value = "1e-5")]
+    tolerance: f32,
+
+    /// Seed for the spawn positions and lifetimes of the particles, which makes runs
+    /// with the same settings reproducible.
+    #[structopt(long = "seed")]
+    seed: Option<u64>,
+
+    /// Number of voxels along the largest side of a generated field.
+    #[structopt(long = "size", default_value = "64")]
+    size: usize,
+
+    /// Label volume in NRRD format with the same dimensions as the field. Particles only
+    /// spawn where the label is non-zero, and the mesh shows the boundary of the mask.
+    #[structopt(long = "mask", parse(from_os_str))]
+    mask: Option<PathBuf>,
+
+    /// Scalar volume in NRRD format with the same dimensions as the field, such as an
+    /// anatomical image. Colors the particles, the mesh and the map.
+    #[structopt(long = "overlay", parse(from_os_str))]
+    overlay: Option<PathBuf>,
+
+    /// What the particle colors show: direction, speed, age, emitter or overlay.
+    /// Defaults to overlay when `--overlay` is given, and to direction otherwise.
+    #[structopt(long = "color-by")]
+    color_by: Option<ColorMode>,
+
+    /// Colormap of the speed, age and overlay color modes: viridis, inferno, coolwarm
+    /// or grayscale.
+    #[structopt(long = "colormap", default_value = "viridis")]
+    colormap: Colormap,
+
+    /// Streamlines traced from the seeding region of each emitter.
+    #[structopt(long = "streamline-seeds", default_value = "100")]
+    streamline_seeds: usize,
+
+    /// Distance between two points of a streamline, in world units.
+    #[structopt(long = "streamline-step", default_value = "0.005")]
+    streamline_step: f32,
+
+    /// The longest a streamline gets, in world units, half of it on each side of its seed.
+    #[structopt(long = "streamline-length", default_value = "0.5")]
+    streamline_length: f32,
+
+    /// The sharpest turn between two steps before a streamline ends, in degrees.
+    #[structopt(long = "max-turn", default_value = "60")]
+    max_turn: f32,
+
+    /// Radius of the streamline tubes, in world units.
+    #[structopt(long = "tube-radius", default_value = "0.003")]
+    tube_radius: f32,
+
+    /// Streamlines shorter than this are left out, in world units.
+    #[structopt(long = "min-length", default_value = "0")]
+    min_length: f32,
+
+    /// Show fibers tracked along the axis of the field whatever the sign of its vectors,
+    /// as for the eigenvectors of diffusion tensors.
+    #[structopt(long = "tractography")]
+    tractography: bool,
+
+    /// The lowest fourth channel, such as the fractional anisotropy, that fibers go through.
+    #[structopt(long = "fa-threshold", default_value = "0.2")]
+    fa_threshold: f32,
+
+    /// Restore a saved session, including its field file, camera and settings.
+    #[structopt(long = "session", parse(from_os_str))]
+    session: Option<PathBuf>,
 }
 
 /// Main entry point for the native application.
 #[cfg(not(target_arch = "wasm32"))]
 fn main() {
     let opt = Opt::from_args();
+    let size = opt.size;
+    let generated = opt.generate.map(|generator| generator.generate(size));
 
-    let mut app = App::new(opt.file, opt.cpu, opt.gpu_particle_count);
+    if let Some(output) = opt.export {
+        if let Err(e) = export(opt.file, generated, &output) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut app = App::new(opt.file, generated, opt.cpu, opt.gpu_particle_count);
+    app.state.particle_count = opt.particle_count;
+    app.state.integrator = opt.integrator;
+    app.state.tolerance = opt.tolerance;
+    app.state.colormap = opt.colormap;
+    app.state.streamlines.seeds = opt.streamline_seeds;
+    app.state.streamlines.step = opt.streamline_step;
+    app.state.streamlines.max_length = opt.streamline_length;
+    app.state.streamlines.max_turn = opt.max_turn;
+    app.state.streamlines.tube_radius = opt.tube_radius;
+    app.state.streamlines.min_length = opt.min_length;
+    app.state.streamlines.fa_threshold = opt.fa_threshold;
+    if opt.tractography {
+        app.state.streamlines.tractography = true;
+        app.state.show_streamlines = true;
+    }
+    if opt.seed.is_some() {
+        app.state.seed = opt.seed;
+        app.state.reseed = true;
+    }
+    if let Some(mask) = opt.mask {
+        app.state.mask_file = Some(mask);
+        app.state.reload_mask = true;
+    }
+    if let Some(overlay) = opt.overlay {
+        app.state.overlay_file = Some(overlay);
+        app.state.reload_overlay = true;
+    }
+    if let Some(color_mode) = opt.color_by {
+        app.state.color_mode = color_mode;
+    }
+    if let Some(session) = opt.session {
+        app.load_session(&session);
+    }
     window::Window::run_loop(move |_| app.run());
 }
 
+/// Writes the generated fields, or the field in `input`, to `output` as a compressed container.
+#[cfg(not(target_arch = "wasm32"))]
+fn export(
+    input: Option<PathBuf>,
+    generated: Option<FieldSequence>,
+    output: &std::path::Path,
+) -> Result<(), String> {
+    let mut state = State::new();
+    let mut fields = match generated {
+        Some(fields) => fields,
+        None => {
+            state.file_path = Some(input.ok_or_else(|| "No input file given.".to_owned())?);
+            file_loading::load_fields(&state).map_err(|e| e.to_string())?
+        }
+    };
+
+    let field = fields.first_mut();
+    let mut metadata = field.metadata().clone();
+    if metadata.name.is_none() {
+        metadata.name = state
+            .file_path
+            .as_ref()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().into_owned());
+    }
+    field.set_metadata(metadata);
+
+    let data = file_loading::write_container(&fields, file_loading::Compression::Gzip)
+        .map_err(|e| e.to_string())?;
+    std::fs::write(output, data)
+        .map_err(|e| format!("Failed to write file {}: {}", output.display(), e))
+}
+
 /// Holds application resources.
 pub struct App {
     camera: camera::ArcBall,
@@ -77,65 +249,136 @@ pub struct App {
     gpu_field: GPUFieldProvider,
     gpu_particles: GPUParticleEngine,
     march: MarchingCubes,
+    streamlines: Streamlines,
     gpu_particle_count: usize,
+    /// The loaded scalar volume coloring the particles, mesh and map.
+    overlay: Option<ScalarVolume>,
+    /// The selected derived quantity and the frame it was computed for, which take
+    /// precedence over the overlay.
+    derived: Option<(Quantity, usize, ScalarVolume)>,
+    /// Texture of the volume coloring the particles and map.
+    color_texture: Option<Rc<Texture>>,
+    /// Level of the derived isosurface shown as the mesh, if any.
+    mesh_level: Option<f32>,
+    /// The frame whose critical points are shown, if they are shown.
+    critical_points_frame: Option<usize>,
+    /// The settings used for the landmarks shown, if they are up to date.
+    landmarks: Option<LandmarkSettings>,
+    /// The interpolation last reported in the status.
+    interpolation: Interpolation,
+    /// The integrator last reported in the status.
+    integrator: Integrator,
+    /// The seeding shape and axis last reported in the status.
+    seeding: (SeedShape, Axis),
+    /// The selected emitter and the number of emitters last reported in the status.
+    emitters: (usize, usize),
+    /// The colormap and its lookup texture, shared by both particle engines.
+    colormap: (Colormap, Rc<Texture>),
+    /// The color mode and colormap last reported in the status.
+    coloring: (ColorMode, Colormap),
 }
 
 impl App {
     /// Starts the application.
-    /// Expects a file path for non-web compile targets.
-    pub fn new(path: Option<PathBuf>, start_with_cpu: bool, gpu_particle_count: usize) -> App {
+    /// Expects a file path or generated fields for non-web compile targets.
+    pub fn new(
+        path: Option<PathBuf>,
+        generated: Option<FieldSequence>,
+        start_with_cpu: bool,
+        gpu_particle_count: usize,
+    ) -> App {
         #[allow(unused_assignments)]
         let mut field_provider = None;
         #[allow(unused_assignments)]
         let mut gpu_field = None;
+        // Startup problems are shown in the status label once the GUI exists.
+        #[allow(unused_mut)]
+        let mut status = None;
+        #[allow(unused_mut)]
+        let mut options_file = None;
         let window = Window::new("Brainstorm!", INITIAL_WINDOW_WIDTH, INITIAL_WINDOW_HEIGHT);
 
         // For web we embed the data in the executable.
         #[cfg(target_arch = "wasm32")]
         {
             stdweb::initialize();
-            let vector_field =
-                bincode::deserialize(&resources::fields::TEST_DATA).expect("Failed to parse data.");
-            gpu_field = Some(GPUFieldProvider::new(&vector_field));
-            field_provider = Some(FieldProvider::new(vector_field));
+            let fields = generated.unwrap_or_else(|| {
+                file_loading::read_container(&resources::fields::TEST_DATA)
+                    .expect("Failed to parse the embedded test field.")
+            });
+            gpu_field = Some(GPUFieldProvider::new(&fields));
+            field_provider = Some(FieldProvider::new(fields));
         }
-        // For desktop we load a file if it exists.
+        // For desktop we use generated fields, or load a file if it exists.
         #[cfg(not(target_arch = "wasm32"))]
         {
-            let content: Vec<u8> = if let Some(ref path) = path {
-                let mut file = std::fs::File::open(path).expect("Failed to open file!");
-                let mut content = Vec::new();
-                file.read_to_end(&mut content)
-                    .expect("Failed to read file!");
-                content
-            } else {
-                Vec::from(resources::fields::DEFAULT_SPIRAL)
+            let create_providers = |fields: FieldSequence| {
+                let gpu = GPUFieldProvider::new(&fields);
+                (FieldProvider::new(fields), gpu)
+            };
+            let loaded = match (generated, &path) {
+                (Some(fields), _) => Some(create_providers(fields)),
+                (None, Some(_)) => {
+                    let mut state = State::new();
+                    state.file_path = path.clone();
+                    match file_loading::reload_file(&state) {
+                        Ok(FileResult::VectorField(providers)) => Some(providers),
+                        Ok(FileResult::OptionsFile(header)) => {
+                            status = Some("Header loaded - load data file next.".to_owned());
+                            options_file = Some(header);
+                            None
+                        }
+                        Err(e) => {
+                            status = Some(e.to_string());
+                            None
+                        }
+                    }
+                }
+                (None, None) => None,
             };
-            let vector_field = bincode::deserialize(&content).expect("Failed to parse data.");
-            gpu_field = Some(GPUFieldProvider::new(&vector_field));
-            field_provider = Some(FieldProvider::new(vector_field));
+            // Show the default field if nothing else could be loaded.
+            let (fp, gfp) = loaded.unwrap_or_else(|| {
+                create_providers(
+                    file_loading::read_container(&resources::fields::DEFAULT_SPIRAL)
+                        .expect("Failed to parse the embedded default field."),
+                )
+            });
+            gpu_field = Some(gfp);
+            field_provider = Some(fp);
         }
 
         let field_provider = field_provider.unwrap();
         let gpu_field = gpu_field.unwrap();
         let march = MarchingCubes::marching_cubes(&field_provider);
-        let particles = ParticleEngine::new(field_provider);
-        let gpu_particles = GPUParticleEngine::new(gpu_particle_count);
+        let transform = field_provider.transform().clone();
+        let frame_count = field_provider.frame_count();
+        let time_step = field_provider.time_step();
+        let mut particles = ParticleEngine::new(field_provider, None);
+        let mut gpu_particles = GPUParticleEngine::new(gpu_particle_count, None);
+        let colormap = Rc::new(Colormap::default().create_texture());
+        particles.set_colormap(colormap.clone());
+        gpu_particles.set_colormap(colormap.clone());
 
         let mut state = State::new();
         state.file_path = path;
+        state.options_file = options_file;
         state.use_cpu_particles = start_with_cpu;
-        state.directional_data = particles.calculate_highly_directional_positions();
+        state.frame_count = frame_count;
+        state.time_step = time_step;
 
         let mut gui = Gui::new(
             (INITIAL_WINDOW_WIDTH as f32, INITIAL_WINDOW_HEIGHT as f32),
             &state,
         );
 
-        gui.map.set_texture(&Some(gpu_field.get_texture()));
-
-        gui.world_points
-            .set_points(particles.calculate_highly_directional_positions());
+        if let Some(status) = status {
+            gui.status.set_status(status);
+        }
+        gui.map
+            .set_texture(&Some(gpu_field.get_texture(state.time)));
+        let (min, max) = transform.bounds();
+        gui.map.set_transform(transform);
+        gui.model_bound.set_bounds(min, max);
 
         App {
             window,
@@ -148,7 +391,20 @@ impl App {
             gpu_field,
             gpu_particles,
             march,
+            streamlines: Streamlines::new(),
             gpu_particle_count,
+            overlay: None,
+            derived: None,
+            color_texture: None,
+            mesh_level: None,
+            critical_points_frame: None,
+            landmarks: None,
+            interpolation: Interpolation::default(),
+            integrator: Integrator::default(),
+            seeding: (SeedShape::default(), Axis::default()),
+            emitters: (0, 1),
+            colormap: (Colormap::default(), colormap),
+            coloring: (ColorMode::default(), Colormap::default()),
         }
     }
 
@@ -177,11 +433,12 @@ impl App {
             }
         }
 
-        // Update camera position.
+        // Update camera position. The selected emitter follows the camera target.
         {
             self.camera.set_target_position(self.state.camera_target);
-            self.gui.seeding_sphere.retarget(self.state.camera_target);
             self.gui.map.set_target(self.state.camera_target);
+            let target = self.state.camera_target;
+            self.state.emitter_mut().position = target;
         }
 
         // Replace particle data if requested.
@@ -197,18 +454,119 @@ impl App {
         // Load new file if requested.
         self.load_file();
 
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.handle_session_requests();
+            self.load_mask();
+            self.load_overlay();
+        }
+
+        self.update_derived();
+        self.update_critical_points();
+        self.update_landmarks();
+
         // Update status label timer
         self.gui.status.update_status();
 
+        // Advance time-varying fields.
+        self.update_playback();
+
+        // Show settings that may have changed elsewhere, such as on the command line or by
+        // selecting another emitter.
+        self.gui.sync(&self.state);
+
+        if self.state.interpolation != self.interpolation {
+            self.interpolation = self.state.interpolation;
+            self.gui
+                .status
+                .set_status(format!("Interpolation: {}", self.interpolation));
+        }
+        let seeding = (self.state.emitter().shape, self.state.emitter().axis);
+        if seeding != self.seeding {
+            self.seeding = seeding;
+            let status = match seeding.0 {
+                SeedShape::Plane | SeedShape::Rake | SeedShape::Disk => {
+                    format!("Seeding: {} along {}", seeding.0, seeding.1)
+                }
+                _ => format!("Seeding: {}", seeding.0),
+            };
+            self.gui.status.set_status(status);
+        }
+        let emitters = (self.state.selected_emitter, self.state.emitters.len());
+        if emitters != self.emitters {
+            self.emitters = emitters;
+            self.gui
+                .status
+                .set_status(format!("Emitter {} of {}", emitters.0 + 1, emitters.1));
+        }
+        self.update_colormap();
+        let coloring = (self.state.color_mode, self.state.colormap);
+        if coloring != self.coloring {
+            self.coloring = coloring;
+            let status = if coloring.0.uses_colormap() {
+                format!("Color: {} ({})", coloring.0, coloring.1)
+            } else {
+                format!("Color: {}", coloring.0)
+            };
+            self.gui.status.set_status(status);
+        }
+        self.update_streamlines();
+        if self.state.integrator != self.integrator {
+            self.integrator = self.state.integrator;
+            self.gui
+                .status
+                .set_status(format!("Integrator: {}", self.integrator));
+        }
+        if self.state.reseed {
+            self.state.reseed = false;
+            self.particles.reseed(self.state.seed);
+            self.gpu_particles.reseed(self.state.seed);
+        }
+
         // Update particle system
         let (cx, cy, cz) = self.camera.get_position();
         self.march.set_light_dir((cx, cy, cz));
+        self.streamlines.set_light_dir((cx, cy, cz));
 
         self.render_all();
         self.time += 0.01;
         self.state.is_running
     }
 
+    /// Advances the current time while playing, and keeps the timeline and map in sync with it.
+    fn update_playback(&mut self) {
+        if self.state.frame_count < 2 {
+            return;
+        }
+        let end = (self.state.frame_count - 1) as f32;
+        if self.state.playing {
+            // Without a usable time step, as in a hand-written container, play a frame per unit.
+            let time_step = if self.state.time_step > 0.0 {
+                self.state.time_step
+            } else {
+                1.0
+            };
+            self.state.time += 0.01 * self.state.speed_multiplier / time_step;
+            if self.state.time > end {
+                self.state.time = if self.state.loop_playback {
+                    self.state.time % end
+                } else {
+                    end
+                };
+            }
+        }
+        self.gui.timeline.set_value(self.state.time / end);
+        self.gui.map.set_texture(&Some(self.map_texture()));
+    }
+
+    /// The texture shown on the map: the color volume if there is one, otherwise the current frame.
+    fn map_texture(&self) -> Rc<Texture> {
+        match &self.color_texture {
+            Some(texture) => texture.clone(),
+            None => self.gpu_field.get_texture(self.state.time),
+        }
+    }
+
     fn render_all(&mut self) {
         // Clear screen
         let context = Context::get_context();
@@ -219,15 +577,16 @@ impl App {
         // Draw everything
         context.enable(Context::DEPTH_TEST);
         let projection_matrix = self.camera.get_projection_matrix();
-        self.gui.seeding_sphere.resize(self.state.seeding_size);
+        self.gui
+            .seeding_gizmo
+            .set_emitters(&self.state.emitters, self.state.selected_emitter);
 
         if self.state.use_cpu_particles {
             self.particles.update(&self.state, &self.camera);
             self.particles.draw(&projection_matrix, &self.state);
         } else {
             context.disable(Context::DEPTH_TEST);
-            self.gpu_particles
-                .update(&self.gpu_field, &self.state, &self.camera);
+            self.gpu_particles.update(&self.gpu_field, &self.state);
             context.enable(Context::DEPTH_TEST);
             context.blend_func(Context::SRC_ALPHA, Context::ONE);
             context.depth_mask(false);
@@ -236,6 +595,10 @@ impl App {
             context.blend_func(Context::SRC_ALPHA, Context::ONE_MINUS_SRC_ALPHA);
         }
 
+        if self.state.show_streamlines {
+            self.streamlines.draw_transformed(&projection_matrix);
+        }
+
         if self.state.mesh_transparency < 1.0 {
             context.depth_mask(false);
         }
@@ -256,6 +619,359 @@ impl App {
         self.window.swap_buffers();
     }
 
+    /// Replaces the field and everything derived from it.
+    fn set_field(&mut self, providers: (FieldProvider, GPUFieldProvider)) {
+        let (field_provider, gpu_field_provider) = providers;
+        self.state.options_file = None;
+        self.state.mask_file = None;
+        self.state.overlay_file = None;
+        self.overlay = None;
+        self.derived = None;
+        self.critical_points_frame = None;
+        self.gui.world_points.set_critical_points(&[]);
+        self.landmarks = None;
+        self.color_texture = None;
+        let transform = field_provider.transform().clone();
+        self.state.frame_count = field_provider.frame_count();
+        self.state.time_step = field_provider.time_step();
+        self.state.time = 0.0;
+        self.particles = ParticleEngine::new(field_provider, self.state.seed);
+        self.gpu_field = gpu_field_provider;
+        self.gpu_particles = GPUParticleEngine::new(self.gpu_particle_count, self.state.seed);
+        self.particles.set_colormap(self.colormap.1.clone());
+        self.gpu_particles.set_colormap(self.colormap.1.clone());
+        self.update_mesh();
+        self.gui.map.set_texture(&Some(self.map_texture()));
+        let (min, max) = transform.bounds();
+        self.gui.map.set_transform(transform);
+        self.gui.model_bound.set_bounds(min, max);
+    }
+
+    /// Rebuilds the context mesh: the isosurface of the derived quantity if requested,
+    /// otherwise the boundary of the mask or the field.
+    fn update_mesh(&mut self) {
+        let field_provider = self.particles.field_provider();
+        let transform = field_provider.transform();
+        self.march = match (&self.derived, self.mesh_level, self.particles.mask()) {
+            (Some((_, _, volume)), Some(level), _) => {
+                MarchingCubes::isosurface(volume, transform, level)
+            }
+            (_, _, Some(mask)) => MarchingCubes::mask_boundary(mask, transform),
+            _ => MarchingCubes::marching_cubes(field_provider),
+        };
+        self.update_colors();
+    }
+
+    /// The volume coloring the particles, mesh and map: the derived quantity if one is
+    /// selected, otherwise the loaded overlay.
+    fn color_volume(&self) -> Option<&ScalarVolume> {
+        match &self.derived {
+            Some((_, _, volume)) => Some(volume),
+            None => self.overlay.as_ref(),
+        }
+    }
+
+    /// Applies the current color volume to the particles, mesh and map.
+    fn update_colors(&mut self) {
+        let volume = self.color_volume().cloned();
+        let transform = self.particles.field_provider().transform();
+        self.march.set_overlay(volume.as_ref(), transform);
+        self.color_texture = volume
+            .as_ref()
+            .map(|volume| Rc::new(volume.create_texture()));
+        self.particles.set_overlay(volume);
+        self.gpu_particles.set_overlay(self.color_texture.clone());
+        self.gui.map.set_texture(&Some(self.map_texture()));
+        self.streamlines.invalidate();
+    }
+
+    /// Traces the streamlines again when they are shown and their settings, seeds or filters
+    /// changed.
+    fn update_streamlines(&mut self) {
+        if !self.state.show_streamlines {
+            return;
+        }
+        self.particles.sync_field(&self.state);
+        // The color volume, borrowed apart from the streamlines.
+        let overlay = match &self.derived {
+            Some((_, _, volume)) => Some(volume),
+            None => self.overlay.as_ref(),
+        };
+        let traced = self.streamlines.update(
+            self.particles.field_provider(),
+            self.particles.mask(),
+            overlay,
+            &self.state,
+        );
+        if let Some(count) = traced {
+            self.gui
+                .status
+                .set_status(format!("Traced {} streamlines", count));
+        }
+    }
+
+    /// Colors the particles by the overlay or derived quantity, unless another color mode
+    /// than the default was chosen.
+    fn show_color_volume(&mut self) {
+        if self.state.color_mode == ColorMode::Direction {
+            self.state.color_mode = ColorMode::Overlay;
+        }
+    }
+
+    /// Recreates the colormap lookup texture when another colormap is selected.
+    fn update_colormap(&mut self) {
+        if self.state.colormap == self.colormap.0 {
+            return;
+        }
+        let texture = Rc::new(self.state.colormap.create_texture());
+        self.particles.set_colormap(texture.clone());
+        self.gpu_particles.set_colormap(texture.clone());
+        self.colormap = (self.state.colormap, texture);
+    }
+
+    /// Finds the landmarks shown as world points when their settings change.
+    fn update_landmarks(&mut self) {
+        if self.landmarks.as_ref() == Some(&self.state.landmarks) {
+            return;
+        }
+        let landmarks = find_landmarks(self.particles.field_provider(), &self.state.landmarks);
+        if self.landmarks.is_some() {
+            self.gui.status.set_status(format!(
+                "Found {} landmarks ({})",
+                landmarks.len(),
+                self.state.landmarks.method
+            ));
+        }
+        self.gui.world_points.set_points(landmarks.clone());
+        self.state.directional_data = landmarks;
+        self.landmarks = Some(self.state.landmarks.clone());
+    }
+
+    /// Finds or hides the critical points when requested, and finds them again when the frame
+    /// of the field changes.
+    fn update_critical_points(&mut self) {
+        self.particles.sync_field(&self.state);
+        let frame = self.particles.field_provider().frame();
+        let wanted = Some(frame).filter(|_| self.state.show_critical_points);
+        if wanted == self.critical_points_frame {
+            return;
+        }
+        let shown = self.critical_points_frame.is_some();
+        self.critical_points_frame = wanted;
+        let points = if wanted.is_some() {
+            let points = find_critical_points(self.particles.field_provider());
+            if !shown {
+                self.gui
+                    .status
+                    .set_status(format!("Found {} critical points", points.len()));
+            }
+            points
+        } else {
+            Vec::new()
+        };
+        self.gui.world_points.set_critical_points(&points);
+    }
+
+    /// Recomputes the derived quantity and its isosurface when their settings or the frame of
+    /// the field change.
+    fn update_derived(&mut self) {
+        self.particles.sync_field(&self.state);
+        let frame = self.particles.field_provider().frame();
+        let current = self
+            .derived
+            .as_ref()
+            .map(|(quantity, frame, _)| (*quantity, *frame));
+        let wanted = self.state.derived.map(|quantity| (quantity, frame));
+        if current != wanted {
+            let field_provider = self.particles.field_provider();
+            self.derived = self
+                .state
+                .derived
+                .map(|quantity| (quantity, frame, quantity.compute(field_provider)));
+            // Only a new selection is reported, not every frame of the playback.
+            if current.map(|(quantity, _)| quantity) != self.state.derived {
+                if let Some(quantity) = self.state.derived {
+                    self.gui.status.set_status(format!("Showing {}", quantity));
+                    self.show_color_volume();
+                }
+            }
+            self.update_colors();
+            self.mesh_level = None;
+        }
+
+        let level = match &self.derived {
+            Some((_, _, volume)) if self.state.derived_surface => {
+                let (min, max) = volume.range();
+                Some(min + (max - min) * self.state.derived_level)
+            }
+            _ => None,
+        };
+        if level != self.mesh_level {
+            self.mesh_level = level;
+            self.update_mesh();
+        }
+    }
+
+    /// Restricts particles to a mask and shows its boundary, or removes the current mask.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn set_mask(&mut self, mask: Option<Mask>) {
+        self.gpu_particles.set_mask(mask.as_ref());
+        self.particles.set_mask(mask);
+        self.update_mesh();
+    }
+
+    /// Loads the mask file if requested, or removes the mask if there is none.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_mask(&mut self) {
+        if !self.state.reload_mask {
+            return;
+        }
+        self.state.reload_mask = false;
+        let path = match &self.state.mask_file {
+            Some(path) => path,
+            None => return self.set_mask(None),
+        };
+
+        let field = self.particles.field_provider();
+        match file_loading::load_mask(path, (field.width, field.height, field.depth)) {
+            Ok(mask) => {
+                self.set_mask(Some(mask));
+                self.gui.status.set_status("Mask loaded!".to_owned());
+            }
+            Err(e) => {
+                self.state.mask_file = None;
+                self.set_mask(None);
+                self.gui.status.set_status(e.to_string());
+            }
+        }
+    }
+
+    /// Colors the particles, mesh and map by an overlay, or shows the field again.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn set_overlay(&mut self, overlay: Option<ScalarVolume>) {
+        self.overlay = overlay;
+        self.update_colors();
+    }
+
+    /// Loads the overlay file if requested, or removes the overlay if there is none.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_overlay(&mut self) {
+        if !self.state.reload_overlay {
+            return;
+        }
+        self.state.reload_overlay = false;
+        let path = match &self.state.overlay_file {
+            Some(path) => path,
+            None => return self.set_overlay(None),
+        };
+
+        let field = self.particles.field_provider();
+        match file_loading::load_scalar_volume(path, (field.width, field.height, field.depth)) {
+            Ok(overlay) => {
+                self.set_overlay(Some(overlay));
+                self.gui.status.set_status("Overlay loaded!".to_owned());
+                self.show_color_volume();
+            }
+            Err(e) => {
+                self.state.overlay_file = None;
+                self.set_overlay(None);
+                self.gui.status.set_status(e.to_string());
+            }
+        }
+    }
+
+    /// Saves or loads a session if requested through the GUI.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn handle_session_requests(&mut self) {
+        if let Some(path) = self.state.save_session.take() {
+            self.save_session(&path);
+        }
+        if let Some(path) = self.state.load_session.take() {
+            self.load_session(&path);
+        }
+    }
+
+    /// Writes the state, camera and GUI settings to a session file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_session(&mut self, path: &std::path::Path) {
+        let session = Session {
+            state: self.state.clone(),
+            camera: self.camera.pose(),
+            gui: self.gui.settings(),
+        };
+        match session.write(path) {
+            Ok(()) => self.gui.status.set_status("Session saved!".to_owned()),
+            Err(e) => self.gui.status.set_status(e.to_string()),
+        }
+    }
+
+    /// Restores a session file, loading its field file if it differs from the current one.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_session(&mut self, path: &std::path::Path) {
+        let Session {
+            mut state,
+            camera,
+            gui,
+        } = match Session::read(path) {
+            Ok(session) => session,
+            Err(e) => {
+                self.gui.status.set_status(e.to_string());
+                return;
+            }
+        };
+        let changes = match state.fix_emitters() {
+            Ok(changes) => changes,
+            Err(e) => {
+                self.gui.status.set_status(e);
+                return;
+            }
+        };
+
+        // Sessions without a file path were made with a built-in field, so keep the current one.
+        if state.file_path.is_some() && state.file_path != self.state.file_path {
+            let mut file_state = State::new();
+            file_state.file_path = state.file_path.clone();
+            match file_loading::reload_file(&file_state) {
+                Ok(FileResult::VectorField(providers)) => self.set_field(providers),
+                Ok(FileResult::OptionsFile(_)) => {
+                    self.gui
+                        .status
+                        .set_status("No data file found for the session's header.".to_owned());
+                    return;
+                }
+                Err(e) => {
+                    self.gui.status.set_status(e.to_string());
+                    return;
+                }
+            }
+        } else {
+            state.file_path = self.state.file_path.clone();
+        }
+
+        // Keep what belongs to the window and the loaded field rather than the session.
+        state.mouse_x = self.state.mouse_x;
+        state.mouse_y = self.state.mouse_y;
+        state.window_w = self.state.window_w;
+        state.window_h = self.state.window_h;
+        state.directional_data = std::mem::take(&mut self.state.directional_data);
+        state.frame_count = self.state.frame_count;
+        state.time_step = self.state.time_step;
+        state.time = state.time.min((state.frame_count - 1) as f32);
+        state.reload_mask = true;
+        state.reload_overlay = true;
+        state.reseed = true;
+        self.state = state;
+
+        self.camera.set_pose(camera);
+        self.gui.restore_settings(&gui);
+        let status = if changes.is_empty() {
+            "Session loaded!".to_owned()
+        } else {
+            format!("Session loaded, but {}.", changes.join(" and "))
+        };
+        self.gui.status.set_status(status);
+    }
+
     fn load_file(&mut self) {
         // Two-step file reload:
         // Step 1 (reload_file): Write "Loading file".
@@ -270,26 +986,18 @@ impl App {
                             self.state.options_file = Some(opt);
                             self.gui
                                 .status
-                                .set_status("Options file loaded - load raw file next.".to_owned());
+                                .set_status("Header loaded - load data file next.".to_owned());
                         }
-                        FileResult::VectorField((field_provider, gpu_field_provider)) => {
-                            self.gui.status.set_status("File loaded!".to_owned());
-                            self.state.options_file = None;
-                            self.march = MarchingCubes::marching_cubes(&field_provider);
-                            self.particles = ParticleEngine::new(field_provider);
-                            self.state.directional_data =
-                                self.particles.calculate_highly_directional_positions();
-                            self.gpu_field = gpu_field_provider;
-                            self.gpu_particles = GPUParticleEngine::new(self.gpu_particle_count);
-                            self.gui
-                                .map
-                                .set_texture(&Some(self.gpu_field.get_texture()));
-                            self.gui.world_points.set_points(
-                                self.particles.calculate_highly_directional_positions(),
-                            );
+                        FileResult::VectorField(providers) => {
+                            let status = match &providers.0.metadata().name {
+                                Some(name) => format!("Loaded {}!", name),
+                                None => "File loaded!".to_owned(),
+                            };
+                            self.gui.status.set_status(status);
+                            self.set_field(providers);
                         }
                     },
-                    Err(e) => self.gui.status.set_status(e),
+                    Err(e) => self.gui.status.set_status(e.to_string()),
                 }
                 self.mid_reload = false;
             } else {
@@ -0,0 +1,134 @@
+//! How particles are colored, and the colormaps used for scalar coloring.
+
+use gl_bindings::{Texture, TextureFormat};
+use serde_derive::{Deserialize, Serialize};
+
+type Vector3 = (f32, f32, f32);
+
+/// Texels in a colormap lookup texture.
+const COLORMAP_SIZE: usize = 256;
+
+/// What the color of a particle shows.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum ColorMode {
+    /// The absolute direction of motion as RGB, red for x, green for y and blue for z,
+    /// as is standard for diffusion tensor imaging.
+    #[default]
+    Direction,
+    /// The speed through the colormap, relative to the fastest in the field.
+    Speed,
+    /// The age through the colormap, relative to the lifetime.
+    Age,
+    /// The color of the emitter that spawned the particle.
+    Emitter,
+    /// The overlay or derived quantity at the particle through the colormap,
+    /// or white without either.
+    Overlay,
+}
+
+gui_enum!(ColorMode, COLOR_MODES, "color mode", {
+    Direction => "direction",
+    Speed => "speed",
+    Age => "age",
+    Emitter => "emitter",
+    Overlay => "overlay",
+});
+
+impl ColorMode {
+    /// Whether the mode maps a scalar through the colormap.
+    pub fn uses_colormap(self) -> bool {
+        match self {
+            ColorMode::Speed | ColorMode::Age | ColorMode::Overlay => true,
+            ColorMode::Direction | ColorMode::Emitter => false,
+        }
+    }
+}
+
+/// Maps scalars in [0, 1] to colors.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum Colormap {
+    /// Perceptually uniform from dark blue through green to yellow.
+    #[default]
+    Viridis,
+    /// Perceptually uniform from black through red to pale yellow.
+    Inferno,
+    /// Diverging from blue through gray to red, for values around a middle.
+    CoolWarm,
+    Grayscale,
+}
+
+gui_enum!(Colormap, COLORMAPS, "colormap", {
+    Viridis => "viridis",
+    Inferno => "inferno",
+    CoolWarm => "coolwarm",
+    Grayscale => "grayscale",
+});
+
+const VIRIDIS: [Vector3; 10] = [
+    (0.267, 0.005, 0.329),
+    (0.282, 0.157, 0.471),
+    (0.243, 0.290, 0.537),
+    (0.192, 0.408, 0.557),
+    (0.149, 0.510, 0.557),
+    (0.122, 0.620, 0.537),
+    (0.208, 0.718, 0.475),
+    (0.427, 0.804, 0.349),
+    (0.706, 0.871, 0.173),
+    (0.992, 0.906, 0.145),
+];
+
+const INFERNO: [Vector3; 10] = [
+    (0.001, 0.000, 0.014),
+    (0.106, 0.047, 0.255),
+    (0.290, 0.047, 0.420),
+    (0.471, 0.110, 0.427),
+    (0.647, 0.173, 0.376),
+    (0.812, 0.267, 0.275),
+    (0.929, 0.412, 0.145),
+    (0.984, 0.608, 0.024),
+    (0.969, 0.820, 0.239),
+    (0.988, 1.000, 0.643),
+];
+
+const COOL_WARM: [Vector3; 5] = [
+    (0.230, 0.299, 0.754),
+    (0.552, 0.690, 0.996),
+    (0.866, 0.866, 0.866),
+    (0.956, 0.604, 0.486),
+    (0.706, 0.016, 0.150),
+];
+
+const GRAYSCALE: [Vector3; 2] = [(0.0, 0.0, 0.0), (1.0, 1.0, 1.0)];
+
+impl Colormap {
+    /// Evenly spaced colors, from the color of 0 to that of 1.
+    fn stops(self) -> &'static [Vector3] {
+        match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Inferno => &INFERNO,
+            Colormap::CoolWarm => &COOL_WARM,
+            Colormap::Grayscale => &GRAYSCALE,
+        }
+    }
+
+    /// The color of `value`, which is clamped to [0, 1].
+    pub fn sample(self, value: f32) -> Vector3 {
+        let stops = self.stops();
+        let position = value.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let lower = (position.floor() as usize).min(stops.len() - 2);
+        let t = position - lower as f32;
+        let ((r1, g1, b1), (r2, g2, b2)) = (stops[lower], stops[lower + 1]);
+        (r1 + (r2 - r1) * t, g1 + (g2 - g1) * t, b1 + (b2 - b1) * t)
+    }
+
+    /// Creates a lookup texture of one row, with 0 at the left edge and 1 at the right.
+    pub fn create_texture(self) -> Texture {
+        let data: Vec<f32> = (0..COLORMAP_SIZE)
+            .flat_map(|i| {
+                let (r, g, b) = self.sample(i as f32 / (COLORMAP_SIZE - 1) as f32);
+                vec![r, g, b, 1.0]
+            })
+            .collect();
+        Texture::from_data(COLORMAP_SIZE as u32, 1, TextureFormat::RGBA, &data)
+    }
+}
@@ -0,0 +1,219 @@
+//! Critical points of a field, where the velocity is zero and the flow around them is
+//! determined by the Jacobian.
+//!
+//! The field is interpolated trilinearly within each cell, and the zeros are located with
+//! Newton's method in the cells where every velocity component changes sign.
+
+use super::{derived, derived::Jacobian, fieldprovider::FieldProvider};
+use na::{Matrix3, Vector3};
+
+/// The most critical points reported, keeping the ones with the strongest flow around them.
+const MAX_POINTS: usize = 100;
+/// Newton iterations used to locate a zero within a cell.
+const ITERATIONS: usize = 10;
+/// Tolerance, relative to the largest velocity of a cell, for a zero to be accepted.
+const TOLERANCE: f32 = 1e-5;
+/// Tolerance, relative to the largest eigenvalue, below which a part of an eigenvalue is zero.
+const EIGENVALUE_TOLERANCE: f32 = 1e-3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CriticalPointKind {
+    /// Flow leaves in every direction.
+    Source,
+    /// Flow arrives from every direction.
+    Sink,
+    /// Flow arrives along some directions and leaves along others.
+    Saddle,
+    /// Flow circles around the point without approaching or leaving it.
+    Center,
+    /// Flow circles around the point while approaching or leaving it.
+    Spiral,
+}
+
+impl CriticalPointKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            CriticalPointKind::Source => "source",
+            CriticalPointKind::Sink => "sink",
+            CriticalPointKind::Saddle => "saddle",
+            CriticalPointKind::Center => "center",
+            CriticalPointKind::Spiral => "spiral",
+        }
+    }
+
+    /// The color of the markers of this kind.
+    pub fn color(self) -> (f32, f32, f32) {
+        match self {
+            CriticalPointKind::Source => (1.0, 0.3, 0.3),
+            CriticalPointKind::Sink => (0.3, 0.5, 1.0),
+            CriticalPointKind::Saddle => (1.0, 0.9, 0.3),
+            CriticalPointKind::Center => (0.9, 0.4, 1.0),
+            CriticalPointKind::Spiral => (1.0, 0.6, 0.2),
+        }
+    }
+
+    /// Classifies a critical point from the eigenvalues of its Jacobian.
+    /// Returns `None` for degenerate points, where an eigenvalue is zero.
+    fn classify(jacobian: &Jacobian) -> Option<Self> {
+        let eigenvalues = derived::eigenvalues(jacobian);
+        let scale = eigenvalues
+            .iter()
+            .fold(0.0, |max: f32, (re, im)| max.max(re.abs()).max(im.abs()));
+        let tolerance = EIGENVALUE_TOLERANCE * scale;
+        let is_zero = |value: f32| value.abs() <= tolerance;
+        if scale == 0.0
+            || eigenvalues
+                .iter()
+                .any(|&(re, im)| is_zero(re) && is_zero(im))
+        {
+            return None;
+        }
+
+        let kind = match eigenvalues.iter().find(|&&(_, im)| !is_zero(im)) {
+            Some(&(re, _)) if is_zero(re) => CriticalPointKind::Center,
+            Some(_) => CriticalPointKind::Spiral,
+            None if eigenvalues.iter().all(|&(re, _)| re > 0.0) => CriticalPointKind::Source,
+            None if eigenvalues.iter().all(|&(re, _)| re < 0.0) => CriticalPointKind::Sink,
+            None => CriticalPointKind::Saddle,
+        };
+        Some(kind)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CriticalPoint {
+    /// The position in world space.
+    pub position: (f32, f32, f32),
+    pub kind: CriticalPointKind,
+}
+
+/// Finds the critical points of the current frame of a field.
+///
+/// Cells where every corner is zero are background and skipped, as are degenerate points
+/// such as the lines of zeros in flows without a z component.
+pub fn find_critical_points(field: &FieldProvider) -> Vec<CriticalPoint> {
+    let transform = field.transform();
+    let derivatives = transform.world_to_voxel_derivatives();
+    let cells = |size: usize| 0..size.saturating_sub(1);
+
+    let mut found = Vec::new();
+    for z in cells(field.depth) {
+        for y in cells(field.height) {
+            for x in cells(field.width) {
+                let corners = cell_corners(field, (x, y, z));
+                let t = match locate_zero(&corners) {
+                    Some(t) => t,
+                    None => continue,
+                };
+                let jacobian = derived::to_world(&gradient(&corners, t), &derivatives);
+                if let Some(kind) = CriticalPointKind::classify(&jacobian) {
+                    let position = (x as f32 + t[0], y as f32 + t[1], z as f32 + t[2]);
+                    let point = CriticalPoint {
+                        position: transform.to_world(position),
+                        kind,
+                    };
+                    found.push((point, determinant(&jacobian).abs()));
+                }
+            }
+        }
+    }
+
+    found.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    found.truncate(MAX_POINTS);
+    found.into_iter().map(|(point, _)| point).collect()
+}
+
+/// The velocities at the corners of a cell, where bit 0, 1 and 2 of the index give the
+/// offset along x, y and z.
+fn cell_corners(field: &FieldProvider, (x, y, z): (usize, usize, usize)) -> [[f32; 3]; 8] {
+    let mut corners = [[0.0; 3]; 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let (dx, dy, dz, w) = field.get_vec((x + (i & 1), y + ((i >> 1) & 1), z + (i >> 2)));
+        *corner = [dx * w, dy * w, dz * w];
+    }
+    corners
+}
+
+/// Locates the zero of the trilinear interpolation of a cell, as offsets within the cell.
+/// Zeros on the upper faces belong to the neighboring cells, so that each is found once.
+fn locate_zero(corners: &[[f32; 3]; 8]) -> Option<[f32; 3]> {
+    // Every component must change sign for the cell to contain a zero.
+    let spans_zero = |c: usize| {
+        let (min, max) = corners.iter().fold((f32::MAX, f32::MIN), |(min, max), v| {
+            (min.min(v[c]), max.max(v[c]))
+        });
+        min <= 0.0 && max >= 0.0
+    };
+    let scale = corners
+        .iter()
+        .map(|v| v.iter().map(|c| c * c).sum::<f32>())
+        .fold(0.0, f32::max)
+        .sqrt();
+    if scale == 0.0 || !(0..3).all(spans_zero) {
+        return None;
+    }
+
+    let mut t = [0.5; 3];
+    for _ in 0..ITERATIONS {
+        let value = Vector3::from(interpolate(corners, t));
+        if value.norm() <= TOLERANCE * scale {
+            let inside = t.iter().all(|t| (-TOLERANCE..1.0 - TOLERANCE).contains(t));
+            return if inside { Some(t) } else { None };
+        }
+        let j = gradient(corners, t);
+        let step = Matrix3::from_fn(|r, c| j[r][c]).try_inverse()? * value;
+        for (t, step) in t.iter_mut().zip(step.iter()) {
+            *t -= step;
+        }
+        if t.iter().any(|t| !(-0.5..=1.5).contains(t)) {
+            return None;
+        }
+    }
+    None
+}
+
+/// The trilinear weights of the corners of a cell, and their derivatives along each axis.
+fn weights(t: [f32; 3]) -> [(f32, [f32; 3]); 8] {
+    let mut weights = [(0.0, [0.0; 3]); 8];
+    for (i, (weight, derivative)) in weights.iter_mut().enumerate() {
+        // The weight along each axis, and its derivative.
+        let axis = |a: usize| {
+            if (i >> a) & 1 == 1 {
+                (t[a], 1.0)
+            } else {
+                (1.0 - t[a], -1.0)
+            }
+        };
+        let (x, y, z) = (axis(0), axis(1), axis(2));
+        *weight = x.0 * y.0 * z.0;
+        *derivative = [x.1 * y.0 * z.0, x.0 * y.1 * z.0, x.0 * y.0 * z.1];
+    }
+    weights
+}
+
+fn interpolate(corners: &[[f32; 3]; 8], t: [f32; 3]) -> [f32; 3] {
+    let mut value = [0.0; 3];
+    for (corner, (weight, _)) in corners.iter().zip(weights(t).iter()) {
+        for (v, c) in value.iter_mut().zip(corner.iter()) {
+            *v += c * weight;
+        }
+    }
+    value
+}
+
+/// The gradient of the trilinear interpolation along the voxel axes.
+fn gradient(corners: &[[f32; 3]; 8], t: [f32; 3]) -> Jacobian {
+    let mut jacobian = [[0.0; 3]; 3];
+    for (corner, (_, derivative)) in corners.iter().zip(weights(t).iter()) {
+        for (row, c) in jacobian.iter_mut().zip(corner.iter()) {
+            for (value, d) in row.iter_mut().zip(derivative.iter()) {
+                *value += c * d;
+            }
+        }
+    }
+    jacobian
+}
+
+fn determinant(j: &Jacobian) -> f32 {
+    Matrix3::from_fn(|r, c| j[r][c]).determinant()
+}
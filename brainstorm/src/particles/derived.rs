@@ -0,0 +1,215 @@
+//! Scalar quantities derived from the velocity gradient of a field, for isosurfaces,
+//! particle coloring and the map.
+//!
+//! The gradient is computed with central differences between voxels, or one-sided
+//! differences at the edges, and converted to world space so that anisotropic voxels
+//! are handled correctly.
+
+use super::{fieldprovider::FieldProvider, tensor, ScalarVolume};
+use serde_derive::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// The velocity gradient `J[i][j] = d u_i / d x_j`.
+pub(super) type Jacobian = [[f32; 3]; 3];
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Quantity {
+    /// Net outflow, the trace of the gradient.
+    Divergence,
+    /// Length of the curl of the velocity.
+    Vorticity,
+    /// Velocity dotted with the vorticity, large in swirling flow along its axis.
+    Helicity,
+    /// Where rotation dominates strain, positive inside vortices.
+    QCriterion,
+    /// The middle eigenvalue of `S² + Ω²`, negative inside vortices.
+    Lambda2,
+    /// The real part of the largest eigenvalue of the gradient.
+    LargestEigenvalue,
+    /// The real part of the middle eigenvalue of the gradient.
+    MiddleEigenvalue,
+    /// The real part of the smallest eigenvalue of the gradient.
+    SmallestEigenvalue,
+    /// The imaginary part of the complex eigenvalues of the gradient, zero without swirl.
+    SwirlStrength,
+}
+
+gui_enum!(Quantity, QUANTITIES, "derived quantity", {
+    Divergence => "divergence",
+    Vorticity => "vorticity",
+    Helicity => "helicity",
+    QCriterion => "Q-criterion",
+    Lambda2 => "lambda2",
+    LargestEigenvalue => "largest eigenvalue",
+    MiddleEigenvalue => "middle eigenvalue",
+    SmallestEigenvalue => "smallest eigenvalue",
+    SwirlStrength => "swirl strength",
+});
+
+impl Quantity {
+    /// Computes the quantity for every voxel of the current frame of the field.
+    pub fn compute(self, field: &FieldProvider) -> ScalarVolume {
+        let (width, height, depth) = (field.width, field.height, field.depth);
+        let derivatives = field.transform().world_to_voxel_derivatives();
+        let mut values = Vec::with_capacity(width * height * depth);
+        for z in 0..depth {
+            for y in 0..height {
+                for x in 0..width {
+                    let jacobian = velocity_gradient(field, (x, y, z), &derivatives);
+                    values.push(self.evaluate(velocity(field, (x, y, z)), &jacobian));
+                }
+            }
+        }
+        ScalarVolume::new(width, height, depth, values)
+    }
+
+    /// Evaluates the quantity from the velocity and its gradient at a point.
+    fn evaluate(self, (u, v, w): (f32, f32, f32), j: &Jacobian) -> f32 {
+        let vorticity = (j[2][1] - j[1][2], j[0][2] - j[2][0], j[1][0] - j[0][1]);
+        match self {
+            Quantity::Divergence => j[0][0] + j[1][1] + j[2][2],
+            Quantity::Vorticity => {
+                let (a, b, c) = vorticity;
+                (a * a + b * b + c * c).sqrt()
+            }
+            Quantity::Helicity => {
+                let (a, b, c) = vorticity;
+                u * a + v * b + w * c
+            }
+            Quantity::QCriterion => {
+                let (strain, rotation) = split(j);
+                let norm = |m: &Jacobian| m.iter().flatten().map(|v| v * v).sum::<f32>();
+                0.5 * (norm(&rotation) - norm(&strain))
+            }
+            Quantity::Lambda2 => {
+                let (s, o) = split(j);
+                let m = |a: usize, b: usize| {
+                    (0..3)
+                        .map(|k| s[a][k] * s[k][b] + o[a][k] * o[k][b])
+                        .sum::<f32>()
+                };
+                let mut values =
+                    tensor::eigenvalues(&[m(0, 0), m(0, 1), m(0, 2), m(1, 1), m(1, 2), m(2, 2)]);
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                values[1] as f32
+            }
+            Quantity::LargestEigenvalue => eigenvalues(j)[0].0,
+            Quantity::MiddleEigenvalue => eigenvalues(j)[1].0,
+            Quantity::SmallestEigenvalue => eigenvalues(j)[2].0,
+            Quantity::SwirlStrength => {
+                let values = eigenvalues(j);
+                values.iter().fold(0.0, |max, value| value.1.abs().max(max))
+            }
+        }
+    }
+}
+
+/// The velocity of a voxel, with the direction scaled by the magnitude.
+fn velocity(field: &FieldProvider, position: (usize, usize, usize)) -> (f32, f32, f32) {
+    let (x, y, z, w) = field.get_vec(position);
+    (x * w, y * w, z * w)
+}
+
+/// The velocity gradient at a voxel with respect to world space.
+fn velocity_gradient(
+    field: &FieldProvider,
+    (x, y, z): (usize, usize, usize),
+    derivatives: &[[f32; 3]; 3],
+) -> Jacobian {
+    let size = [field.width, field.height, field.depth];
+    let index = [x, y, z];
+
+    // Derivatives along the voxel axes, as columns.
+    let mut voxel = [[0.0; 3]; 3];
+    for axis in 0..3 {
+        let lower = index[axis].saturating_sub(1);
+        let upper = (index[axis] + 1).min(size[axis] - 1);
+        if lower == upper {
+            continue;
+        }
+        let at = |i| {
+            let mut position = index;
+            position[axis] = i;
+            velocity(field, (position[0], position[1], position[2]))
+        };
+        let (a, b) = (at(lower), at(upper));
+        let step = (upper - lower) as f32;
+        voxel[0][axis] = (b.0 - a.0) / step;
+        voxel[1][axis] = (b.1 - a.1) / step;
+        voxel[2][axis] = (b.2 - a.2) / step;
+    }
+
+    to_world(&voxel, derivatives)
+}
+
+/// Converts a gradient along the voxel axes to world space, given the derivatives of the
+/// voxel indices with respect to world space.
+pub(super) fn to_world(voxel: &Jacobian, derivatives: &[[f32; 3]; 3]) -> Jacobian {
+    // Chain rule: d u / d world = d u / d voxel * d voxel / d world.
+    let mut jacobian = [[0.0; 3]; 3];
+    for (i, row) in jacobian.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| voxel[i][k] * derivatives[k][j]).sum();
+        }
+    }
+    jacobian
+}
+
+/// Splits a gradient into its symmetric strain rate and antisymmetric rotation tensors.
+fn split(j: &Jacobian) -> (Jacobian, Jacobian) {
+    let mut strain = [[0.0; 3]; 3];
+    let mut rotation = [[0.0; 3]; 3];
+    for a in 0..3 {
+        for b in 0..3 {
+            strain[a][b] = 0.5 * (j[a][b] + j[b][a]);
+            rotation[a][b] = 0.5 * (j[a][b] - j[b][a]);
+        }
+    }
+    (strain, rotation)
+}
+
+/// The eigenvalues of a gradient as (real, imaginary) pairs, sorted by descending real part.
+/// Complex eigenvalues come in a conjugate pair, with the positive imaginary part first.
+pub(super) fn eigenvalues(j: &Jacobian) -> [(f32, f32); 3] {
+    let m = |a: usize, b: usize| f64::from(j[a][b]);
+    // Coefficients of the characteristic polynomial λ³ + aλ² + bλ + c.
+    let trace = m(0, 0) + m(1, 1) + m(2, 2);
+    let minors = m(0, 0) * m(1, 1) - m(0, 1) * m(1, 0) + m(0, 0) * m(2, 2) - m(0, 2) * m(2, 0)
+        + m(1, 1) * m(2, 2)
+        - m(1, 2) * m(2, 1);
+    let determinant = m(0, 0) * (m(1, 1) * m(2, 2) - m(1, 2) * m(2, 1))
+        - m(0, 1) * (m(1, 0) * m(2, 2) - m(1, 2) * m(2, 0))
+        + m(0, 2) * (m(1, 0) * m(2, 1) - m(1, 1) * m(2, 0));
+    let (a, b, c) = (-trace, minors, -determinant);
+
+    // Substituting λ = t - a / 3 gives the depressed cubic t³ + pt + q.
+    let shift = -a / 3.0;
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let discriminant = (q / 2.0).powi(2) + (p / 3.0).powi(3);
+
+    let mut roots = if discriminant > 0.0 {
+        // One real root and a complex conjugate pair.
+        let root = discriminant.sqrt();
+        let u = (-q / 2.0 + root).cbrt();
+        let v = (-q / 2.0 - root).cbrt();
+        let real = -(u + v) / 2.0 + shift;
+        let imaginary = (u - v).abs() * 3f64.sqrt() / 2.0;
+        [(u + v + shift, 0.0), (real, imaginary), (real, -imaginary)]
+    } else if p == 0.0 {
+        [(shift, 0.0); 3]
+    } else {
+        // Three real roots.
+        let r = (-p / 3.0).sqrt();
+        let phi = (-q / (2.0 * r * r * r)).clamp(-1.0, 1.0).acos();
+        let root = |k: f64| (2.0 * r * ((phi - 2.0 * PI * k) / 3.0).cos() + shift, 0.0);
+        [root(0.0), root(1.0), root(2.0)]
+    };
+    roots.sort_by(|x, y| {
+        (y.0, y.1)
+            .partial_cmp(&(x.0, x.1))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let f = |(re, im): (f64, f64)| (re as f32, im as f32);
+    [f(roots[0]), f(roots[1]), f(roots[2])]
+}
@@ -0,0 +1,74 @@
+//! Emitters spawning particles, each with its own seeding region and particle settings.
+
+use super::seeding::{Axis, SeedRegion, SeedShape};
+use serde_derive::{Deserialize, Serialize};
+
+type Vector3 = (f32, f32, f32);
+
+/// The most emitters at once, which the GPU particle shaders are compiled for.
+pub const MAX_EMITTERS: usize = 8;
+
+/// The colors offered for emitters, with each new emitter taking the next one.
+pub const EMITTER_COLORS: [Vector3; MAX_EMITTERS] = [
+    (1.0, 1.0, 1.0),
+    (1.0, 0.55, 0.1),
+    (0.1, 0.8, 1.0),
+    (1.0, 0.3, 0.9),
+    (1.0, 0.9, 0.2),
+    (0.3, 1.0, 0.3),
+    (1.0, 0.25, 0.25),
+    (0.35, 0.45, 1.0),
+];
+
+/// A place where particles spawn, and how the particles it spawns behave.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Emitter {
+    /// Center of the seeding region, in world space.
+    pub position: Vector3,
+    pub shape: SeedShape,
+    /// The axis of the rake, and the normal of the seeding plane and disk.
+    pub axis: Axis,
+    /// Size of the seeding region, from 0 to 1.
+    pub size: f32,
+    /// CPU particles respawned per tick, for every `DEFAULT_PARTICLE_COUNT` of its particles.
+    pub spawn_rate: u32,
+    /// Ticks a particle lives.
+    pub lifetime: f32,
+    /// Speed of the particles, relative to the overall speed.
+    pub speed: f32,
+    /// Tint of the particles.
+    pub color: Vector3,
+}
+
+impl Emitter {
+    /// Creates an emitter with the default settings.
+    pub fn new(position: Vector3, color: Vector3) -> Self {
+        Emitter {
+            position,
+            shape: SeedShape::default(),
+            axis: Axis::default(),
+            size: 1.0,
+            spawn_rate: 1000,
+            lifetime: 100.0,
+            speed: 1.0,
+            color,
+        }
+    }
+
+    /// The seeding region placed at the emitter.
+    pub fn region(&self) -> SeedRegion {
+        SeedRegion {
+            shape: self.shape,
+            axis: self.axis,
+            center: self.position,
+            radius: self.size * 0.6 + 0.01,
+        }
+    }
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self::new((0.0, 0.0, 0.0), EMITTER_COLORS[0])
+    }
+}
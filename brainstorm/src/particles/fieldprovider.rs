@@ -1,121 +1,156 @@
-use super::{Vector4, VectorField};
+use super::interpolation::lerp;
+use super::{EmptyCells, FieldSequence, Interpolation, Metadata, Vector4, WorldTransform};
 use std::f32;
 
-fn lerpf(a: f32, b: f32, t: f32) -> f32 {
-    a * (1.0 - t) + b * t
-}
-
-fn lerp((ax, ay, az, afa): Vector4, (bx, by, bz, bfa): Vector4, t: f32) -> Vector4 {
-    (
-        lerpf(ax, bx, t),
-        lerpf(ay, by, t),
-        lerpf(az, bz, t),
-        lerpf(afa, bfa, t),
-    )
-}
-
-fn lerp2d(lxly: Vector4, lxuy: Vector4, uxly: Vector4, uxuy: Vector4, t1: f32, t2: f32) -> Vector4 {
-    let s = lerp(lxuy, uxuy, t1);
-    let v = lerp(lxly, uxly, t1);
-    lerp(s, v, t2)
-}
-
-#[allow(clippy::too_many_arguments)]
-fn lerp3d(
-    // naming scheme: face <n> <lower|upper>x <lower|upper>y
-    f1lxly: Vector4,
-    f1lxuy: Vector4,
-    f1uxly: Vector4,
-    f1uxuy: Vector4,
-    f2lxly: Vector4,
-    f2lxuy: Vector4,
-    f2uxly: Vector4,
-    f2uxuy: Vector4,
-    t1: f32,
-    t2: f32,
-    t3: f32,
-) -> Vector4 {
-    let s = lerp2d(f1lxly, f1lxuy, f1uxly, f1uxuy, t1, t2);
-    let v = lerp2d(f2lxly, f2lxuy, f2uxly, f2uxuy, t1, t2);
-    lerp(s, v, t3)
-}
-
 pub struct FieldProvider {
     pub width: usize,
     pub height: usize,
     pub depth: usize,
-    data: Vec<(f32, f32, f32, f32)>,
+    frames: Vec<Vec<(f32, f32, f32, f32)>>,
     directional: Vec<(f32, f32, f32)>,
+    transform: WorldTransform,
+    metadata: Metadata,
+    time_step: f32,
+    max_velocity: f32,
+    /// The current time, in frames.
+    time: f32,
+    interpolation: Interpolation,
+    empty_cells: EmptyCells,
 }
 
 impl FieldProvider {
-    pub fn get_vec(&self, (fx, fy, fz): (usize, usize, usize)) -> (f32, f32, f32, f32) {
+    pub fn get_vec(&self, position: (usize, usize, usize)) -> (f32, f32, f32, f32) {
+        self.get_vec_in(self.frame(), position)
+    }
+
+    fn get_vec_in(&self, frame: usize, (fx, fy, fz): (usize, usize, usize)) -> Vector4 {
         if fx >= self.width || fy >= self.height || fz >= self.depth {
             return (0.0, 0.0, 0.0, 0.0);
         }
-        self.get(fx, fy, fz)
+        self.get_in(frame, fx, fy, fz)
     }
 
-    pub fn get(&self, x: usize, y: usize, z: usize) -> (f32, f32, f32, f32) {
-        self.data[x + y * self.width + z * self.width * self.height]
+    fn get_in(&self, frame: usize, x: usize, y: usize, z: usize) -> Vector4 {
+        self.frames[frame][x + y * self.width + z * self.width * self.height]
     }
 
-    pub fn new(x: VectorField) -> Self {
-        let mut data: Vec<(f32, f32, f32, f32)> = Vec::new();
-        for plane in x.vectors {
-            for row in plane {
-                for elem in row {
-                    data.push(elem);
-                }
+    pub fn new(fields: FieldSequence) -> Self {
+        let time_step = fields.time_step();
+        let first = fields.first();
+        let transform = first.world_transform();
+        let (width, height, depth) = first.dimensions();
+        let directional = first.directional.clone();
+        let metadata = first.metadata.clone();
+
+        let mut max_velocity: f32 = 0.0;
+        let mut frames = Vec::new();
+        for field in fields.frames {
+            let data = field.data();
+            for (dx, dy, dz, fa) in &data {
+                let velocity = ((dx * fa).powi(2) + (dy * fa).powi(2) + (dz * fa).powi(2)).sqrt();
+                max_velocity = max_velocity.max(velocity);
             }
+            frames.push(data);
         }
+
         FieldProvider {
-            width: x.width,
-            height: x.height,
-            depth: x.depth,
-            data,
-            directional: x.directional,
+            width,
+            height,
+            depth,
+            frames,
+            directional,
+            transform,
+            metadata,
+            time_step,
+            max_velocity,
+            time: 0.0,
+            interpolation: Interpolation::default(),
+            empty_cells: EmptyCells::default(),
         }
     }
 
-    pub fn delta(&self, (x, y, z): (f32, f32, f32)) -> (f32, f32, f32, f32) {
-        let x = x * (self.width as f32) + (self.width as f32) / 2.0;
-        let y = y * (self.height as f32) + (self.height as f32) / 2.0;
-        let z = z * (self.depth as f32) + (self.depth as f32) / 2.0;
-        let lx = x.floor() as usize;
-        let ly = y.floor() as usize;
-        let lz = z.floor() as usize;
-        let ux = x.ceil() as usize;
-        let uy = y.ceil() as usize;
-        let uz = z.ceil() as usize;
-        let v1 = self.get_vec((lx, ly, lz)); // lower depth
-        let v2 = self.get_vec((lx, uy, lz)); // lower depth
-        let v3 = self.get_vec((ux, ly, lz)); // lower depth
-        let v4 = self.get_vec((ux, uy, lz)); // lower depth
-        let v5 = self.get_vec((lx, ly, uz)); // upper depth
-        let v6 = self.get_vec((lx, uy, uz)); // upper depth
-        let v7 = self.get_vec((ux, ly, uz)); // upper depth
-        let v8 = self.get_vec((ux, uy, uz)); // upper depth
+    /// Sets the current time, in frames. Sampling interpolates between the adjacent frames.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.max(0.0).min((self.frames.len() - 1) as f32);
+    }
 
-        // remove noise
-        if v1 == (0.0, 0.0, 0.0, 0.0)
-            && v2 == (0.0, 0.0, 0.0, 0.0)
-            && v3 == (0.0, 0.0, 0.0, 0.0)
-            && v4 == (0.0, 0.0, 0.0, 0.0)
-            && v5 == (0.0, 0.0, 0.0, 0.0)
-            && v6 == (0.0, 0.0, 0.0, 0.0)
-            && v7 == (0.0, 0.0, 0.0, 0.0)
-            && v8 == (0.0, 0.0, 0.0, 0.0)
-        {
+    /// The frame at or before the current time.
+    pub fn frame(&self) -> usize {
+        self.time.floor() as usize
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Time between two frames, in the same unit as the particle advection.
+    pub fn time_step(&self) -> f32 {
+        self.time_step
+    }
+
+    /// The largest velocity in any frame, used for the high- and low-pass filters.
+    pub fn max_velocity(&self) -> f32 {
+        self.max_velocity
+    }
+
+    /// Samples the field at a position in world space and the current time.
+    pub fn delta(&self, position: (f32, f32, f32)) -> (f32, f32, f32, f32) {
+        self.sample(position, None)
+    }
+
+    /// Samples the field like `delta`, with the vector of every voxel flipped where needed to
+    /// point the same way as `reference`. For fields of orientations without a sign, such as
+    /// the eigenvectors of diffusion tensors, where opposite vectors would cancel out.
+    pub fn axis(&self, position: (f32, f32, f32), reference: (f32, f32, f32)) -> Vector4 {
+        self.sample(position, Some(reference))
+    }
+
+    fn sample(&self, position: (f32, f32, f32), reference: Option<(f32, f32, f32)>) -> Vector4 {
+        let frame = self.frame();
+        let t = self.time - frame as f32;
+        let current = self.delta_in(frame, position, reference);
+        if t <= 0.0 || frame + 1 >= self.frames.len() {
+            return current;
+        }
+        lerp(current, self.delta_in(frame + 1, position, reference), t)
+    }
+
+    fn delta_in(
+        &self,
+        frame: usize,
+        position: (f32, f32, f32),
+        reference: Option<(f32, f32, f32)>,
+    ) -> Vector4 {
+        let (x, y, z) = self.transform.to_voxel(position);
+        let (mx, my, mz) = (
+            (self.width - 1) as f32,
+            (self.height - 1) as f32,
+            (self.depth - 1) as f32,
+        );
+        if x < 0.0 || y < 0.0 || z < 0.0 || x > mx || y > my || z > mz {
             return (f32::NAN, f32::NAN, f32::NAN, f32::NAN);
         }
+        let get = |x: isize, y: isize, z: isize| {
+            if x < 0 || y < 0 || z < 0 {
+                return (0.0, 0.0, 0.0, 0.0);
+            }
+            let (dx, dy, dz, w) = self.get_vec_in(frame, (x as usize, y as usize, z as usize));
+            match reference {
+                Some((rx, ry, rz)) if dx * rx + dy * ry + dz * rz < 0.0 => (-dx, -dy, -dz, w),
+                _ => (dx, dy, dz, w),
+            }
+        };
 
-        let t1 = x - x.floor();
-        let t2 = y - y.floor();
-        let t3 = z - z.floor();
+        // remove noise
+        if self.empty_cells == EmptyCells::Kill {
+            let (lx, ly, lz) = (x as isize, y as isize, z as isize);
+            let corner = |i: isize| get(lx + (i & 1), ly + ((i >> 1) & 1), lz + (i >> 2));
+            if (0..8).map(corner).all(|v| v == (0.0, 0.0, 0.0, 0.0)) {
+                return (f32::NAN, f32::NAN, f32::NAN, f32::NAN);
+            }
+        }
 
-        let (rx, ry, rz, ra) = lerp3d(v1, v2, v3, v4, v5, v6, v7, v8, t1, t2, t3);
-        (rx, ry, rz, ra)
+        self.interpolation.sample(get, (x, y, z))
     }
 
     pub fn get_len(&self, v: (usize, usize, usize)) -> f32 {
@@ -123,11 +158,26 @@ impl FieldProvider {
         dt.3
     }
 
-    pub fn data(&self) -> &[(f32, f32, f32, f32)] {
-        &self.data
+    /// Sets how the field is reconstructed between voxels.
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+
+    /// Sets what to do in cells where every corner is zero.
+    pub fn set_empty_cells(&mut self, empty_cells: EmptyCells) {
+        self.empty_cells = empty_cells;
     }
 
     pub fn directional(&self) -> &[(f32, f32, f32)] {
         &self.directional
     }
+
+    /// The mapping between voxel indices and world space.
+    pub fn transform(&self) -> &WorldTransform {
+        &self.transform
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
 }
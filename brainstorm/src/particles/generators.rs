@@ -0,0 +1,368 @@
+//! Procedural vector fields, used to demo the application without data files
+//! and to validate the particle advection against known flows.
+
+use super::{Affine, FieldSequence, Metadata, VectorField};
+use std::{f32::consts::PI, fmt, str::FromStr};
+
+type Vector3 = (f32, f32, f32);
+
+/// Number of frames generated for one period of a time-varying flow.
+const FRAMES_PER_PERIOD: usize = 20;
+
+/// An analytic flow together with its parameters.
+///
+/// Parsed from strings of the form `name` or `name:key=value,key=value`,
+/// such as `lorenz:rho=28,sigma=10`. Parameters that are left out use the classic values.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Generator {
+    /// Arnold-Beltrami-Childress flow on the periodic cube `[0, 2π]³`.
+    Abc { a: f32, b: f32, c: f32 },
+    /// The time-periodic double gyre on `[0, 2] x [0, 1]`, extruded along z.
+    DoubleGyre {
+        amplitude: f32,
+        epsilon: f32,
+        period: f32,
+    },
+    /// The Lorenz system, used as a velocity field around its attractor.
+    Lorenz { sigma: f32, rho: f32, beta: f32 },
+    /// Hill's spherical vortex in the frame moving with the vortex, along the z axis.
+    HillsVortex { radius: f32, velocity: f32 },
+    /// A point source and a point sink of equal strength on the x axis.
+    SourceSink { strength: f32, separation: f32 },
+    /// The same velocity everywhere.
+    Uniform { x: f32, y: f32, z: f32 },
+}
+
+impl Generator {
+    /// Every flow with its default parameters.
+    fn all_defaults() -> Vec<Self> {
+        vec![
+            Generator::Abc {
+                a: 3f32.sqrt(),
+                b: 2f32.sqrt(),
+                c: 1.0,
+            },
+            Generator::DoubleGyre {
+                amplitude: 0.1,
+                epsilon: 0.25,
+                period: 10.0,
+            },
+            Generator::Lorenz {
+                sigma: 10.0,
+                rho: 28.0,
+                beta: 8.0 / 3.0,
+            },
+            Generator::HillsVortex {
+                radius: 1.0,
+                velocity: 1.0,
+            },
+            Generator::SourceSink {
+                strength: 1.0,
+                separation: 1.0,
+            },
+            Generator::Uniform {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        ]
+    }
+
+    /// Creates the named flow with its default parameters.
+    pub fn with_defaults(name: &str) -> Result<Self, String> {
+        let defaults = Generator::all_defaults();
+        match defaults
+            .iter()
+            .position(|generator| generator.name() == name)
+        {
+            Some(index) => Ok(defaults[index].clone()),
+            None => {
+                let names: Vec<_> = defaults.iter().map(Generator::name).collect();
+                Err(format!(
+                    "Unknown field generator {}, expected one of: {}.",
+                    name,
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Generator::Abc { .. } => "abc",
+            Generator::DoubleGyre { .. } => "double-gyre",
+            Generator::Lorenz { .. } => "lorenz",
+            Generator::HillsVortex { .. } => "hills-vortex",
+            Generator::SourceSink { .. } => "source-sink",
+            Generator::Uniform { .. } => "uniform",
+        }
+    }
+
+    /// The parameters of the flow, by name.
+    fn parameters(&self) -> Vec<(&'static str, f32)> {
+        let mut generator = self.clone();
+        let parameters = generator
+            .parameters_mut()
+            .into_iter()
+            .map(|(name, value)| (name, *value))
+            .collect();
+        parameters
+    }
+
+    fn parameters_mut(&mut self) -> Vec<(&'static str, &mut f32)> {
+        match self {
+            Generator::Abc { a, b, c } => vec![("a", a), ("b", b), ("c", c)],
+            Generator::DoubleGyre {
+                amplitude,
+                epsilon,
+                period,
+            } => vec![
+                ("amplitude", amplitude),
+                ("epsilon", epsilon),
+                ("period", period),
+            ],
+            Generator::Lorenz { sigma, rho, beta } => {
+                vec![("sigma", sigma), ("rho", rho), ("beta", beta)]
+            }
+            Generator::HillsVortex { radius, velocity } => {
+                vec![("radius", radius), ("velocity", velocity)]
+            }
+            Generator::SourceSink {
+                strength,
+                separation,
+            } => vec![("strength", strength), ("separation", separation)],
+            Generator::Uniform { x, y, z } => vec![("x", x), ("y", y), ("z", z)],
+        }
+    }
+
+    /// The physical region covered by the generated field.
+    fn domain(&self) -> (Vector3, Vector3) {
+        match *self {
+            Generator::Abc { .. } => ((0.0, 0.0, 0.0), (2.0 * PI, 2.0 * PI, 2.0 * PI)),
+            Generator::DoubleGyre { .. } => ((0.0, 0.0, 0.0), (2.0, 1.0, 0.25)),
+            Generator::Lorenz { .. } => ((-25.0, -25.0, 0.0), (25.0, 25.0, 50.0)),
+            Generator::HillsVortex { radius: r, .. } => {
+                ((-2.0 * r, -2.0 * r, -2.0 * r), (2.0 * r, 2.0 * r, 2.0 * r))
+            }
+            Generator::SourceSink { separation: s, .. } => ((-s, -s, -s), (s, s, s)),
+            Generator::Uniform { .. } => ((-1.0, -1.0, -1.0), (1.0, 1.0, 1.0)),
+        }
+    }
+
+    /// The times at which frames are generated, and the time between them.
+    fn times(&self) -> (Vec<f32>, f32) {
+        match *self {
+            Generator::DoubleGyre { period, .. } => {
+                let step = period / FRAMES_PER_PERIOD as f32;
+                // The last frame equals the first, so looping playback is seamless.
+                let times = (0..=FRAMES_PER_PERIOD).map(|i| i as f32 * step).collect();
+                (times, step)
+            }
+            _ => (vec![0.0], 1.0),
+        }
+    }
+
+    /// The velocity at a physical position and time.
+    /// `min_distance` keeps singular flows finite close to their singularities.
+    pub fn velocity(&self, (x, y, z): Vector3, t: f32, min_distance: f32) -> Vector3 {
+        match *self {
+            Generator::Abc { a, b, c } => (
+                a * z.sin() + c * y.cos(),
+                b * x.sin() + a * z.cos(),
+                c * y.sin() + b * x.cos(),
+            ),
+            Generator::DoubleGyre {
+                amplitude,
+                epsilon,
+                period,
+            } => {
+                let s = epsilon * (2.0 * PI * t / period).sin();
+                let f = s * x * x + (1.0 - 2.0 * s) * x;
+                let df = 2.0 * s * x + 1.0 - 2.0 * s;
+                (
+                    -PI * amplitude * (PI * f).sin() * (PI * y).cos(),
+                    PI * amplitude * (PI * f).cos() * (PI * y).sin() * df,
+                    0.0,
+                )
+            }
+            Generator::Lorenz { sigma, rho, beta } => {
+                (sigma * (y - x), x * (rho - z) - y, x * y - beta * z)
+            }
+            Generator::HillsVortex { radius, velocity } => {
+                let a2 = radius * radius;
+                let rho2 = x * x + y * y;
+                let r2 = rho2 + z * z;
+                if r2 < a2 {
+                    let radial = 1.5 * velocity * z / a2;
+                    (
+                        radial * x,
+                        radial * y,
+                        1.5 * velocity * (1.0 - (2.0 * rho2 + z * z) / a2),
+                    )
+                } else {
+                    let a3 = a2 * radius;
+                    let r3 = r2 * r2.sqrt();
+                    let r5 = r3 * r2;
+                    let radial = 1.5 * velocity * a3 * z / r5;
+                    (
+                        radial * x,
+                        radial * y,
+                        velocity * (a3 / r3 - 1.0) - 1.5 * velocity * a3 * rho2 / r5,
+                    )
+                }
+            }
+            Generator::SourceSink {
+                strength,
+                separation,
+            } => {
+                let pole = |px: f32| {
+                    let (dx, dy, dz) = (x - px, y, z);
+                    let distance = (dx * dx + dy * dy + dz * dz).sqrt().max(min_distance);
+                    let scale = strength / (4.0 * PI * distance.powi(3));
+                    (dx * scale, dy * scale, dz * scale)
+                };
+                let source = pole(-separation / 2.0);
+                let sink = pole(separation / 2.0);
+                (source.0 - sink.0, source.1 - sink.1, source.2 - sink.2)
+            }
+            Generator::Uniform { x, y, z } => (x, y, z),
+        }
+    }
+
+    /// Samples the flow on a grid whose largest side has `size` voxels.
+    ///
+    /// Like the other fields, the vectors are stored as unit directions,
+    /// with the speed relative to the fastest voxel of all frames as the fourth channel.
+    pub fn generate(&self, size: usize) -> FieldSequence {
+        let (min, max) = self.domain();
+        let extent = (max.0 - min.0, max.1 - min.1, max.2 - min.2);
+        let largest = extent.0.max(extent.1).max(extent.2);
+        let spacing = largest / (size.max(2) - 1) as f32;
+        let voxels = |e: f32| ((e / spacing).round() as usize + 1).max(2);
+        let (width, height, depth) = (voxels(extent.0), voxels(extent.1), voxels(extent.2));
+
+        let (times, time_step) = self.times();
+        let samples: Vec<Vec<Vector3>> = times
+            .iter()
+            .map(|&t| {
+                let mut frame = Vec::with_capacity(width * height * depth);
+                for z in 0..depth {
+                    for y in 0..height {
+                        for x in 0..width {
+                            let position = (
+                                min.0 + x as f32 * spacing,
+                                min.1 + y as f32 * spacing,
+                                min.2 + z as f32 * spacing,
+                            );
+                            frame.push(self.velocity(position, t, spacing));
+                        }
+                    }
+                }
+                frame
+            })
+            .collect();
+
+        let length = |(x, y, z): Vector3| (x * x + y * y + z * z).sqrt();
+        let max_speed = samples
+            .iter()
+            .flatten()
+            .map(|&v| length(v))
+            .fold(0.0, f32::max);
+
+        let affine = Affine {
+            origin: min,
+            directions: [
+                (spacing, 0.0, 0.0),
+                (0.0, spacing, 0.0),
+                (0.0, 0.0, spacing),
+            ],
+        };
+        let frames = samples
+            .into_iter()
+            .map(|frame| {
+                let data = frame
+                    .into_iter()
+                    .map(|v| match length(v) {
+                        len if len > 0.0 => (v.0 / len, v.1 / len, v.2 / len, len / max_speed),
+                        _ => (0.0, 0.0, 0.0, 0.0),
+                    })
+                    .collect();
+                let mut field = VectorField::new(width, height, depth, data);
+                field.set_affine(affine.clone());
+                field
+            })
+            .collect();
+
+        let mut fields = FieldSequence { frames, time_step };
+        fields.first_mut().set_metadata(Metadata {
+            name: Some(self.name().to_owned()),
+            notes: Some(format!("Generated as {} with {} voxels.", self, size)),
+            ..Metadata::default()
+        });
+        fields
+    }
+}
+
+impl FromStr for Generator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut parts = s.splitn(2, ':');
+        let mut generator = Generator::with_defaults(parts.next().unwrap_or("").trim())?;
+
+        for assignment in parts.next().unwrap_or("").split(',') {
+            if assignment.trim().is_empty() {
+                continue;
+            }
+            let mut pair = assignment.splitn(2, '=');
+            let key = pair.next().unwrap_or("").trim();
+            let value: f32 = pair
+                .next()
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|v: &f32| v.is_finite())
+                .ok_or_else(|| format!("Expected a number for parameter {}.", key))?;
+
+            let name = generator.name();
+            let mut parameters = generator.parameters_mut();
+            let known: Vec<_> = parameters.iter().map(|(name, _)| *name).collect();
+            let parameter = parameters
+                .iter_mut()
+                .find(|(name, _)| *name == key)
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown parameter {} for {}, expected one of: {}.",
+                        key,
+                        name,
+                        known.join(", ")
+                    )
+                })?;
+            *parameter.1 = value;
+        }
+
+        let invalid = match generator {
+            Generator::DoubleGyre { period, .. } if period <= 0.0 => Some("period"),
+            Generator::HillsVortex { radius, .. } if radius <= 0.0 => Some("radius"),
+            Generator::SourceSink { separation, .. } if separation <= 0.0 => Some("separation"),
+            _ => None,
+        };
+        match invalid {
+            Some(key) => Err(format!(
+                "Parameter {} of {} must be positive.",
+                key,
+                generator.name()
+            )),
+            None => Ok(generator),
+        }
+    }
+}
+
+impl fmt::Display for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parameters: Vec<_> = self
+            .parameters()
+            .into_iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        write!(f, "{}:{}", self.name(), parameters.join(","))
+    }
+}
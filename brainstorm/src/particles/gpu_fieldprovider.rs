@@ -1,59 +1,138 @@
-use super::VectorField;
+use super::{FieldSequence, VectorField, WorldTransform};
 use gl_bindings::{Texture, TextureFormat};
 use std::{f32, rc::Rc};
 
 pub struct GPUFieldProvider {
-    texture: Rc<Texture>,
+    textures: Vec<Rc<Texture>>,
+    transform: WorldTransform,
+    min: f32,
+    max: f32,
+    max_velocity: f32,
+    max_channel: f32,
 }
 
 impl GPUFieldProvider {
-    pub fn new(x: &VectorField) -> Self {
+    pub fn new(fields: &FieldSequence) -> Self {
         let mut max: f32 = 0.0;
         let mut min: f32 = 0.0;
-        for plane in x.vectors.iter() {
-            for row in plane {
-                for elem in row {
-                    let (dx, dy, dz, _) = elem;
-                    max = max.max(*dy);
-                    max = max.max(*dx);
-                    max = max.max(*dz);
-                    min = min.min(*dx);
-                    min = min.min(*dy);
-                    min = min.min(*dz);
+        let mut max_velocity: f32 = 0.0;
+        let mut max_channel: f32 = 0.0;
+        for x in fields.frames() {
+            for plane in x.vectors.iter() {
+                for row in plane {
+                    for elem in row {
+                        let (dx, dy, dz, da) = elem;
+                        max = max.max(*dy);
+                        max = max.max(*dx);
+                        max = max.max(*dz);
+                        min = min.min(*dx);
+                        min = min.min(*dy);
+                        min = min.min(*dz);
+                        let velocity =
+                            ((dx * da).powi(2) + (dy * da).powi(2) + (dz * da).powi(2)).sqrt();
+                        max_velocity = max_velocity.max(velocity);
+                        max_channel = max_channel.max(da.abs());
+                    }
                 }
             }
         }
 
-        let mut data = Vec::new();
-        for plane in &x.vectors {
-            for row in plane {
-                for elem in row {
-                    let (dx, dy, dz, da) = elem;
+        // All frames share the same range, so they can be interpolated directly.
+        let mut scaled = false;
+        let textures = fields
+            .frames()
+            .iter()
+            .map(|x| {
+                let (texture, is_scaled) = GPUFieldProvider::create_texture(x, min, max);
+                scaled |= is_scaled;
+                Rc::new(texture)
+            })
+            .collect();
+        let (min, max) = if scaled { (min, max) } else { (0.0, 1.0) };
 
-                    let dx = (dx - min) / (max - min);
-                    let dy = (dy - min) / (max - min);
-                    let dz = (dz - min) / (max - min);
-
-                    data.push((dx * 255.0).round() as u8);
-                    data.push((dy * 255.0).round() as u8);
-                    data.push((dz * 255.0).round() as u8);
-                    data.push((da * 255.0).round() as u8);
-                }
-            }
-        }
         GPUFieldProvider {
-            texture: Rc::new(Texture::from_3d_data(
-                x.width as u32,
-                x.height as u32,
-                x.depth as u32,
-                TextureFormat::RGBA,
-                &data[..],
-                false,
-            )),
+            textures,
+            transform: fields.first().world_transform(),
+            min,
+            max,
+            max_velocity,
+            max_channel,
         }
     }
 
-    pub fn get_texture(&self) -> Rc<Texture> {
-        self.texture.clone()
+    /// Creates the texture of a frame. Uses the components as they are, in half floats, where
+    /// supported. Otherwise uses 8 bits per component, with the vector components scaled from
+    /// `[min, max]` to `[0, 1]`, which makes small vectors indistinguishable. Returns whether
+    /// the components were scaled.
+    fn create_texture(x: &VectorField, min: f32, max: f32) -> (Texture, bool) {
+        let data: Vec<f32> = x
+            .vectors()
+            .flat_map(|&(dx, dy, dz, da)| vec![dx, dy, dz, da])
+            .collect();
+        let (width, height, depth) = (x.width as u32, x.height as u32, x.depth as u32);
+        if let Some(texture) =
+            Texture::from_3d_data_linear_f(width, height, depth, TextureFormat::RGBA, &data)
+        {
+            return (texture, false);
+        }
+
+        // Without a range, as in a field of zero vectors, every component is at `min`.
+        let scale = |v: f32| {
+            if max > min {
+                (v - min) / (max - min)
+            } else {
+                0.0
+            }
+        };
+        let data: Vec<u8> = data
+            .chunks(4)
+            .flat_map(|v| vec![scale(v[0]), scale(v[1]), scale(v[2]), v[3]])
+            .map(|v| (v * 255.0).round() as u8)
+            .collect();
+        let texture =
+            Texture::from_3d_data(width, height, depth, TextureFormat::RGBA, &data[..], false);
+        (texture, true)
+    }
+
+    /// Returns the texture of the frame at or before `time`, in frames.
+    pub fn get_texture(&self, time: f32) -> Rc<Texture> {
+        self.textures[self.frame(time)].clone()
+    }
+
+    /// Returns the textures of the frames around `time` and the blend factor between them.
+    pub fn get_textures(&self, time: f32) -> (Rc<Texture>, Rc<Texture>, f32) {
+        let frame = self.frame(time);
+        let next = (frame + 1).min(self.textures.len() - 1);
+        let blend = (time - frame as f32).clamp(0.0, 1.0);
+        (
+            self.textures[frame].clone(),
+            self.textures[next].clone(),
+            blend,
+        )
+    }
+
+    fn frame(&self, time: f32) -> usize {
+        (time.max(0.0).floor() as usize).min(self.textures.len() - 1)
+    }
+
+    /// The mapping between texture coordinates and world space.
+    pub fn transform(&self) -> &WorldTransform {
+        &self.transform
+    }
+
+    /// The range of the vector components, which the textures store scaled to [0, 1]. It is
+    /// [0, 1] itself for textures storing the components as they are.
+    pub fn range(&self) -> (f32, f32) {
+        (self.min, self.max)
+    }
+
+    /// The largest velocity in the field, used for the high- and low-pass filters.
+    pub fn max_velocity(&self) -> f32 {
+        self.max_velocity
+    }
+
+    /// The largest absolute value of the fourth channel in the field.
+    pub fn max_channel(&self) -> f32 {
+        self.max_channel
     }
 }
@@ -3,22 +3,23 @@ use gl_bindings::{
     shaders::OurShader, shaders::ShaderAttribute, AbstractContext, Buffer, BufferType, Context,
     FrameBuffer, Texture, TextureFormat,
 };
-use resources::shaders::{
+
+use crate::particles::shaders::{
     GPU_PARTICLES_FRAGMENT_SHADER, GPU_PARTICLES_UPDATE_FRAGMENT_SHADER,
     GPU_PARTICLES_UPDATE_VERTEX_SHADER, GPU_PARTICLES_VERTEX_SHADER,
 };
-
-use crate::particles::gpu_fieldprovider::GPUFieldProvider;
+use crate::particles::{
+    create_rng, gpu_fieldprovider::GPUFieldProvider, ColorMode, Emitter, Integrator, Mask,
+    SeedShape, RAKE_SEEDS,
+};
 
 use na::Matrix4;
 use std::rc::Rc;
 use std::str;
 
-use crate::camera::ArcBall;
 use crate::State;
 
-use rand::rngs::SmallRng;
-use rand::{FromEntropy, Rng};
+use rand::Rng;
 
 const MAXSTREAMLETSIZE: usize = 4;
 
@@ -26,12 +27,20 @@ pub struct GPUParticleEngine {
     texture: Rc<Texture>,
     texture2: Rc<Texture>,
     noise: Rc<Texture>,
+    mask: Option<Rc<Texture>>,
+    overlay: Option<Rc<Texture>>,
+    /// Lookup texture of the colormap, created by `Colormap::create_texture`.
+    colormap: Option<Rc<Texture>>,
+    /// The emitter of each particle.
+    emitters: Rc<Texture>,
+    emitter_count: usize,
     vertices: Buffer<f32>,
     indices: Buffer<u32>,
     shader: Rc<OurShader>,
     update_shader: Rc<OurShader>,
     framebuffer: FrameBuffer,
     layer: i32,
+    frame: i32,
     timer: f32,
     update: bool,
     swap: bool,
@@ -39,34 +48,32 @@ pub struct GPUParticleEngine {
 }
 
 impl GPUParticleEngine {
-    pub fn new(gpu_particle_count: usize) -> Self {
+    /// The noise giving spawn positions and lifetimes is seeded with `seed` if given,
+    /// for reproducible runs.
+    pub fn new(gpu_particle_count: usize, seed: Option<u64>) -> Self {
         let texture_size = gpu_particle_count;
         let mut data = Vec::new();
         let mut particle_data = Vec::new();
-        let mut noise_data = Vec::new();
-        let mut rng = SmallRng::from_entropy();
         let mut index_data = Vec::new();
         let mut index = 0;
         let offset = (texture_size * texture_size) as u32;
         for q in 0..MAXSTREAMLETSIZE {
             for u in 0..(texture_size) {
                 for v in 0..(texture_size) {
+                    // The update shader keeps the age of a particle in w, with 0 marking a
+                    // dead one, so every particle spawns in the seeding region on the first
+                    // update instead of starting at the origin.
+                    data.push(0.0);
                     data.push(0.0);
                     data.push(0.0);
                     data.push(0.0);
-                    data.push(255.0);
 
                     particle_data
                         .push(u as f32 / (texture_size as f32) + 0.5 / texture_size as f32);
                     particle_data
                         .push(v as f32 / (texture_size as f32) + 0.5 / texture_size as f32);
 
-                    if q == 0 {
-                        noise_data.push(rng.gen_range(0.0, 1.0));
-                        noise_data.push(rng.gen_range(0.0, 1.0));
-                        noise_data.push(rng.gen_range(0.0, 1.0));
-                        noise_data.push(rng.gen_range(0.0, 1.0));
-                    } else {
+                    if q != 0 {
                         index_data.push(index - offset);
                         index_data.push(index);
                     }
@@ -134,18 +141,19 @@ impl GPUParticleEngine {
         GPUParticleEngine {
             texture,
             texture2,
-            noise: Rc::new(Texture::from_data(
-                texture_size as u32,
-                texture_size as u32,
-                TextureFormat::RGBA,
-                &noise_data[..],
-            )),
+            noise: Rc::new(noise_texture(texture_size, seed)),
+            mask: None,
+            overlay: None,
+            colormap: None,
+            emitters: Rc::new(emitter_texture(texture_size, 1)),
+            emitter_count: 1,
             vertices,
             indices,
             shader: Rc::new(shader),
             update_shader: Rc::new(update_shader),
             framebuffer,
             layer: 0,
+            frame: 0,
             timer: 0.0,
             update: false,
             swap: false,
@@ -153,7 +161,10 @@ impl GPUParticleEngine {
         }
     }
 
-    pub fn update(&mut self, field_provider: &GPUFieldProvider, state: &State, camera: &ArcBall) {
+    pub fn update(&mut self, field_provider: &GPUFieldProvider, state: &State) {
+        if state.emitters.len() != self.emitter_count {
+            self.set_emitter_count(state.emitters.len());
+        }
         self.timer += 0.004;
         let context = Context::get_context();
         //if self.timer < 1.0 {
@@ -168,15 +179,64 @@ impl GPUParticleEngine {
         self.update_shader
             .uniform1f("u_highpass", state.highpass_filter);
         self.update_shader
-            .uniform1f("u_seedsize", state.seeding_size * 0.6 + 0.01);
-
-        let (cx, cy, cz) = camera.get_target();
+            .uniform1f("u_max_velocity", field_provider.max_velocity());
+        let (min, max) = field_provider.range();
+        self.update_shader.uniform1f("u_min", min);
+        self.update_shader.uniform1f("u_max", max);
+        self.update_shader.uniform_mat4fv(
+            "u_world_to_texture",
+            field_provider.transform().world_to_texture(),
+        );
+        self.update_shader.uniform1i("u_frame", self.frame);
+        self.update_shader
+            .uniform1i("u_use_mask", self.mask.is_some() as i32);
+        self.update_shader.uniform1i(
+            "u_mask_kills",
+            (self.mask.is_some() && state.mask_kills) as i32,
+        );
+        // The adaptive integrator needs a variable number of steps, so RK4 takes its place.
+        let integrator = match state.integrator {
+            Integrator::Euler => 0,
+            Integrator::Midpoint => 1,
+            Integrator::RungeKutta4 | Integrator::DormandPrince => 2,
+        };
+        self.update_shader.uniform1i("u_integrator", integrator);
+        for (i, emitter) in state.emitters.iter().enumerate() {
+            self.set_emitter_uniforms(i, emitter);
+        }
         self.update_shader
-            .uniform3f("u_seedpos", cx + 0.5, cy + 0.5, cz + 0.5);
+            .uniform1i("u_rake_seeds", RAKE_SEEDS as i32);
+        self.update_shader
+            .uniform1f("u_max_channel", field_provider.max_channel());
+        let world_to_texture = field_provider.transform().world_to_texture();
+        self.update_shader.uniform_mat4fv(
+            "u_texture_to_world",
+            world_to_texture
+                .try_inverse()
+                .unwrap_or_else(Matrix4::identity),
+        );
+        self.frame = self.frame.wrapping_add(1);
 
-        self.shader.uniform1f("u_q", state.seeding_size + 0.01);
         self.shader
             .uniform1f("u_transparency", state.particle_transparency);
+        self.shader
+            .uniform1i("u_use_overlay", self.overlay.is_some() as i32);
+        let color_mode = match state.color_mode {
+            ColorMode::Direction => 0,
+            ColorMode::Speed => 1,
+            ColorMode::Age => 2,
+            ColorMode::Emitter => 3,
+            ColorMode::Overlay => 4,
+        };
+        self.shader.uniform1i("u_color_mode", color_mode);
+        self.shader
+            .uniform1f("u_speed", state.speed_multiplier * 0.016);
+        self.shader
+            .uniform1f("u_max_velocity", field_provider.max_velocity());
+        self.shader.uniform_mat4fv(
+            "u_world_to_texture",
+            field_provider.transform().world_to_texture(),
+        );
 
         self.update_shader.uniform1i("u_layer", (self.layer) as i32);
         self.layer = (self.layer + 1) % MAXSTREAMLETSIZE as i32;
@@ -190,10 +250,16 @@ impl GPUParticleEngine {
         self.get_texture()
             .unwrap()
             .activate(Some(&self.update_shader), 0, "uSampler");
-        field_provider
-            .get_texture()
-            .activate(Some(&self.update_shader), 1, "uData");
+        let (data, next, blend) = field_provider.get_textures(state.time);
+        data.activate(Some(&self.update_shader), 1, "uData");
+        next.activate(Some(&self.update_shader), 3, "uDataNext");
+        self.update_shader.uniform1f("u_time_blend", blend);
         self.noise.activate(Some(&self.update_shader), 2, "uNoise");
+        self.emitters
+            .activate(Some(&self.update_shader), 5, "uEmitters");
+        if let Some(mask) = &self.mask {
+            mask.activate(Some(&self.update_shader), 4, "uMask");
+        }
         self.framebuffer.bind();
         self.update_texture().bind();
         self.framebuffer
@@ -213,6 +279,81 @@ impl GPUParticleEngine {
         self.swap = !self.swap;
     }
 
+    /// Starts over with new noise, seeded with `seed` if given, and no particles alive.
+    /// A seeded run is then the same as one started with that seed.
+    pub fn reseed(&mut self, seed: Option<u64>) {
+        self.clear();
+        self.noise = Rc::new(noise_texture(self.texture_size, seed));
+        self.frame = 0;
+    }
+
+    /// Shares the particles evenly between `count` emitters, and starts over with no
+    /// particles alive so that none are left with the settings of another emitter.
+    fn set_emitter_count(&mut self, count: usize) {
+        self.emitters = Rc::new(emitter_texture(self.texture_size, count));
+        self.emitter_count = count;
+        self.clear();
+    }
+
+    /// Kills every particle.
+    fn clear(&mut self) {
+        let size = self.texture_size as u32;
+        let data = vec![0.0; self.texture_size * self.texture_size * MAXSTREAMLETSIZE * 4];
+        let empty = || {
+            let format = TextureFormat::RGBA;
+            Texture::from_3d_data_f(size, size, MAXSTREAMLETSIZE as u32, format, &data, false)
+        };
+        self.texture = Rc::new(empty());
+        self.texture2 = Rc::new(empty());
+        self.layer = 0;
+        self.swap = false;
+    }
+
+    /// Passes the settings of the emitter at `index` to the shaders.
+    fn set_emitter_uniforms(&self, index: usize, emitter: &Emitter) {
+        let name = |uniform: &str| format!("u_emitter_{}[{}]", uniform, index);
+        let shape = match emitter.shape {
+            SeedShape::Sphere => 0,
+            SeedShape::Box => 1,
+            SeedShape::Plane => 2,
+            SeedShape::Rake => 3,
+            SeedShape::Disk => 4,
+            SeedShape::Volume => 5,
+            SeedShape::MagnitudeWeighted => 6,
+            SeedShape::ChannelWeighted => 7,
+        };
+        let region = emitter.region();
+        let (x, y, z) = region.center;
+        let (r, g, b) = emitter.color;
+        let shader = &self.update_shader;
+        shader.uniform3f(&name("position"), x, y, z);
+        shader.uniform1i(&name("shape"), shape);
+        shader.uniform1i(&name("axis"), emitter.axis.index() as i32);
+        shader.uniform1f(&name("radius"), region.radius);
+        shader.uniform1f(&name("lifetime"), emitter.lifetime);
+        shader.uniform1f(&name("speed"), emitter.speed);
+        self.shader.uniform3f(&name("color"), r, g, b);
+        self.shader.uniform1f(&name("lifetime"), emitter.lifetime);
+        self.shader.uniform1f(&name("speed"), emitter.speed);
+    }
+
+    /// Restricts spawning to the inside of a mask, which uses the voxel grid of the field,
+    /// or removes the restriction.
+    pub fn set_mask(&mut self, mask: Option<&Mask>) {
+        self.mask = mask.map(|mask| Rc::new(mask.create_texture()));
+    }
+
+    /// Sets the overlay texture created by `ScalarVolume::create_texture` that the overlay
+    /// color mode shows, or removes it.
+    pub fn set_overlay(&mut self, overlay: Option<Rc<Texture>>) {
+        self.overlay = overlay;
+    }
+
+    /// Sets the colormap lookup texture, created by `Colormap::create_texture`.
+    pub fn set_colormap(&mut self, colormap: Rc<Texture>) {
+        self.colormap = Some(colormap);
+    }
+
     pub fn update_texture(&self) -> Rc<Texture> {
         if !self.swap {
             self.texture.clone()
@@ -242,6 +383,14 @@ impl Drawable for GPUParticleEngine {
     fn draw_transformed(&self, view_matrix: &Matrix4<f32>) {
         self.update_texture()
             .activate(Some(&self.shader), 1, "uOther");
+        if let Some(overlay) = &self.overlay {
+            overlay.activate(Some(&self.shader), 2, "uOverlay");
+        }
+        self.emitters.activate(Some(&self.shader), 3, "uEmitters");
+        self.noise.activate(Some(&self.shader), 4, "uNoise");
+        if let Some(colormap) = &self.colormap {
+            colormap.activate(Some(&self.shader), 5, "uColormap");
+        }
         render_target::draw_indices(
             DrawMode::LINES,
             &self.vertices,
@@ -253,3 +402,36 @@ impl Drawable for GPUParticleEngine {
         //render_target::draw_vertex_array(DrawMode::POINTS, 0, len, &self.vertices, self.render_states(), view_matrix);
     }
 }
+
+/// Creates the texture of random values in [0, 1) that each particle draws its spawn
+/// positions and lifetime from.
+fn noise_texture(texture_size: usize, seed: Option<u64>) -> Texture {
+    let mut rng = create_rng(seed);
+    let noise_data: Vec<f32> = (0..texture_size * texture_size * 4)
+        .map(|_| rng.gen_range(0.0, 1.0))
+        .collect();
+    Texture::from_data(
+        texture_size as u32,
+        texture_size as u32,
+        TextureFormat::RGBA,
+        &noise_data,
+    )
+}
+
+/// Creates the texture holding the emitter of each particle in its red channel, sharing the
+/// particles evenly between `count` emitters.
+fn emitter_texture(texture_size: usize, count: usize) -> Texture {
+    let particles = texture_size * texture_size;
+    let data: Vec<f32> = (0..particles)
+        .flat_map(|i| {
+            let emitter = (i * count / particles) as f32;
+            vec![emitter, 0.0, 0.0, 0.0]
+        })
+        .collect();
+    Texture::from_data(
+        texture_size as u32,
+        texture_size as u32,
+        TextureFormat::RGBA,
+        &data,
+    )
+}
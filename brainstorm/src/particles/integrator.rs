@@ -0,0 +1,164 @@
+//! Numerical integration of particle paths through a steady velocity field.
+
+use serde_derive::{Deserialize, Serialize};
+
+type Vector3 = (f32, f32, f32);
+
+/// The most substeps an adaptive step is split into, which bounds its cost.
+const MAX_SUBSTEPS: usize = 64;
+
+/// How particles are advanced along the field in each tick.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum Integrator {
+    /// Forward Euler, first order. Cheap, but spirals outward on closed orbits.
+    #[default]
+    Euler,
+    /// The explicit midpoint method, second order.
+    Midpoint,
+    /// The classic fourth-order Runge-Kutta method.
+    RungeKutta4,
+    /// The Dormand-Prince 5(4) method, splitting each tick into substeps
+    /// so that the estimated error stays below the tolerance.
+    DormandPrince,
+}
+
+gui_enum!(Integrator, INTEGRATORS, "integrator", {
+    Euler => "euler",
+    Midpoint => "midpoint",
+    RungeKutta4 => "rk4",
+    DormandPrince => "rk45",
+});
+
+impl Integrator {
+    /// Advances `position` by `h` time units, where `velocity` gives the velocity at a
+    /// position. `tolerance` is the largest error allowed per substep of the adaptive method,
+    /// in world units. Returns the new position, which is NaN if any velocity was.
+    pub fn step<F>(self, velocity: F, position: Vector3, h: f32, tolerance: f32) -> Vector3
+    where
+        F: Fn(Vector3) -> Vector3,
+    {
+        match self {
+            Integrator::Euler => add(position, &[(h, velocity(position))]),
+            Integrator::Midpoint => {
+                let k1 = velocity(position);
+                let k2 = velocity(add(position, &[(h / 2.0, k1)]));
+                add(position, &[(h, k2)])
+            }
+            Integrator::RungeKutta4 => {
+                let k1 = velocity(position);
+                let k2 = velocity(add(position, &[(h / 2.0, k1)]));
+                let k3 = velocity(add(position, &[(h / 2.0, k2)]));
+                let k4 = velocity(add(position, &[(h, k3)]));
+                add(
+                    position,
+                    &[(h / 6.0, k1), (h / 3.0, k2), (h / 3.0, k3), (h / 6.0, k4)],
+                )
+            }
+            Integrator::DormandPrince => adaptive(velocity, position, h, tolerance),
+        }
+    }
+}
+
+/// Adds weighted vectors to `position`.
+fn add(position: Vector3, terms: &[(f32, Vector3)]) -> Vector3 {
+    terms
+        .iter()
+        .fold(position, |(x, y, z), &(w, (dx, dy, dz))| {
+            (x + w * dx, y + w * dy, z + w * dz)
+        })
+}
+
+/// Integrates over `h` with Dormand-Prince steps, shrinking them where the error estimate
+/// exceeds the tolerance and growing them where it is well below.
+fn adaptive<F>(velocity: F, mut position: Vector3, h: f32, tolerance: f32) -> Vector3
+where
+    F: Fn(Vector3) -> Vector3,
+{
+    let min_step = h / MAX_SUBSTEPS as f32;
+    let mut step = h;
+    let mut done = 0.0;
+    while done < h {
+        step = step.min(h - done);
+        let (next, error) = dormand_prince(&velocity, position, step);
+        if !error.is_finite() {
+            return (f32::NAN, f32::NAN, f32::NAN);
+        }
+        if error <= tolerance || step <= min_step {
+            position = next;
+            done += step;
+        }
+
+        // The error of the embedded fourth-order solution scales with the fifth power
+        // of the step.
+        let factor = if error > 0.0 {
+            0.9 * (tolerance / error).powf(0.2)
+        } else {
+            5.0
+        };
+        step = (step * factor.clamp(0.2, 5.0)).max(min_step);
+    }
+    position
+}
+
+/// Takes one Dormand-Prince step, returning the fifth-order solution and the distance
+/// to the embedded fourth-order solution as the error estimate.
+fn dormand_prince<F>(velocity: &F, p: Vector3, h: f32) -> (Vector3, f32)
+where
+    F: Fn(Vector3) -> Vector3,
+{
+    let k1 = velocity(p);
+    let k2 = velocity(add(p, &[(h / 5.0, k1)]));
+    let k3 = velocity(add(p, &[(h * 3.0 / 40.0, k1), (h * 9.0 / 40.0, k2)]));
+    let k4 = velocity(add(
+        p,
+        &[
+            (h * 44.0 / 45.0, k1),
+            (h * -56.0 / 15.0, k2),
+            (h * 32.0 / 9.0, k3),
+        ],
+    ));
+    let k5 = velocity(add(
+        p,
+        &[
+            (h * 19372.0 / 6561.0, k1),
+            (h * -25360.0 / 2187.0, k2),
+            (h * 64448.0 / 6561.0, k3),
+            (h * -212.0 / 729.0, k4),
+        ],
+    ));
+    let k6 = velocity(add(
+        p,
+        &[
+            (h * 9017.0 / 3168.0, k1),
+            (h * -355.0 / 33.0, k2),
+            (h * 46732.0 / 5247.0, k3),
+            (h * 49.0 / 176.0, k4),
+            (h * -5103.0 / 18656.0, k5),
+        ],
+    ));
+    let next = add(
+        p,
+        &[
+            (h * 35.0 / 384.0, k1),
+            (h * 500.0 / 1113.0, k3),
+            (h * 125.0 / 192.0, k4),
+            (h * -2187.0 / 6784.0, k5),
+            (h * 11.0 / 84.0, k6),
+        ],
+    );
+    let k7 = velocity(next);
+
+    // Difference between the fifth- and fourth-order weights.
+    let (ex, ey, ez) = add(
+        (0.0, 0.0, 0.0),
+        &[
+            (h * 71.0 / 57600.0, k1),
+            (h * -71.0 / 16695.0, k3),
+            (h * 71.0 / 1920.0, k4),
+            (h * -17253.0 / 339_200.0, k5),
+            (h * 22.0 / 525.0, k6),
+            (-h / 40.0, k7),
+        ],
+    );
+    (next, (ex * ex + ey * ey + ez * ez).sqrt())
+}
@@ -0,0 +1,137 @@
+//! Reconstruction of a continuous field from its voxels.
+
+use super::Vector4;
+use serde_derive::{Deserialize, Serialize};
+
+/// How to reconstruct the field between voxels.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum Interpolation {
+    /// The value of the closest voxel.
+    Nearest,
+    /// Linear along each axis, from the 8 surrounding voxels.
+    #[default]
+    Trilinear,
+    /// Catmull-Rom splines along each axis, from the 64 surrounding voxels.
+    /// Smooth first derivatives, but may overshoot near sharp edges.
+    CatmullRom,
+    /// Cubic Hermite splines with Fritsch-Carlson tangents, which do not overshoot.
+    MonotoneCubic,
+}
+
+gui_enum!(Interpolation, MODES, "interpolation", {
+    Nearest => "nearest",
+    Trilinear => "trilinear",
+    CatmullRom => "catmull-rom",
+    MonotoneCubic => "monotone-cubic",
+});
+
+/// What to do in cells where every corner is zero, which usually is background noise
+/// around the scanned object rather than data.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum EmptyCells {
+    /// Sample as NaN, which kills the particles entering them.
+    #[default]
+    Kill,
+    /// Sample as usual, giving a zero vector.
+    Keep,
+}
+
+impl Interpolation {
+    /// Samples at fractional voxel indices, with voxel `i` located at index `i`.
+    /// `get` returns the voxel at the given indices, which may lie outside the volume.
+    pub fn sample<F>(self, get: F, (x, y, z): (f32, f32, f32)) -> Vector4
+    where
+        F: Fn(isize, isize, isize) -> Vector4,
+    {
+        let (lx, ly, lz) = (x.floor(), y.floor(), z.floor());
+        let (tx, ty, tz) = (x - lx, y - ly, z - lz);
+        let (lx, ly, lz) = (lx as isize, ly as isize, lz as isize);
+        match self {
+            Interpolation::Nearest => get(
+                lx + (tx >= 0.5) as isize,
+                ly + (ty >= 0.5) as isize,
+                lz + (tz >= 0.5) as isize,
+            ),
+            Interpolation::Trilinear => {
+                let plane = |z| {
+                    let row = |y| lerp(get(lx, y, z), get(lx + 1, y, z), tx);
+                    lerp(row(ly), row(ly + 1), ty)
+                };
+                lerp(plane(lz), plane(lz + 1), tz)
+            }
+            Interpolation::CatmullRom | Interpolation::MonotoneCubic => {
+                let curve = |p: [Vector4; 4], t| self.cubic(p, t);
+                let plane = |z| {
+                    let row = |y| {
+                        curve(
+                            [
+                                get(lx - 1, y, z),
+                                get(lx, y, z),
+                                get(lx + 1, y, z),
+                                get(lx + 2, y, z),
+                            ],
+                            tx,
+                        )
+                    };
+                    curve([row(ly - 1), row(ly), row(ly + 1), row(ly + 2)], ty)
+                };
+                curve([plane(lz - 1), plane(lz), plane(lz + 1), plane(lz + 2)], tz)
+            }
+        }
+    }
+
+    /// Interpolates between `p[1]` and `p[2]` with a cubic Hermite spline, per component.
+    fn cubic(self, p: [Vector4; 4], t: f32) -> Vector4 {
+        let component = |c: fn(&Vector4) -> f32| {
+            let (p0, p1, p2, p3) = (c(&p[0]), c(&p[1]), c(&p[2]), c(&p[3]));
+            let (m1, m2) = match self {
+                Interpolation::MonotoneCubic => monotone_tangents(p0, p1, p2, p3),
+                _ => ((p2 - p0) / 2.0, (p3 - p1) / 2.0),
+            };
+            hermite(p1, p2, m1, m2, t)
+        };
+        (
+            component(|v| v.0),
+            component(|v| v.1),
+            component(|v| v.2),
+            component(|v| v.3),
+        )
+    }
+}
+
+/// Blends linearly from `a` at `t = 0` to `b` at `t = 1`.
+pub(super) fn lerpf(a: f32, b: f32, t: f32) -> f32 {
+    a * (1.0 - t) + b * t
+}
+
+/// `lerpf` for each component.
+pub(super) fn lerp((ax, ay, az, aw): Vector4, (bx, by, bz, bw): Vector4, t: f32) -> Vector4 {
+    (
+        lerpf(ax, bx, t),
+        lerpf(ay, by, t),
+        lerpf(az, bz, t),
+        lerpf(aw, bw, t),
+    )
+}
+
+fn hermite(p1: f32, p2: f32, m1: f32, m2: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (2.0 * t3 - 3.0 * t2 + 1.0) * p1
+        + (t3 - 2.0 * t2 + t) * m1
+        + (-2.0 * t3 + 3.0 * t2) * p2
+        + (t3 - t2) * m2
+}
+
+/// Tangents at `p1` and `p2` that keep the spline between them monotone.
+fn monotone_tangents(p0: f32, p1: f32, p2: f32, p3: f32) -> (f32, f32) {
+    let (d0, d1, d2) = (p1 - p0, p2 - p1, p3 - p2);
+    if d1 == 0.0 {
+        return (0.0, 0.0);
+    }
+    // Flat at local extrema, the average slope elsewhere.
+    let tangent = |a: f32, b: f32| if a * b <= 0.0 { 0.0 } else { (a + b) / 2.0 };
+    // Limiting the tangents to three times the slope is sufficient for monotonicity.
+    let limit = |m: f32| if m / d1 > 3.0 { 3.0 * d1 } else { m };
+    (limit(tangent(d0, d1)), limit(tangent(d1, d2)))
+}
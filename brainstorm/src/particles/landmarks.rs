@@ -0,0 +1,218 @@
+//! Landmarks marking interesting regions of a field, shown as world points for navigation.
+//!
+//! All methods work on the fourth channel of the current frame, which is the magnitude of
+//! a flow or the anisotropy of a diffusion tensor.
+
+use super::fieldprovider::FieldProvider;
+use serde_derive::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Minimum distance between landmarks, in voxels, for the methods picking single voxels.
+const SUPPRESSION_RADIUS: f32 = 4.0;
+
+/// How to find the landmarks.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum LandmarkMethod {
+    /// The highly directional points stored in the file, or local maxima if there are none.
+    #[default]
+    Stored,
+    /// Voxels larger than all of their neighbors.
+    LocalMaxima,
+    /// The centers of connected regions above the threshold, largest regions first.
+    Clusters,
+    /// The largest voxels, skipping voxels close to one already picked.
+    Strongest,
+}
+
+gui_enum!(LandmarkMethod, METHODS, "landmark method", {
+    Stored => "stored",
+    LocalMaxima => "local maxima",
+    Clusters => "clusters",
+    Strongest => "strongest",
+});
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct LandmarkSettings {
+    pub method: LandmarkMethod,
+    /// The most landmarks to find. Points stored in the file are all shown.
+    pub count: usize,
+    /// Voxels below this fraction of the largest value are ignored.
+    pub threshold: f32,
+}
+
+impl Default for LandmarkSettings {
+    fn default() -> Self {
+        LandmarkSettings {
+            method: LandmarkMethod::default(),
+            count: 10,
+            threshold: 0.5,
+        }
+    }
+}
+
+/// Finds landmarks in the current frame of a field, as positions in world space.
+pub fn find_landmarks(field: &FieldProvider, settings: &LandmarkSettings) -> Vec<(f32, f32, f32)> {
+    let transform = field.transform();
+    let stored = field.directional();
+    let voxels = match settings.method {
+        LandmarkMethod::Stored if !stored.is_empty() => stored.to_vec(),
+        LandmarkMethod::Stored | LandmarkMethod::LocalMaxima => local_maxima(field, settings),
+        LandmarkMethod::Clusters => clusters(field, settings),
+        LandmarkMethod::Strongest => strongest(field, settings),
+    };
+    voxels
+        .into_iter()
+        .map(|position| transform.to_world(position))
+        .collect()
+}
+
+/// The value of every voxel above the threshold with its indices, largest first.
+fn candidates(field: &FieldProvider, threshold: f32) -> Vec<(f32, (usize, usize, usize))> {
+    let mut max: f32 = 0.0;
+    let mut values = Vec::with_capacity(field.width * field.height * field.depth);
+    for z in 0..field.depth {
+        for y in 0..field.height {
+            for x in 0..field.width {
+                let value = field.get_len((x, y, z));
+                if value.is_finite() {
+                    max = max.max(value);
+                    values.push((value, (x, y, z)));
+                }
+            }
+        }
+    }
+    values.retain(|&(value, _)| value > 0.0 && value >= threshold * max);
+    values.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+    values
+}
+
+/// Picks voxels in order, skipping the ones within the suppression radius of one picked
+/// before, until `count` are found.
+fn suppress<I>(voxels: I, count: usize) -> Vec<(f32, f32, f32)>
+where
+    I: IntoIterator<Item = (usize, usize, usize)>,
+{
+    let mut picked: Vec<(f32, f32, f32)> = Vec::new();
+    for (x, y, z) in voxels {
+        let position = (x as f32, y as f32, z as f32);
+        let distance2 = |p: &(f32, f32, f32)| {
+            (p.0 - position.0).powi(2) + (p.1 - position.1).powi(2) + (p.2 - position.2).powi(2)
+        };
+        if picked
+            .iter()
+            .all(|p| distance2(p) >= SUPPRESSION_RADIUS.powi(2))
+        {
+            picked.push(position);
+            if picked.len() == count {
+                break;
+            }
+        }
+    }
+    picked
+}
+
+fn local_maxima(field: &FieldProvider, settings: &LandmarkSettings) -> Vec<(f32, f32, f32)> {
+    let is_maximum = |&(value, (x, y, z)): &(f32, (usize, usize, usize))| {
+        neighbors(field, (x, y, z), true)
+            .into_iter()
+            .all(|n| field.get_len(n) <= value)
+    };
+    let maxima = candidates(field, settings.threshold)
+        .into_iter()
+        .filter(is_maximum)
+        .map(|(_, voxel)| voxel);
+    // Plateaus have several equal maxima next to each other.
+    suppress(maxima, settings.count)
+}
+
+fn strongest(field: &FieldProvider, settings: &LandmarkSettings) -> Vec<(f32, f32, f32)> {
+    let voxels = candidates(field, settings.threshold)
+        .into_iter()
+        .map(|(_, voxel)| voxel);
+    suppress(voxels, settings.count)
+}
+
+/// Groups the voxels above the threshold into face-connected regions, and returns the
+/// weighted center of each, largest total value first, up to `count` of them.
+fn clusters(field: &FieldProvider, settings: &LandmarkSettings) -> Vec<(f32, f32, f32)> {
+    let (width, height) = (field.width, field.height);
+    let index = |(x, y, z): (usize, usize, usize)| x + y * width + z * width * height;
+    let mut above = vec![false; field.width * field.height * field.depth];
+    for (_, voxel) in candidates(field, settings.threshold) {
+        above[index(voxel)] = true;
+    }
+
+    let mut clusters = Vec::new();
+    let mut stack = Vec::new();
+    for z in 0..field.depth {
+        for y in 0..field.height {
+            for x in 0..field.width {
+                if !above[index((x, y, z))] {
+                    continue;
+                }
+                above[index((x, y, z))] = false;
+                stack.push((x, y, z));
+
+                let (mut total, mut sum) = (0.0, (0.0, 0.0, 0.0));
+                while let Some(voxel) = stack.pop() {
+                    let value = field.get_len(voxel);
+                    total += value;
+                    sum.0 += voxel.0 as f32 * value;
+                    sum.1 += voxel.1 as f32 * value;
+                    sum.2 += voxel.2 as f32 * value;
+                    for neighbor in neighbors(field, voxel, false) {
+                        if above[index(neighbor)] {
+                            above[index(neighbor)] = false;
+                            stack.push(neighbor);
+                        }
+                    }
+                }
+                clusters.push((total, (sum.0 / total, sum.1 / total, sum.2 / total)));
+            }
+        }
+    }
+
+    clusters.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+    clusters
+        .into_iter()
+        .take(settings.count)
+        .map(|(_, center)| center)
+        .collect()
+}
+
+/// The neighbors of a voxel within the volume: the 6 sharing a face, and the 20 sharing
+/// an edge or a corner if `diagonals` is set.
+fn neighbors(
+    field: &FieldProvider,
+    (x, y, z): (usize, usize, usize),
+    diagonals: bool,
+) -> Vec<(usize, usize, usize)> {
+    let inside = |v: usize, d: isize, size: usize| {
+        let v = v as isize + d;
+        if v >= 0 && v < size as isize {
+            Some(v as usize)
+        } else {
+            None
+        }
+    };
+    let mut neighbors = Vec::with_capacity(26);
+    for dz in -1..=1 {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let steps = (dx != 0) as usize + (dy != 0) as usize + (dz != 0) as usize;
+                if steps == 0 || (steps > 1 && !diagonals) {
+                    continue;
+                }
+                if let (Some(nx), Some(ny), Some(nz)) = (
+                    inside(x, dx, field.width),
+                    inside(y, dy, field.height),
+                    inside(z, dz, field.depth),
+                ) {
+                    neighbors.push((nx, ny, nz));
+                }
+            }
+        }
+    }
+    neighbors
+}
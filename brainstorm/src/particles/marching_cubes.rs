@@ -1,12 +1,17 @@
 // Largely translated from this: http://paulbourke.net/geometry/polygonise/
 
 use crate::graphics::{render_target, DrawMode, Drawable};
-use crate::particles::{consts, fieldprovider::FieldProvider};
+use crate::particles::shaders::{MESH_FRAGMENT_SHADER, MESH_VERTEX_SHADER};
+use crate::particles::{consts, fieldprovider::FieldProvider, Mask, ScalarVolume, WorldTransform};
 use gl_bindings::{shaders::OurShader, shaders::ShaderAttribute, Buffer, BufferType};
 use na::Matrix4;
-use resources::shaders::{OBJ_FRAGMENT_SHADER, OBJ_VERTEX_SHADER};
 use std::{rc::Rc, str};
 
+/// Position, normal and color.
+const VERTEX_SIZE: usize = 9;
+/// Color of the mesh without an overlay.
+const DEFAULT_COLOR: Vector3 = (1.0, 1.0, 1.0);
+
 pub struct MarchingCubes {
     vertices: Buffer<f32>,
     shader: Rc<OurShader>,
@@ -20,7 +25,7 @@ impl Drawable for MarchingCubes {
     }
 
     fn draw_transformed(&self, view_matrix: &Matrix4<f32>) {
-        let len = self.vertices.len() as i32 / 6;
+        let len = (self.vertices.len() / VERTEX_SIZE) as i32;
         render_target::draw_vertex_array(
             DrawMode::TRIANGLES,
             0,
@@ -46,42 +51,106 @@ impl MarchingCubes {
         self.shader.uniform1f("u_transparency", transparency);
     }
 
+    /// Colors the vertices by the value of an overlay at their position, or resets them
+    /// to the default color. The overlay uses the voxel grid of `transform`.
+    pub fn set_overlay(&mut self, overlay: Option<&ScalarVolume>, transform: &WorldTransform) {
+        for i in 0..self.vertices.len() / VERTEX_SIZE {
+            let offset = i * VERTEX_SIZE;
+            let (r, g, b) = match overlay {
+                Some(overlay) => {
+                    let position = (
+                        self.vertices[offset],
+                        self.vertices[offset + 1],
+                        self.vertices[offset + 2],
+                    );
+                    let value = overlay.sample(transform, position);
+                    (value, value, value)
+                }
+                None => DEFAULT_COLOR,
+            };
+            self.vertices[offset + 6] = r;
+            self.vertices[offset + 7] = g;
+            self.vertices[offset + 8] = b;
+        }
+        self.vertices.bind();
+        let len = self.vertices.len();
+        self.vertices.upload_data(0, len, true);
+    }
+
+    /// Extracts the surface where the magnitude of the field crosses a small threshold.
     pub fn marching_cubes(field: &FieldProvider) -> MarchingCubes {
+        const EPSILON: f32 = 0.1; // NOTE: 0.1 to reduce noise in data.
+        MarchingCubes::extract(
+            (field.width, field.height, field.depth),
+            field.transform(),
+            EPSILON,
+            |v| field.get_len(v),
+        )
+    }
+
+    /// Extracts the boundary of a mask, which uses the voxel grid of `transform`.
+    pub fn mask_boundary(mask: &Mask, transform: &WorldTransform) -> MarchingCubes {
+        MarchingCubes::extract(mask.dimensions(), transform, 0.5, |(x, y, z)| {
+            if mask.contains_voxel(x, y, z) {
+                1.0
+            } else {
+                0.0
+            }
+        })
+    }
+
+    /// Extracts the surface where a scalar volume crosses `level`.
+    pub fn isosurface(volume: &ScalarVolume, transform: &WorldTransform, level: f32) -> Self {
+        MarchingCubes::extract(volume.dimensions(), transform, level, |(x, y, z)| {
+            volume.get(x, y, z)
+        })
+    }
+
+    /// Extracts the surface where `value` crosses `threshold`, with values outside
+    /// the volume expected to be below the threshold.
+    fn extract<F>(
+        (width, height, depth): (usize, usize, usize),
+        transform: &WorldTransform,
+        threshold: f32,
+        value: F,
+    ) -> MarchingCubes
+    where
+        F: Fn((usize, usize, usize)) -> f32,
+    {
         let mut vertices = Buffer::<f32>::new(BufferType::Array);
 
-        const EPSILON: f32 = 0.1; // NOTE: 0.1 to reduce noise in data.
         const S: usize = 1; // step size
 
         let mut verts: [Vector3; 12] = [(0.0, 0.0, 0.0); 12];
 
         // Equivalent to `for x in... for y in... for z in...
-        let iterator = (0..field.width)
+        let iterator = (0..width)
             .step_by(S)
-            .flat_map(|x| (0..field.height).step_by(S).map(move |y| (x, y)))
-            .flat_map(|(x, y)| (0..field.depth).step_by(S).map(move |z| (x, y, z)));
+            .flat_map(|x| (0..height).step_by(S).map(move |y| (x, y)))
+            .flat_map(|(x, y)| (0..depth).step_by(S).map(move |z| (x, y, z)));
 
         for (x, y, z) in iterator {
             let xs = x + S;
             let ys = y + S;
             let zs = z + S;
-            let v1m = field.get_len((x, y, z));
-            let v2m = field.get_len((xs, y, z));
-            let v3m = field.get_len((xs, y, zs));
-            let v4m = field.get_len((x, y, zs));
-            let v5m = field.get_len((x, ys, z));
-            let v6m = field.get_len((xs, ys, z));
-            let v7m = field.get_len((xs, ys, zs));
-            let v8m = field.get_len((x, ys, zs));
+            let v1m = value((x, y, z));
+            let v2m = value((xs, y, z));
+            let v3m = value((xs, y, zs));
+            let v4m = value((x, y, zs));
+            let v5m = value((x, ys, z));
+            let v6m = value((xs, ys, z));
+            let v7m = value((xs, ys, zs));
+            let v8m = value((x, ys, zs));
 
             let mut cidx: usize = 0;
-            cidx |= if v1m > EPSILON { 1 } else { 0 };
-            cidx |= if v2m > EPSILON { 2 } else { 0 };
-            cidx |= if v3m > EPSILON { 4 } else { 0 };
-            cidx |= if v4m > EPSILON { 8 } else { 0 };
-            cidx |= if v5m > EPSILON { 16 } else { 0 };
-            cidx |= if v6m > EPSILON { 32 } else { 0 };
-            cidx |= if v7m > EPSILON { 64 } else { 0 };
-            cidx |= if v8m > EPSILON { 128 } else { 0 };
+            cidx |= if v1m > threshold { 1 } else { 0 };
+            cidx |= if v2m > threshold { 2 } else { 0 };
+            cidx |= if v3m > threshold { 4 } else { 0 };
+            cidx |= if v4m > threshold { 8 } else { 0 };
+            cidx |= if v5m > threshold { 16 } else { 0 };
+            cidx |= if v6m > threshold { 32 } else { 0 };
+            cidx |= if v7m > threshold { 64 } else { 0 };
+            cidx |= if v8m > threshold { 128 } else { 0 };
 
             let edges = consts::MARCHING_CUBES_EDGE_TABLE[cidx];
             // This voxel is not on an edge
@@ -89,13 +158,14 @@ impl MarchingCubes {
                 continue;
             }
 
-            let fx1 = x as f32 / field.width as f32;
-            let fy1 = y as f32 / field.height as f32;
-            let fz1 = z as f32 / field.depth as f32;
+            // Corners are in voxel space, and transformed to world space after interpolation.
+            let fx1 = x as f32;
+            let fy1 = y as f32;
+            let fz1 = z as f32;
 
-            let dx = S as f32 / field.width as f32;
-            let dy = S as f32 / field.height as f32;
-            let dz = S as f32 / field.depth as f32;
+            let dx = S as f32;
+            let dy = S as f32;
+            let dz = S as f32;
 
             let fx2 = fx1 + dx;
             let fy2 = fy1 + dy;
@@ -103,7 +173,7 @@ impl MarchingCubes {
 
             let v = &mut verts;
             let push = &MarchingCubes::push_edge;
-            let ep = EPSILON;
+            let ep = threshold;
             push(edges, 00, v, ep, (fx1, fy1, fz1), (fx2, fy1, fz1), v1m, v2m);
             push(edges, 01, v, ep, (fx2, fy1, fz1), (fx2, fy1, fz2), v2m, v3m);
             push(edges, 02, v, ep, (fx2, fy1, fz2), (fx1, fy1, fz2), v3m, v4m);
@@ -121,9 +191,9 @@ impl MarchingCubes {
 
             let triangle_table = &consts::MARCHING_CUBES_TRIANGLE_TABLE;
             while triangle_table[cidx][id] != -1 {
-                let v1 = verts[triangle_table[cidx][id] as usize];
-                let v2 = verts[triangle_table[cidx][id + 1] as usize];
-                let v3 = verts[triangle_table[cidx][id + 2] as usize];
+                let v1 = transform.to_world(verts[triangle_table[cidx][id] as usize]);
+                let v2 = transform.to_world(verts[triangle_table[cidx][id + 1] as usize]);
+                let v3 = transform.to_world(verts[triangle_table[cidx][id + 2] as usize]);
 
                 let ab = (v2.0 - v1.0, v2.1 - v1.1, v2.2 - v1.2);
                 let cb = (v3.0 - v1.0, v3.1 - v1.1, v3.2 - v1.2);
@@ -148,8 +218,8 @@ impl MarchingCubes {
         vertices.upload_data(0, len, true);
 
         let shader: OurShader = OurShader::new(
-            str::from_utf8(OBJ_VERTEX_SHADER).expect("Failed to read vertex shader"),
-            str::from_utf8(OBJ_FRAGMENT_SHADER).expect("Failed to read fragment shader"),
+            str::from_utf8(MESH_VERTEX_SHADER).expect("Failed to read vertex shader"),
+            str::from_utf8(MESH_FRAGMENT_SHADER).expect("Failed to read fragment shader"),
             &[
                 ShaderAttribute {
                     name: "a_position".to_string(),
@@ -159,6 +229,10 @@ impl MarchingCubes {
                     name: "a_normal".to_string(),
                     size: 3,
                 },
+                ShaderAttribute {
+                    name: "a_color".to_string(),
+                    size: 3,
+                },
             ],
         );
         MarchingCubes {
@@ -184,13 +258,11 @@ impl MarchingCubes {
     }
 
     fn push_vert(vertices: &mut Buffer<f32>, (x, y, z): Vector3, (nx, ny, nz): Vector3) {
+        let (r, g, b) = DEFAULT_COLOR;
         vertices.push(&[
-            x - 0.5,
-            y - 0.5,
-            z - 0.5, // position
-            nx,
-            ny,
-            nz, // normals
+            x, y, z, // position
+            nx, ny, nz, // normals
+            r, g, b, // color
         ])
     }
 
@@ -0,0 +1,70 @@
+use super::WorldTransform;
+use gl_bindings::{Texture, TextureFormat};
+
+/// A label volume marking the voxels of interest, such as white matter or a single lobe.
+/// Uses the voxel grid and transform of the field it belongs to.
+#[derive(Clone, Debug)]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    depth: usize,
+    inside: Vec<bool>,
+}
+
+impl Mask {
+    /// Creates a mask from a flat list of labels, with x being the fastest axis.
+    /// Every non-zero label is inside the mask.
+    pub fn from_labels(width: usize, height: usize, depth: usize, labels: &[f32]) -> Self {
+        Mask {
+            width,
+            height,
+            depth,
+            inside: labels.iter().map(|&label| label != 0.0).collect(),
+        }
+    }
+
+    pub fn dimensions(&self) -> (usize, usize, usize) {
+        (self.width, self.height, self.depth)
+    }
+
+    /// Whether a voxel is inside the mask. Voxels outside the volume never are.
+    pub fn contains_voxel(&self, x: usize, y: usize, z: usize) -> bool {
+        x < self.width
+            && y < self.height
+            && z < self.depth
+            && self.inside[x + y * self.width + z * self.width * self.height]
+    }
+
+    /// Whether a world position is inside the mask, using the nearest voxel. Voxels lie at
+    /// their indices as in `FieldProvider`, so the boundary runs halfway between voxels, where
+    /// the mesh of it is drawn.
+    pub fn contains(&self, transform: &WorldTransform, position: (f32, f32, f32)) -> bool {
+        let (x, y, z) = transform.to_voxel(position);
+        let nearest = |v: f32| (v + 0.5).floor();
+        let (x, y, z) = (nearest(x), nearest(y), nearest(z));
+        if x < 0.0 || y < 0.0 || z < 0.0 {
+            return false;
+        }
+        self.contains_voxel(x as usize, y as usize, z as usize)
+    }
+
+    /// Creates a texture for the GPU particles, with inside voxels being white.
+    pub fn create_texture(&self) -> Texture {
+        let data: Vec<u8> = self
+            .inside
+            .iter()
+            .flat_map(|&inside| {
+                let value = if inside { 255 } else { 0 };
+                vec![value; 4]
+            })
+            .collect();
+        Texture::from_3d_data(
+            self.width as u32,
+            self.height as u32,
+            self.depth as u32,
+            TextureFormat::RGBA,
+            &data[..],
+            false,
+        )
+    }
+}
@@ -1,16 +1,84 @@
 //! Module containing all particle-system related code.
 
+use rand::{rngs::SmallRng, FromEntropy, SeedableRng};
 use serde_derive::{Deserialize, Serialize};
 
+/// Implements what the enums chosen with GUI sliders and command line options share:
+/// `$all`, listing the variants in the order used by the GUI, `name`, `Display` and
+/// `FromStr` by name. `$what` describes the enum in the error of `FromStr`.
+macro_rules! gui_enum {
+    (@one $variant:ident) => {
+        1
+    };
+    ($type:ident, $all:ident, $what:expr, { $($variant:ident => $name:expr),+ $(,)? }) => {
+        /// The variants in the order used by the GUI.
+        pub const $all: [$type; 0 $(+ gui_enum!(@one $variant))+] = [$($type::$variant),+];
+
+        impl $type {
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($type::$variant => $name),+
+                }
+            }
+        }
+
+        impl std::fmt::Display for $type {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "{}", self.name())
+            }
+        }
+
+        impl std::str::FromStr for $type {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, String> {
+                $all.iter().find(|v| v.name() == s).cloned().ok_or_else(|| {
+                    let names: Vec<_> = $all.iter().map(|v| v.name()).collect();
+                    format!(
+                        "Unknown {} {}, expected one of: {}.",
+                        $what,
+                        s,
+                        names.join(", ")
+                    )
+                })
+            }
+        }
+    };
+}
+
+mod coloring;
 mod consts;
+mod critical_points;
+mod derived;
+mod emitter;
 pub mod fieldprovider;
+mod generators;
 pub mod gpu_fieldprovider;
 pub mod gpu_particles;
+mod integrator;
+mod interpolation;
+mod landmarks;
 mod marching_cubes;
+mod mask;
 mod particle_engine;
+mod scalar_volume;
+mod seeding;
+mod shaders;
+mod streamlines;
+pub mod tensor;
+mod transform;
 
 pub type Vector4 = (f32, f32, f32, f32);
 
+/// Creates the random number generator of a particle engine, from `seed` for reproducible
+/// runs, or from entropy.
+fn create_rng(seed: Option<u64>) -> SmallRng {
+    match seed {
+        Some(seed) => SmallRng::seed_from_u64(seed),
+        None => SmallRng::from_entropy(),
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct VectorField {
     width: usize,
@@ -18,7 +86,188 @@ pub struct VectorField {
     depth: usize,
     vectors: Vec<Vec<Vec<Vector4>>>,
     directional: Vec<(f32, f32, f32)>,
+    /// Not part of the serialized data, so that existing files keep working.
+    #[serde(skip)]
+    affine: Affine,
+    #[serde(skip)]
+    metadata: Metadata,
+}
+
+/// A time-varying vector field, as a sequence of frames with the same dimensions.
+/// The transform, directional points and metadata of the first frame apply to all frames.
+#[derive(Clone, Debug)]
+pub struct FieldSequence {
+    frames: Vec<VectorField>,
+    /// Time between two frames, in the same unit as the particle advection.
+    time_step: f32,
+}
+
+impl FieldSequence {
+    /// Creates a sequence, making sure that all frames have the same dimensions.
+    pub fn new(frames: Vec<VectorField>, time_step: f32) -> Result<Self, String> {
+        let first = frames
+            .first()
+            .ok_or_else(|| "A field sequence needs at least one frame.".to_owned())?;
+        if frames.iter().any(|f| f.dimensions() != first.dimensions()) {
+            return Err("All frames of a field sequence must have the same dimensions.".to_owned());
+        }
+        Ok(FieldSequence { frames, time_step })
+    }
+
+    pub fn frames(&self) -> &[VectorField] {
+        &self.frames
+    }
+
+    pub fn into_frames(self) -> Vec<VectorField> {
+        self.frames
+    }
+
+    /// The first frame, which holds the properties shared by all frames.
+    pub fn first(&self) -> &VectorField {
+        &self.frames[0]
+    }
+
+    pub fn first_mut(&mut self) -> &mut VectorField {
+        &mut self.frames[0]
+    }
+
+    pub fn time_step(&self) -> f32 {
+        self.time_step
+    }
+}
+
+impl From<VectorField> for FieldSequence {
+    fn from(field: VectorField) -> Self {
+        FieldSequence {
+            frames: vec![field],
+            time_step: 1.0,
+        }
+    }
+}
+
+/// Descriptive information about a dataset.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Metadata {
+    /// Name of the dataset.
+    pub name: Option<String>,
+    /// Unit of the physical coordinates, such as "mm".
+    pub units: Option<String>,
+    /// Free-form notes, such as acquisition details.
+    pub notes: Option<String>,
+}
+
+impl VectorField {
+    /// Creates a vector field from a flat list of vectors, with x being the fastest axis.
+    pub fn new(width: usize, height: usize, depth: usize, data: Vec<Vector4>) -> Self {
+        let mut vectors = Vec::with_capacity(depth);
+        let mut iter = data.into_iter();
+        for _ in 0..depth {
+            let mut plane = Vec::with_capacity(height);
+            for _ in 0..height {
+                plane.push(iter.by_ref().take(width).collect());
+            }
+            vectors.push(plane);
+        }
+        VectorField {
+            width,
+            height,
+            depth,
+            vectors,
+            directional: Vec::new(),
+            affine: Affine::default(),
+            metadata: Metadata::default(),
+        }
+    }
+
+    /// Creates a vector field from a flat list of `x, y, z` components,
+    /// using the magnitude of each vector as the fourth channel.
+    pub fn from_vectors(width: usize, height: usize, depth: usize, components: &[f32]) -> Self {
+        let data = components
+            .chunks_exact(3)
+            .map(|c| {
+                (
+                    c[0],
+                    c[1],
+                    c[2],
+                    (c[0] * c[0] + c[1] * c[1] + c[2] * c[2]).sqrt(),
+                )
+            })
+            .collect();
+        VectorField::new(width, height, depth, data)
+    }
+
+    /// Creates a vector field from diffusion tensors, storing the principal eigenvector
+    /// and the fractional anisotropy of each tensor.
+    pub fn from_tensors(
+        width: usize,
+        height: usize,
+        depth: usize,
+        tensors: &[tensor::SymmetricTensor],
+    ) -> Self {
+        let data = tensors.iter().map(tensor::principal_direction).collect();
+        VectorField::new(width, height, depth, data)
+    }
+
+    /// Sets the mapping from voxel indices to physical coordinates.
+    pub fn set_affine(&mut self, affine: Affine) {
+        self.affine = affine;
+    }
+
+    /// Sets the positions of highly directional points, in voxel coordinates.
+    pub fn set_directional(&mut self, directional: Vec<(f32, f32, f32)>) {
+        self.directional = directional;
+    }
+
+    pub fn set_metadata(&mut self, metadata: Metadata) {
+        self.metadata = metadata;
+    }
+
+    pub fn dimensions(&self) -> (usize, usize, usize) {
+        (self.width, self.height, self.depth)
+    }
+
+    /// Returns the vectors as a flat list, with x being the fastest axis.
+    pub fn data(&self) -> Vec<Vector4> {
+        self.vectors().cloned().collect()
+    }
+
+    /// Iterates over the vectors, with x being the fastest axis.
+    pub fn vectors(&self) -> impl Iterator<Item = &Vector4> {
+        self.vectors.iter().flatten().flatten()
+    }
+
+    pub fn directional(&self) -> &[(f32, f32, f32)] {
+        &self.directional
+    }
+
+    pub fn affine(&self) -> &Affine {
+        &self.affine
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Creates the transform between voxel indices and world space for this field.
+    pub fn world_transform(&self) -> WorldTransform {
+        WorldTransform::new(&self.affine, (self.width, self.height, self.depth))
+    }
 }
 
+pub use self::coloring::{ColorMode, Colormap, COLORMAPS, COLOR_MODES};
+pub use self::critical_points::{find_critical_points, CriticalPoint};
+pub use self::derived::{Quantity, QUANTITIES};
+pub use self::emitter::{Emitter, EMITTER_COLORS, MAX_EMITTERS};
+pub use self::generators::Generator;
+pub use self::integrator::{Integrator, INTEGRATORS};
+pub use self::interpolation::{EmptyCells, Interpolation, MODES as INTERPOLATION_MODES};
+pub use self::landmarks::{find_landmarks, LandmarkSettings, METHODS as LANDMARK_METHODS};
 pub use self::marching_cubes::MarchingCubes;
-pub use self::particle_engine::ParticleEngine;
+pub use self::mask::Mask;
+pub use self::particle_engine::{ParticleEngine, DEFAULT_PARTICLE_COUNT};
+pub use self::scalar_volume::ScalarVolume;
+pub use self::seeding::{
+    rake_offset, Axis, SeedRegion, SeedShape, AXES as SEED_AXES, RAKE_SEEDS, SHAPES as SEED_SHAPES,
+};
+pub use self::streamlines::{StreamlineSettings, Streamlines};
+pub use self::transform::{Affine, WorldTransform};
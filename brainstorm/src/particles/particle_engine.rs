@@ -1,24 +1,42 @@
 use na::Matrix4;
 use rand::rngs::SmallRng;
-use rand::{FromEntropy, Rng};
+use rand::{Rng, SeedableRng};
 
-use std::{f32, str};
+use std::{f32, rc::Rc, str};
 
-use crate::particles::fieldprovider::FieldProvider;
+use crate::particles::{
+    create_rng,
+    fieldprovider::FieldProvider,
+    seeding::{Importance, SeedRegion},
+    shaders::{CPU_PARTICLES_FRAGMENT_SHADER, CPU_PARTICLES_VERTEX_SHADER},
+    ColorMode, Emitter, Mask, ScalarVolume,
+};
 use crate::State;
-use gl_bindings::{shaders, AbstractContext, Buffer, BufferType, Context, UniformLocation};
+use gl_bindings::{
+    shaders, AbstractContext, Buffer, BufferType, Context, Texture, UniformLocation,
+};
 
 use crate::camera::{ArcBall, Camera};
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
 
-use resources::shaders::{PARTICLES_FRAGMENT_SHADER, PARTICLES_VERTEX_SHADER};
-
-const PARTICLE_COUNT: usize = 100_000;
+/// Number of CPU particles until another is requested.
+pub const DEFAULT_PARTICLE_COUNT: usize = 100_000;
+/// Position, color and the value looked up in the colormap.
+const VERTEX_SIZE: usize = 7;
+/// How many random positions in the seeding region to try before giving up on a mask.
+const MASK_SEED_ATTEMPTS: usize = 16;
+/// Particles in each chunk, the unit of work of the update threads. Each chunk has its own
+/// generator, so seeded runs give the same particles on any number of cores.
+const CHUNK_SIZE: usize = 10_000;
 
 /// Struct containing the data for a single particle.
 #[derive(Clone, Debug)]
 struct ParticleData {
     position: (f32, f32, f32),
     lifetime: f32,
+    /// Index of the emitter the particle belongs to.
+    emitter: usize,
 }
 
 /// The particle engine itself.
@@ -27,7 +45,17 @@ pub struct ParticleEngine {
     particles: Vec<ParticleData>,
     particle_data: Buffer<f32>,
     field_provider: FieldProvider,
+    mask: Option<Mask>,
+    overlay: Option<ScalarVolume>,
+    /// Lookup texture of the colormap, created by `Colormap::create_texture`.
+    colormap: Option<Rc<Texture>>,
+    /// Weights of the voxels for each weighted seeding shape in use.
+    importance: Vec<Importance>,
+    /// Number of emitters sharing the particles.
+    emitter_count: usize,
     rng: SmallRng,
+    /// One generator for each chunk of particles, seeded from `rng`.
+    chunk_rngs: Vec<SmallRng>,
     mvp_uniform: UniformLocation,
     shader: shaders::OurShader,
     alive_count: usize,
@@ -38,109 +66,404 @@ pub struct ParticleEngine {
 
 impl ParticleEngine {
     /// Initializes a new particle engine.
-    pub fn new(field_provider: FieldProvider) -> Self {
-        let mut rng = SmallRng::from_entropy();
+    /// Random choices are seeded with `seed` if given, for reproducible runs.
+    pub fn new(field_provider: FieldProvider, seed: Option<u64>) -> Self {
+        let rng = create_rng(seed);
 
         // Set up particles.
-        let mut data: Buffer<f32> = Buffer::new(BufferType::Array);
-        data.resize(PARTICLE_COUNT * 3, 0.0);
-        let mut particles = Vec::with_capacity(PARTICLE_COUNT);
-        for i in 0..PARTICLE_COUNT {
-            particles.push(ParticleData {
-                position: (
-                    rng.gen_range(-0.5, 0.5),
-                    rng.gen_range(-0.5, 0.5),
-                    rng.gen_range(-0.5, 0.5),
-                ),
-                lifetime: (i as f32 / PARTICLE_COUNT as f32) * 100.0,
-            });
-        }
-
+        let data: Buffer<f32> = Buffer::new(BufferType::Array);
         data.bind();
 
         // Set up shaders
         let vertex_shader =
-            str::from_utf8(PARTICLES_VERTEX_SHADER).expect("Failed to read vertex shader");
+            str::from_utf8(CPU_PARTICLES_VERTEX_SHADER).expect("Failed to read vertex shader");
         let fragment_shader =
-            str::from_utf8(PARTICLES_FRAGMENT_SHADER).expect("Failed to read fragment shader");
+            str::from_utf8(CPU_PARTICLES_FRAGMENT_SHADER).expect("Failed to read fragment shader");
 
         let mut attributes = Vec::new();
         attributes.push(shaders::ShaderAttribute {
             name: "position".to_string(),
             size: 3,
         });
+        attributes.push(shaders::ShaderAttribute {
+            name: "color".to_string(),
+            size: 3,
+        });
+        attributes.push(shaders::ShaderAttribute {
+            name: "value".to_string(),
+            size: 1,
+        });
 
         let shader = shaders::OurShader::new(vertex_shader, fragment_shader, &attributes);
         shader.use_program();
 
         let mvp_uniform = shader.get_uniform_location();
 
-        // Find the max velocity to be used with the high-pass filter later.
-        let mut max_dist: f32 = 0.0;
-        for (dx, dy, dz, fa) in field_provider.data() {
-            let dist = ((dx * fa).powf(2.0) + (dy * fa).powf(2.0) + (dz * fa).powf(2.0)).sqrt();
-            max_dist = max_dist.max(dist);
-        }
+        // The max velocity is used with the high-pass filter later.
+        let max_dist = field_provider.max_velocity();
 
-        ParticleEngine {
-            particles,
+        let mut engine = ParticleEngine {
+            particles: Vec::new(),
             particle_data: data,
             field_provider,
+            mask: None,
+            overlay: None,
+            colormap: None,
+            importance: Vec::new(),
+            emitter_count: 1,
             rng,
+            chunk_rngs: Vec::new(),
             shader,
             mvp_uniform,
             alive_count: 0,
             max_dist,
             max_camera_dist: 0.0,
             min_camera_dist: 0.0,
-        }
+        };
+        engine.set_particle_count(DEFAULT_PARTICLE_COUNT);
+        engine
     }
 
     /// Update the particle system, advancing 1 tick.
     /// Uses settings from `state` to let the user interface with the system.
     pub fn update(&mut self, state: &State, camera: &ArcBall) {
-        self.alive_count = 0;
-        let (cx, cy, cz) = camera.get_position();
-        let (tx, ty, tz) = camera.get_target();
+        self.sync_field(state);
+        if state.particle_count != self.particles.len() {
+            self.set_particle_count(state.particle_count);
+        }
+        if state.emitters.len() != self.emitter_count {
+            self.partition(state.emitters.len());
+        }
 
+        let chunk_count = self.particles.len().div_ceil(CHUNK_SIZE).max(1);
+        while self.chunk_rngs.len() < chunk_count {
+            let rng = SmallRng::from_rng(&mut self.rng).expect("Failed to seed a generator");
+            self.chunk_rngs.push(rng);
+        }
+
+        let regions: Vec<SeedRegion> = state.emitters.iter().map(Emitter::region).collect();
+        self.update_importance(&regions);
+
+        let tick = Tick {
+            state,
+            field_provider: &self.field_provider,
+            mask: self.mask.as_ref(),
+            overlay: self.overlay.as_ref(),
+            camera: camera.get_position(),
+            regions,
+            importance: &self.importance,
+            speed_multiplier: 0.016 * state.speed_multiplier,
+            max_dist: self.max_dist,
+        };
+
+        // Each chunk writes its living particles to the start of its own part of the buffer.
+        let count = self.particles.len();
+        let emitter_count = self.emitter_count;
+        let chunks = self
+            .particles
+            .chunks_mut(CHUNK_SIZE)
+            .zip(
+                self.particle_data
+                    .as_mut_slice()
+                    .chunks_mut(CHUNK_SIZE * VERTEX_SIZE),
+            )
+            .zip(self.chunk_rngs.iter_mut())
+            .enumerate()
+            .map(|(i, ((particles, vertices), rng))| {
+                let start = i * CHUNK_SIZE;
+                let end = start + particles.len();
+                let respawn_budget = (0..emitter_count)
+                    .map(|emitter| {
+                        respawn_budget(
+                            &state.emitters[emitter],
+                            emitter,
+                            emitter_count,
+                            count,
+                            start..end,
+                        )
+                    })
+                    .collect();
+                Chunk {
+                    particles,
+                    vertices,
+                    rng,
+                    respawn_budget,
+                }
+            })
+            .collect();
+        let results = update_chunks(&tick, chunks);
+
+        // Move the living particles of every chunk next to each other.
+        let vertices = self.particle_data.as_mut_slice();
+        self.alive_count = 0;
         self.max_camera_dist = 0.0;
         self.min_camera_dist = f32::MAX;
-        let radius = state.seeding_size * 0.6 + 0.01;
+        for (i, result) in results.iter().enumerate() {
+            let start = i * CHUNK_SIZE * VERTEX_SIZE;
+            vertices.copy_within(
+                start..start + result.alive * VERTEX_SIZE,
+                self.alive_count * VERTEX_SIZE,
+            );
+            self.alive_count += result.alive;
+            self.max_camera_dist = self.max_camera_dist.max(result.max_camera_dist);
+            self.min_camera_dist = self.min_camera_dist.min(result.min_camera_dist);
+        }
+    }
+
+    /// Starts over with new random choices, seeded with `seed` if given. Every particle is
+    /// placed anew, so that a seeded run is the same as one started with that seed.
+    pub fn reseed(&mut self, seed: Option<u64>) {
+        self.rng = create_rng(seed);
+        self.chunk_rngs.clear();
+        let count = self.particles.len();
+        self.particles.clear();
+        self.set_particle_count(count);
+        self.alive_count = 0;
+    }
+
+    /// Updates the weights of the voxels for the weighted shapes of the regions, and drops
+    /// those no longer used.
+    fn update_importance(&mut self, regions: &[SeedRegion]) {
+        let field_provider = &self.field_provider;
+        let used = |shape| regions.iter().any(|region| region.shape == shape);
+        self.importance
+            .retain(|importance| used(importance.shape()) && importance.is_current(field_provider));
+        for region in regions {
+            if !self.importance.iter().any(|i| i.shape() == region.shape) {
+                self.importance
+                    .extend(Importance::new(field_provider, region.shape));
+            }
+        }
+    }
+
+    /// Grows or shrinks the system to `count` particles. New particles start at random
+    /// positions within the field, with lifetimes spread out so that they respawn gradually.
+    fn set_particle_count(&mut self, count: usize) {
+        let ((x1, y1, z1), (x2, y2, z2)) = self.field_provider.transform().bounds();
+        self.particles.truncate(count);
+        for i in self.particles.len()..count {
+            self.particles.push(ParticleData {
+                position: (
+                    between(&mut self.rng, x1, x2),
+                    between(&mut self.rng, y1, y2),
+                    between(&mut self.rng, z1, z2),
+                ),
+                lifetime: (i as f32 / count as f32) * 100.0,
+                emitter: emitter_of(i, self.emitter_count, count),
+            });
+        }
+        self.particle_data.resize(count * VERTEX_SIZE, 0.0);
+        self.alive_count = self.alive_count.min(count);
+        self.partition(self.emitter_count);
+    }
+
+    /// Shares the particles evenly between `emitter_count` emitters, each getting a run of
+    /// neighboring particles. Particles moved to another emitter respawn there.
+    fn partition(&mut self, emitter_count: usize) {
+        self.emitter_count = emitter_count;
+        let count = self.particles.len();
+        for (i, data) in self.particles.iter_mut().enumerate() {
+            let emitter = emitter_of(i, emitter_count, count);
+            if data.emitter != emitter {
+                data.emitter = emitter;
+                data.lifetime = 500.0;
+            }
+        }
+    }
+
+    /// Draw the particles to the screen using the provided (camera)
+    /// projection matrix.
+    pub fn draw(&mut self, projection_matrix: &Matrix4<f32>, state: &State) {
+        let context = Context::get_context();
+        if self.alive_count > 0 {
+            self.particle_data.bind();
+            self.particle_data
+                .upload_data(0, self.alive_count * VERTEX_SIZE, false);
+            self.shader.use_program();
+            self.shader.uniform1f("min_dist", self.min_camera_dist);
+            self.shader.uniform1f("max_dist", self.max_camera_dist);
+            self.shader.uniform1f("transparency", 0.5);
+            self.shader.uniform1f("part_size", state.particle_size);
+            if let Some(colormap) = &self.colormap {
+                colormap.activate(Some(&self.shader), 0, "colormap");
+            }
+            let use_colormap = state.color_mode.uses_colormap()
+                && (state.color_mode != ColorMode::Overlay || self.overlay.is_some());
+            self.shader.uniform1i("use_colormap", use_colormap as i32);
+            self.shader.bind_attribs();
+            context.uniform_matrix_4fv(&self.mvp_uniform, 1, false, &projection_matrix);
+            context.draw_arrays(Context::POINTS, 0, self.alive_count as i32);
+            self.shader.unbind_attribs();
+        }
+    }
+
+    /// Samples the field at the current time, and as set in `state`.
+    pub fn sync_field(&mut self, state: &State) {
+        self.field_provider.set_time(state.time);
+        self.field_provider.set_interpolation(state.interpolation);
+        self.field_provider.set_empty_cells(state.empty_cells);
+    }
 
-        let speed_multiplier = 0.016 * state.speed_multiplier;
+    /// Restricts spawning to the inside of a mask, which uses the voxel grid of the field,
+    /// or removes the restriction. All particles are respawned so that none are left outside.
+    pub fn set_mask(&mut self, mask: Option<Mask>) {
+        self.mask = mask;
+        for data in &mut self.particles {
+            data.lifetime = 500.0;
+        }
+    }
 
-        let mut respawned = 0;
+    /// Sets the overlay shown by the overlay color mode, or removes it.
+    pub fn set_overlay(&mut self, overlay: Option<ScalarVolume>) {
+        self.overlay = overlay;
+    }
+
+    /// Sets the colormap lookup texture, created by `Colormap::create_texture`.
+    pub fn set_colormap(&mut self, colormap: Rc<Texture>) {
+        self.colormap = Some(colormap);
+    }
+
+    pub fn mask(&self) -> Option<&Mask> {
+        self.mask.as_ref()
+    }
+
+    pub fn field_provider(&self) -> &FieldProvider {
+        &self.field_provider
+    }
+}
+
+/// What every thread needs to update its share of the particles for one tick.
+struct Tick<'a> {
+    state: &'a State,
+    field_provider: &'a FieldProvider,
+    mask: Option<&'a Mask>,
+    overlay: Option<&'a ScalarVolume>,
+    camera: (f32, f32, f32),
+    /// The seeding region of each emitter.
+    regions: Vec<SeedRegion>,
+    importance: &'a [Importance],
+    speed_multiplier: f32,
+    max_dist: f32,
+}
+
+/// A share of the particles, with the part of the vertex buffer and the generator it uses.
+struct Chunk<'a> {
+    particles: &'a mut [ParticleData],
+    vertices: &'a mut [f32],
+    rng: &'a mut SmallRng,
+    /// The most particles of each emitter respawned in this chunk during the tick.
+    respawn_budget: Vec<u32>,
+}
+
+/// The living particles of a chunk after a tick, and their distances to the camera.
+struct ChunkResult {
+    alive: usize,
+    max_camera_dist: f32,
+    min_camera_dist: f32,
+}
+
+impl<'a> Tick<'a> {
+    /// Picks a random position within the seeding region of an emitter, and inside the mask
+    /// if there is one. Returns `None` if no position inside the mask was found.
+    fn seed_position(&self, rng: &mut SmallRng, emitter: usize) -> Option<(f32, f32, f32)> {
+        let transform = self.field_provider.transform();
+        let region = &self.regions[emitter];
+        let importance = self.importance.iter().find(|i| i.shape() == region.shape);
+        for _ in 0..MASK_SEED_ATTEMPTS {
+            let position = region.sample(rng, self.field_provider, importance);
+            match self.mask {
+                Some(mask) if !mask.contains(transform, position) => continue,
+                _ => return Some(position),
+            }
+        }
+        None
+    }
+
+    /// The color of a particle moving with `velocity` and `speed`, and the value the shader
+    /// looks up in the colormap instead for the modes that use one.
+    fn color(
+        &self,
+        data: &ParticleData,
+        emitter: &Emitter,
+        (dx, dy, dz): (f32, f32, f32),
+        speed: f32,
+    ) -> ((f32, f32, f32), f32) {
+        let white = (1.0, 1.0, 1.0);
+        match self.state.color_mode {
+            ColorMode::Direction if speed > 0.0 => {
+                let direction = ((dx / speed).abs(), (dy / speed).abs(), (dz / speed).abs());
+                (direction, 0.0)
+            }
+            ColorMode::Direction => (white, 0.0),
+            ColorMode::Speed => (white, speed / self.max_dist),
+            ColorMode::Age => (white, data.lifetime / emitter.lifetime),
+            ColorMode::Emitter => (emitter.color, 0.0),
+            ColorMode::Overlay => match self.overlay {
+                Some(overlay) => {
+                    let transform = self.field_provider.transform();
+                    (white, overlay.sample(transform, data.position))
+                }
+                None => (white, 0.0),
+            },
+        }
+    }
+
+    /// Advances the particles of a chunk, writing the living ones to the start of its vertices.
+    fn update(&self, chunk: Chunk) -> ChunkResult {
+        let state = self.state;
+        let transform = self.field_provider.transform();
+        let (cx, cy, cz) = self.camera;
+        let mut result = ChunkResult {
+            alive: 0,
+            max_camera_dist: 0.0,
+            min_camera_dist: f32::MAX,
+        };
+        let mut respawned = vec![0; chunk.respawn_budget.len()];
+
+        for data in chunk.particles.iter_mut() {
+            let emitter = &state.emitters[data.emitter];
 
-        for i in 0..PARTICLE_COUNT {
-            let mut data = &mut self.particles[i];
             // Respawn particle if it's too old.
-            if data.lifetime > state.lifetime {
+            if data.lifetime > emitter.lifetime {
                 data.lifetime = 500.0;
-                if respawned > state.particle_respawn_per_tick {
+                if respawned[data.emitter] >= chunk.respawn_budget[data.emitter] {
                     continue;
                 }
+                respawned[data.emitter] += 1;
+                match self.seed_position(chunk.rng, data.emitter) {
+                    Some(position) => data.position = position,
+                    None => continue,
+                }
                 data.lifetime = 0.0;
-                let mut dx: f32 = self.rng.gen_range(-1.0, 1.0);
-                let mut dy: f32 = self.rng.gen_range(-1.0, 1.0);
-                let mut dz: f32 = self.rng.gen_range(-1.0, 1.0);
-                let dist = self.rng.gen_range(0.0, radius * radius).sqrt();
-                let dt = (dx * dx + dy * dy + dz * dz).sqrt();
-                dx /= dt;
-                dy /= dt;
-                dz /= dt;
-                data.position = (dx * dist + tx, dy * dist + ty, dz * dist + tz);
-                respawned += 1;
             }
 
             // Update particle position
-            let (dx, dy, dz, fa) = self.field_provider.delta(data.position);
-            let (dx, dy, dz) = (fa * dx, fa * dy, fa * dz);
-            data.position.0 += dx * speed_multiplier;
-            data.position.1 += dy * speed_multiplier;
-            data.position.2 += dz * speed_multiplier;
+            let field_provider = self.field_provider;
+            let velocity = |position| {
+                let (dx, dy, dz, fa) = field_provider.delta(position);
+                (fa * dx, fa * dy, fa * dz)
+            };
+            // The velocity at the start of the step, used by the filters and colors. Sampled
+            // rather than taken from the step, which is empty at a speed of zero.
+            let (dx, dy, dz) = velocity(data.position);
+            data.position = state.integrator.step(
+                velocity,
+                data.position,
+                self.speed_multiplier * emitter.speed,
+                state.tolerance,
+            );
+
+            // Kill particles leaving the mask, if requested.
+            if let Some(mask) = self.mask.filter(|_| state.mask_kills) {
+                if !mask.contains(transform, data.position) {
+                    data.lifetime = 500.0;
+                    continue;
+                }
+            }
 
+            // Kill particles that were outside the field, or that left it during the step.
             let dist = (dx * dx + dy * dy + dz * dz).sqrt();
-            if dist.is_nan() {
+            if dist.is_nan() || data.position.0.is_nan() {
                 data.lifetime = 500.0;
                 continue;
             }
@@ -156,57 +479,88 @@ impl ParticleEngine {
                 continue;
             }
 
+            let (color, value) = self.color(data, emitter, (dx, dy, dz), dist);
+
             let (dx, dy, dz) = (
                 cx - data.position.0,
                 cy - data.position.1,
                 cz - data.position.2,
             );
-            self.max_camera_dist = self
-                .max_camera_dist
-                .max((dx * dx + dy * dy + dz * dz).sqrt());
-            self.min_camera_dist = self
-                .min_camera_dist
-                .min((dx * dx + dy * dy + dz * dz).sqrt());
-
-            // Send the data to the GPU.
-            self.particle_data[self.alive_count * 3] = data.position.0;
-            self.particle_data[self.alive_count * 3 + 1] = data.position.1;
-            self.particle_data[self.alive_count * 3 + 2] = data.position.2;
+            let camera_dist = (dx * dx + dy * dy + dz * dz).sqrt();
+            result.max_camera_dist = result.max_camera_dist.max(camera_dist);
+            result.min_camera_dist = result.min_camera_dist.min(camera_dist);
+
+            // Write the data to send to the GPU.
+            let (r, g, b) = color;
+            let offset = result.alive * VERTEX_SIZE;
+            chunk.vertices[offset..offset + VERTEX_SIZE].copy_from_slice(&[
+                data.position.0,
+                data.position.1,
+                data.position.2,
+                r,
+                g,
+                b,
+                value,
+            ]);
 
             // Update lifetime and alive count.
             data.lifetime += 1.0;
-            self.alive_count += 1;
+            result.alive += 1;
         }
+        result
     }
+}
 
-    /// Draw the particles to the screen using the provided (camera)
-    /// projection matrix.
-    pub fn draw(&mut self, projection_matrix: &Matrix4<f32>, state: &State) {
-        let context = Context::get_context();
-        if self.alive_count > 0 {
-            self.particle_data.bind();
-            self.particle_data
-                .upload_data(0, self.alive_count * 3, false);
-            self.shader.use_program();
-            self.shader.uniform1f("min_dist", self.min_camera_dist);
-            self.shader.uniform1f("max_dist", self.max_camera_dist);
-            self.shader.uniform1f("transparency", 0.5);
-            self.shader.uniform1f("part_size", state.particle_size);
-            self.shader.bind_attribs();
-            context.uniform_matrix_4fv(&self.mvp_uniform, 1, false, &projection_matrix);
-            context.draw_arrays(Context::POINTS, 0, self.alive_count as i32);
-            self.shader.unbind_attribs();
-        }
+/// The emitter of the particle at `index`, when `count` particles are shared evenly by
+/// `emitter_count` emitters.
+fn emitter_of(index: usize, emitter_count: usize, count: usize) -> usize {
+    index * emitter_count / count
+}
+
+/// The most particles of an emitter respawned during a tick among the particles in `range`.
+/// The spawn rate is given for the default count, so that the density stays the same, and
+/// spread over the particles of the emitter, so that chunks share it in proportion.
+fn respawn_budget(
+    emitter: &Emitter,
+    index: usize,
+    emitter_count: usize,
+    count: usize,
+    range: std::ops::Range<usize>,
+) -> u32 {
+    // The first particle of an emitter, given the rounding down in `emitter_of`.
+    let first = |index: usize| (index * count).div_ceil(emitter_count);
+    let (start, end) = (first(index), first(index + 1));
+    let (from, to) = (range.start.max(start), range.end.min(end));
+    if from >= to {
+        return 0;
     }
+    let respawns =
+        u64::from(emitter.spawn_rate) * (end - start) as u64 / DEFAULT_PARTICLE_COUNT as u64;
+    let share = |i: usize| respawns * (i - start) as u64 / (end - start) as u64;
+    (share(to) - share(from)) as u32
+}
+
+/// Updates the chunks in parallel on the thread pool of rayon, which lives as long as the
+/// program. The results are in the order of the chunks.
+#[cfg(not(target_arch = "wasm32"))]
+fn update_chunks(tick: &Tick, chunks: Vec<Chunk>) -> Vec<ChunkResult> {
+    chunks
+        .into_par_iter()
+        .map(|chunk| tick.update(chunk))
+        .collect()
+}
+
+/// Updates the chunks one after another.
+#[cfg(target_arch = "wasm32")]
+fn update_chunks(tick: &Tick, chunks: Vec<Chunk>) -> Vec<ChunkResult> {
+    chunks.into_iter().map(|chunk| tick.update(chunk)).collect()
+}
 
-    pub fn calculate_highly_directional_positions(&self) -> Vec<(f32, f32, f32)> {
-        let fw = self.field_provider.width as f32;
-        let fh = self.field_provider.height as f32;
-        let fd = self.field_provider.depth as f32;
-        let direct = self.field_provider.directional();
-        direct
-            .into_iter()
-            .map(|(x, y, z)| (x / fw - 0.5, y / fh - 0.5, z / fd - 0.5))
-            .collect()
+/// A random number in `low..high`, or `low` if the range is empty, as along a flat volume.
+fn between(rng: &mut SmallRng, low: f32, high: f32) -> f32 {
+    if low < high {
+        rng.gen_range(low, high)
+    } else {
+        low
     }
 }
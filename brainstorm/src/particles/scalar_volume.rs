@@ -0,0 +1,104 @@
+use super::{interpolation::lerpf, WorldTransform};
+use gl_bindings::{Texture, TextureFormat};
+use std::f32;
+
+/// A scalar value per voxel, such as an anatomical image or an activation map.
+/// Uses the voxel grid and transform of the field it belongs to.
+#[derive(Clone, Debug)]
+pub struct ScalarVolume {
+    width: usize,
+    height: usize,
+    depth: usize,
+    values: Vec<f32>,
+    min: f32,
+    max: f32,
+}
+
+impl ScalarVolume {
+    /// Creates a volume from a flat list of values, with x being the fastest axis.
+    pub fn new(width: usize, height: usize, depth: usize, values: Vec<f32>) -> Self {
+        let (min, max) = values
+            .iter()
+            .filter(|v| v.is_finite())
+            .fold((f32::MAX, f32::MIN), |(min, max), &v| {
+                (min.min(v), max.max(v))
+            });
+        let (min, max) = if min <= max { (min, max) } else { (0.0, 0.0) };
+        ScalarVolume {
+            width,
+            height,
+            depth,
+            values,
+            min,
+            max,
+        }
+    }
+
+    pub fn dimensions(&self) -> (usize, usize, usize) {
+        (self.width, self.height, self.depth)
+    }
+
+    /// The value of a voxel, or zero outside the volume.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> f32 {
+        if x >= self.width || y >= self.height || z >= self.depth {
+            return 0.0;
+        }
+        self.values[x + y * self.width + z * self.width * self.height]
+    }
+
+    /// The smallest and largest finite value.
+    pub fn range(&self) -> (f32, f32) {
+        (self.min, self.max)
+    }
+
+    /// Samples the volume at a world position, scaled to [0, 1]. Voxels lie at their indices
+    /// as in `FieldProvider`, and values are interpolated between them, clamping at the edges.
+    pub fn sample(&self, transform: &WorldTransform, position: (f32, f32, f32)) -> f32 {
+        let (x, y, z) = transform.to_voxel(position);
+        let axis = |v: f32, size: usize| {
+            let v = v.max(0.0).min((size - 1) as f32);
+            let lower = v.floor() as usize;
+            (lower, (lower + 1).min(size - 1), v - lower as f32)
+        };
+        let (x1, x2, tx) = axis(x, self.width);
+        let (y1, y2, ty) = axis(y, self.height);
+        let (z1, z2, tz) = axis(z, self.depth);
+
+        let plane = |z| {
+            lerpf(
+                lerpf(self.get(x1, y1, z), self.get(x2, y1, z), tx),
+                lerpf(self.get(x1, y2, z), self.get(x2, y2, z), tx),
+                ty,
+            )
+        };
+        self.normalize(lerpf(plane(z1), plane(z2), tz))
+    }
+
+    fn normalize(&self, value: f32) -> f32 {
+        if self.max > self.min && value.is_finite() {
+            ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Creates a grayscale texture of the normalized values, for the map and the GPU particles.
+    pub fn create_texture(&self) -> Texture {
+        let data: Vec<u8> = self
+            .values
+            .iter()
+            .flat_map(|&value| {
+                let value = (self.normalize(value) * 255.0).round() as u8;
+                vec![value, value, value, 255]
+            })
+            .collect();
+        Texture::from_3d_data(
+            self.width as u32,
+            self.height as u32,
+            self.depth as u32,
+            TextureFormat::RGBA,
+            &data[..],
+            false,
+        )
+    }
+}
@@ -0,0 +1,254 @@
+//! Regions where new particles spawn.
+//!
+//! The shapes are centered on an emitter and sized by its seeding size. The whole volume and
+//! the weighted shapes ignore both, and spawn particles anywhere in the field.
+
+use super::fieldprovider::FieldProvider;
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+
+type Vector3 = (f32, f32, f32);
+
+/// Number of evenly spaced seeds along a rake.
+pub const RAKE_SEEDS: usize = 16;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum SeedShape {
+    /// Uniformly within a sphere.
+    #[default]
+    Sphere,
+    /// Uniformly within an axis-aligned cube.
+    Box,
+    /// Uniformly within a square perpendicular to the seeding axis.
+    Plane,
+    /// At evenly spaced seeds along a line in the direction of the seeding axis.
+    Rake,
+    /// Uniformly within a disk perpendicular to the seeding axis.
+    Disk,
+    /// Uniformly within the whole volume.
+    Volume,
+    /// Anywhere in the volume, with a probability proportional to the velocity.
+    MagnitudeWeighted,
+    /// Anywhere in the volume, with a probability proportional to the fourth channel.
+    ChannelWeighted,
+}
+
+gui_enum!(SeedShape, SHAPES, "seeding shape", {
+    Sphere => "sphere",
+    Box => "box",
+    Plane => "plane",
+    Rake => "rake",
+    Disk => "disk",
+    Volume => "volume",
+    MagnitudeWeighted => "magnitude-weighted",
+    ChannelWeighted => "channel-weighted",
+});
+
+impl SeedShape {
+    /// Whether the shape picks positions in proportion to a weight of the voxels.
+    pub fn is_weighted(self) -> bool {
+        self == SeedShape::MagnitudeWeighted || self == SeedShape::ChannelWeighted
+    }
+
+    /// The weight of a voxel for the weighted shapes.
+    fn weight(self, (x, y, z, w): (f32, f32, f32, f32)) -> f32 {
+        match self {
+            SeedShape::MagnitudeWeighted => (x * x + y * y + z * z).sqrt() * w.abs(),
+            _ => w.abs(),
+        }
+    }
+}
+
+/// The world axis that rakes follow, and that planes and disks are perpendicular to.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum Axis {
+    X,
+    Y,
+    #[default]
+    Z,
+}
+
+gui_enum!(Axis, AXES, "axis", {
+    X => "x",
+    Y => "y",
+    Z => "z",
+});
+
+impl Axis {
+    pub fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+
+    /// Unit vectors along the axis and the two axes perpendicular to it.
+    pub fn frame(self) -> (Vector3, Vector3, Vector3) {
+        let (x, y, z) = ((1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, 1.0));
+        match self {
+            Axis::X => (x, y, z),
+            Axis::Y => (y, z, x),
+            Axis::Z => (z, x, y),
+        }
+    }
+}
+
+/// A seeding shape placed in the world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SeedRegion {
+    pub shape: SeedShape,
+    pub axis: Axis,
+    pub center: Vector3,
+    /// Radius of spheres and disks, and half the side of boxes, planes and rakes.
+    pub radius: f32,
+}
+
+impl SeedRegion {
+    /// Picks a random position in the region. The volume and weighted shapes need the field,
+    /// and the weighted shapes also the importance of its current frame.
+    pub fn sample<R: Rng>(
+        &self,
+        rng: &mut R,
+        field: &FieldProvider,
+        importance: Option<&Importance>,
+    ) -> Vector3 {
+        let (cx, cy, cz) = self.center;
+        // Kept above zero, since the ranges below panic when empty, as for a negative size.
+        let r = self.radius.max(1e-6);
+        let (axis, u, v) = self.axis.frame();
+        let along = |(x, y, z): Vector3, (ax, ay, az): Vector3, t: f32| {
+            (x + ax * t, y + ay * t, z + az * t)
+        };
+        match self.shape {
+            SeedShape::Sphere => {
+                let (dx, dy, dz) = unit_vector(rng);
+                let dist = rng.gen_range(0.0, r * r * r).cbrt();
+                (cx + dx * dist, cy + dy * dist, cz + dz * dist)
+            }
+            SeedShape::Box => (
+                cx + rng.gen_range(-r, r),
+                cy + rng.gen_range(-r, r),
+                cz + rng.gen_range(-r, r),
+            ),
+            SeedShape::Plane => {
+                let position = along(self.center, u, rng.gen_range(-r, r));
+                along(position, v, rng.gen_range(-r, r))
+            }
+            SeedShape::Rake => {
+                let seed = rng.gen_range(0, RAKE_SEEDS);
+                along(self.center, axis, rake_offset(seed) * r)
+            }
+            SeedShape::Disk => {
+                let angle = rng.gen_range(0.0, 2.0 * std::f32::consts::PI);
+                let dist = rng.gen_range(0.0, r * r).sqrt();
+                let position = along(self.center, u, angle.cos() * dist);
+                along(position, v, angle.sin() * dist)
+            }
+            SeedShape::Volume => {
+                let mut coordinate = |size: usize| rng.gen_range(0.0, (size - 1).max(1) as f32);
+                let voxel = (
+                    coordinate(field.width),
+                    coordinate(field.height),
+                    coordinate(field.depth),
+                );
+                field.transform().to_world(voxel)
+            }
+            SeedShape::MagnitudeWeighted | SeedShape::ChannelWeighted => match importance {
+                Some(importance) => importance.sample(rng, field),
+                None => (f32::NAN, f32::NAN, f32::NAN),
+            },
+        }
+    }
+}
+
+/// The offset of a seed along a rake, as a fraction of its half length.
+pub fn rake_offset(seed: usize) -> f32 {
+    (seed as f32 + 0.5) / RAKE_SEEDS as f32 * 2.0 - 1.0
+}
+
+fn unit_vector<R: Rng>(rng: &mut R) -> Vector3 {
+    let z: f32 = rng.gen_range(-1.0, 1.0);
+    let angle = rng.gen_range(0.0, 2.0 * std::f32::consts::PI);
+    let r = (1.0 - z * z).sqrt();
+    (r * angle.cos(), r * angle.sin(), z)
+}
+
+/// The cumulative weights of the voxels of one frame, for picking voxels in proportion
+/// to their weight.
+pub struct Importance {
+    shape: SeedShape,
+    frame: usize,
+    cumulative: Vec<f32>,
+}
+
+impl Importance {
+    /// Weighs the voxels of the current frame of the field for a weighted shape.
+    /// Returns `None` for the other shapes.
+    pub fn new(field: &FieldProvider, shape: SeedShape) -> Option<Self> {
+        if !shape.is_weighted() {
+            return None;
+        }
+        // Summed in double precision, so that small weights still count in large volumes.
+        let mut total = 0.0;
+        let mut cumulative = Vec::with_capacity(field.width * field.height * field.depth);
+        for z in 0..field.depth {
+            for y in 0..field.height {
+                for x in 0..field.width {
+                    let weight = shape.weight(field.get_vec((x, y, z)));
+                    if weight.is_finite() {
+                        total += f64::from(weight);
+                    }
+                    cumulative.push(total as f32);
+                }
+            }
+        }
+        Some(Importance {
+            shape,
+            frame: field.frame(),
+            cumulative,
+        })
+    }
+
+    /// The weighted shape the weights were computed for.
+    pub fn shape(&self) -> SeedShape {
+        self.shape
+    }
+
+    /// Whether the weights were computed for the current frame of the field.
+    pub fn is_current(&self, field: &FieldProvider) -> bool {
+        self.frame == field.frame()
+    }
+
+    /// Picks a random position within a voxel picked in proportion to its weight.
+    /// Returns NaN if every weight is zero.
+    fn sample<R: Rng>(&self, rng: &mut R, field: &FieldProvider) -> Vector3 {
+        let total = self.cumulative.last().cloned().unwrap_or(0.0);
+        if total <= 0.0 {
+            return (f32::NAN, f32::NAN, f32::NAN);
+        }
+        let target = rng.gen_range(0.0, total);
+        let index = self
+            .cumulative
+            .partition_point(|&sum| sum <= target)
+            .min(self.cumulative.len() - 1);
+        let (width, height) = (field.width, field.height);
+        let voxel = (
+            index % width,
+            index / width % height,
+            index / (width * height),
+        );
+
+        // Spread the particles over the voxel, staying within the volume.
+        let mut jitter = |v: usize, size: usize| {
+            let offset: f32 = rng.gen_range(-0.5, 0.5);
+            (v as f32 + offset).max(0.0).min((size - 1) as f32)
+        };
+        let position = (
+            jitter(voxel.0, field.width),
+            jitter(voxel.1, field.height),
+            jitter(voxel.2, field.depth),
+        );
+        field.transform().to_world(position)
+    }
+}
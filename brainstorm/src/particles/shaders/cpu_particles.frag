@@ -0,0 +1,16 @@
+#version 300 es
+
+precision mediump float;
+
+in vec4 f_color;
+
+out vec4 color;
+
+void main(void) {
+    // Round points.
+    vec2 offset = gl_PointCoord - vec2(0.5);
+    if (dot(offset, offset) > 0.25) {
+        discard;
+    }
+    color = f_color;
+}
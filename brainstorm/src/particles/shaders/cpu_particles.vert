@@ -0,0 +1,29 @@
+#version 300 es
+
+precision mediump float;
+
+in vec3 position;
+// Color of the particle, unless it is looked up in the colormap.
+in vec3 color;
+// Scalar in [0, 1] looked up in the colormap.
+in float value;
+
+out vec4 f_color;
+
+uniform mat4 MVP;
+uniform float min_dist;
+uniform float max_dist;
+uniform float transparency;
+uniform float part_size;
+uniform int use_colormap;
+uniform sampler2D colormap;
+
+void main(void) {
+    gl_Position = MVP * vec4(position, 1.0);
+
+    // Particles closer to the camera are larger, brighter and more opaque.
+    float depth = clamp((gl_Position.w - min_dist) / max(max_dist - min_dist, 0.0001), 0.0, 1.0);
+    gl_PointSize = part_size * (1.0 - 0.5 * depth);
+    vec3 rgb = use_colormap != 0 ? texture(colormap, vec2(clamp(value, 0.0, 1.0), 0.5)).rgb : color;
+    f_color = vec4(rgb * (1.0 - 0.6 * depth), transparency * (1.0 - 0.5 * depth));
+}
@@ -0,0 +1,17 @@
+#version 300 es
+
+precision mediump float;
+
+in vec3 v_normal;
+in vec3 v_color;
+
+out vec4 color;
+
+uniform vec3 lightDir;
+uniform float u_transparency;
+
+void main(void) {
+    // The winding of the triangles is not consistent, so light both sides.
+    float diffuse = abs(dot(normalize(v_normal), lightDir));
+    color = vec4(v_color * (0.3 + 0.7 * diffuse), u_transparency);
+}
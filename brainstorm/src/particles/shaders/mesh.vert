@@ -0,0 +1,18 @@
+#version 300 es
+
+precision mediump float;
+
+in vec3 a_position;
+in vec3 a_normal;
+in vec3 a_color;
+
+out vec3 v_normal;
+out vec3 v_color;
+
+uniform mat4 MVP;
+
+void main(void) {
+    gl_Position = MVP * vec4(a_position, 1.0);
+    v_normal = a_normal;
+    v_color = a_color;
+}
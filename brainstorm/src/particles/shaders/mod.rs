@@ -0,0 +1,16 @@
+//! The shaders of the particles, streamlines and meshes drawn in the field.
+//!
+//! Unlike the shaders in `resources`, these work in the world space of `WorldTransform`
+//! and read the vertex and particle layouts of this crate, so they change together with the
+//! code feeding them and are kept here.
+
+pub const CPU_PARTICLES_VERTEX_SHADER: &[u8] = include_bytes!("cpu_particles.vert");
+pub const CPU_PARTICLES_FRAGMENT_SHADER: &[u8] = include_bytes!("cpu_particles.frag");
+pub const GPU_PARTICLES_VERTEX_SHADER: &[u8] = include_bytes!("particles.vert");
+pub const GPU_PARTICLES_FRAGMENT_SHADER: &[u8] = include_bytes!("particles.frag");
+pub const GPU_PARTICLES_UPDATE_VERTEX_SHADER: &[u8] = include_bytes!("update.vert");
+pub const GPU_PARTICLES_UPDATE_FRAGMENT_SHADER: &[u8] = include_bytes!("update.frag");
+pub const MESH_VERTEX_SHADER: &[u8] = include_bytes!("mesh.vert");
+pub const MESH_FRAGMENT_SHADER: &[u8] = include_bytes!("mesh.frag");
+pub const STREAMLINE_VERTEX_SHADER: &[u8] = include_bytes!("streamlines.vert");
+pub const STREAMLINE_FRAGMENT_SHADER: &[u8] = include_bytes!("streamlines.frag");
@@ -0,0 +1,11 @@
+#version 300 es
+
+precision mediump float;
+
+in vec4 f_color;
+
+out vec4 color;
+
+void main(void) {
+    color = f_color;
+}
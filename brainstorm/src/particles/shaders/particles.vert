@@ -0,0 +1,80 @@
+#version 300 es
+
+precision highp float;
+precision highp sampler3D;
+
+in vec2 v_texpos;
+
+out vec4 f_color;
+
+uniform mat4 MVP;
+// The state texture holding the newest layer, and the one holding the other layers.
+uniform sampler3D uSampler;
+uniform sampler3D uOther;
+uniform sampler3D uOverlay;
+// The emitter of each particle in the red channel.
+uniform sampler2D uEmitters;
+uniform sampler2D uNoise;
+uniform sampler2D uColormap;
+
+uniform int u_layer;
+uniform float u_size;
+uniform float u_transparency;
+uniform int u_use_overlay;
+uniform int u_color_mode;
+uniform float u_speed;
+uniform float u_max_velocity;
+uniform mat4 u_world_to_texture;
+// The settings of each emitter, for up to MAX_EMITTERS.
+uniform vec3 u_emitter_color[8];
+uniform float u_emitter_lifetime[8];
+uniform float u_emitter_speed[8];
+
+vec3 colormap(float value) {
+    return texture(uColormap, vec2(clamp(value, 0.0, 1.0), 0.5)).rgb;
+}
+
+vec4 fetch(ivec2 id, int layer) {
+    if (layer % 2 == u_layer % 2) {
+        return texelFetch(uSampler, ivec3(id, layer), 0);
+    }
+    return texelFetch(uOther, ivec3(id, layer), 0);
+}
+
+void main(void) {
+    int layers = textureSize(uSampler, 0).z;
+    int layer = gl_VertexID / (int(u_size) * int(u_size));
+    ivec2 id = ivec2(v_texpos * u_size);
+
+    // Layers written before the particle was spawned are collapsed onto its newest position.
+    int distance = (u_layer - layer + layers) % layers;
+    vec4 newest = fetch(id, u_layer);
+    vec4 current = float(distance) < newest.w ? fetch(id, layer) : newest;
+    vec4 previous = float(distance + 1) < newest.w ? fetch(id, (layer + layers - 1) % layers) : current;
+
+    // Color by the direction for u_color_mode 0, through the colormap by the speed for 1, the
+    // age for 2 and the overlay for 4, and by the emitter for 3.
+    int emitter = int(texelFetch(uEmitters, id, 0).r);
+    vec3 direction = current.xyz - previous.xyz;
+    vec3 rgb = vec3(1.0);
+    if (u_color_mode == 1) {
+        float step = u_speed * u_emitter_speed[emitter];
+        rgb = colormap(length(direction) / (step * u_max_velocity));
+    } else if (u_color_mode == 2) {
+        float lifetime = u_emitter_lifetime[emitter] * (0.5 + texelFetch(uNoise, id, 0).w);
+        rgb = colormap(max(newest.w - float(distance), 0.0) / lifetime);
+    } else if (u_color_mode == 3) {
+        rgb = u_emitter_color[emitter];
+    } else if (u_color_mode == 4) {
+        if (u_use_overlay != 0) {
+            vec3 tex = (u_world_to_texture * vec4(current.xyz, 1.0)).xyz;
+            rgb = colormap(texture(uOverlay, tex).r);
+        }
+    } else if (length(direction) > 0.0) {
+        rgb = abs(normalize(direction));
+    }
+    float fade = 1.0 - float(distance) / float(layers);
+
+    f_color = vec4(rgb, u_transparency * fade);
+    gl_Position = MVP * vec4(current.xyz, 1.0);
+}
@@ -0,0 +1,25 @@
+#version 300 es
+
+precision mediump float;
+
+// The normal of tubes, or the tangent of lines.
+in vec3 v_normal;
+in vec3 v_color;
+
+out vec4 color;
+
+uniform vec3 lightDir;
+uniform int u_tubes;
+
+void main(void) {
+    float diffuse;
+    if (u_tubes != 0) {
+        // Light both sides, like the mesh.
+        diffuse = abs(dot(normalize(v_normal), lightDir));
+    } else {
+        // A line is lit as the brightest of the normals perpendicular to its tangent.
+        float along = dot(normalize(v_normal), lightDir);
+        diffuse = sqrt(max(1.0 - along * along, 0.0));
+    }
+    color = vec4(v_color * (0.3 + 0.7 * diffuse), 1.0);
+}
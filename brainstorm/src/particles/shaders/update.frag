@@ -0,0 +1,198 @@
+#version 300 es
+
+precision highp float;
+precision highp sampler3D;
+
+in vec2 f_texpos;
+
+// World position in xyz, age in frames in w. An age of 0 marks a dead particle.
+out vec4 color;
+
+uniform sampler3D uSampler;
+uniform sampler3D uData;
+uniform sampler3D uDataNext;
+uniform sampler2D uNoise;
+uniform sampler3D uMask;
+// The emitter of each particle in the red channel.
+uniform sampler2D uEmitters;
+
+uniform int u_layer;
+uniform int u_frame;
+uniform int u_use_mask;
+uniform int u_mask_kills;
+uniform int u_integrator;
+uniform int u_rake_seeds;
+uniform float u_size;
+uniform float u_speed;
+uniform float u_lowpass;
+uniform float u_highpass;
+uniform float u_max_velocity;
+uniform float u_max_channel;
+uniform float u_min;
+uniform float u_max;
+uniform float u_time_blend;
+uniform mat4 u_world_to_texture;
+// The settings of each emitter, for up to MAX_EMITTERS.
+uniform vec3 u_emitter_position[8];
+uniform int u_emitter_shape[8];
+uniform int u_emitter_axis[8];
+uniform float u_emitter_radius[8];
+uniform float u_emitter_lifetime[8];
+uniform float u_emitter_speed[8];
+uniform mat4 u_texture_to_world;
+
+uint hash(uint x) {
+    x ^= x >> 16;
+    x *= 0x7feb352du;
+    x ^= x >> 15;
+    x *= 0x846ca68bu;
+    x ^= x >> 16;
+    return x;
+}
+
+float random(inout uint seed) {
+    seed = hash(seed);
+    return float(seed) / 4294967295.0;
+}
+
+// Returns the velocity at a world position and the current time, or zero outside the volume.
+vec3 velocity(vec3 position) {
+    vec3 tex = (u_world_to_texture * vec4(position, 1.0)).xyz;
+    if (any(lessThan(tex, vec3(0.0))) || any(greaterThan(tex, vec3(1.0)))) {
+        return vec3(0.0);
+    }
+    vec4 data = mix(texture(uData, tex), texture(uDataNext, tex), u_time_blend);
+    vec3 direction = data.xyz * (u_max - u_min) + u_min;
+    return direction * data.w;
+}
+
+// Advances a position by a step of length h, given the velocity there: with forward Euler
+// for u_integrator 0, the midpoint method for 1 and classic fourth-order Runge-Kutta for 2.
+vec3 advect(vec3 position, vec3 k1, float h) {
+    if (u_integrator == 0) {
+        return position + k1 * h;
+    }
+    vec3 k2 = velocity(position + k1 * (h / 2.0));
+    if (u_integrator == 1) {
+        return position + k2 * h;
+    }
+    vec3 k3 = velocity(position + k2 * (h / 2.0));
+    vec3 k4 = velocity(position + k3 * h);
+    return position + (k1 + 2.0 * k2 + 2.0 * k3 + k4) * (h / 6.0);
+}
+
+// Returns the unit vector along a world axis, wrapping around after z.
+vec3 unit(int axis) {
+    axis = axis % 3;
+    return vec3(float(axis == 0), float(axis == 1), float(axis == 2));
+}
+
+// Returns the weight of a texture position for the weighted seeding shapes, relative to
+// the largest weight in the field.
+float seed_weight(vec3 tex, int shape) {
+    vec4 data = mix(texture(uData, tex), texture(uDataNext, tex), u_time_blend);
+    if (shape == 6) {
+        vec3 direction = data.xyz * (u_max - u_min) + u_min;
+        return length(direction * data.w) / u_max_velocity;
+    }
+    return abs(data.w) / u_max_channel;
+}
+
+// Picks a random world position in the seeding region of an emitter. Its shape is 0 for a
+// sphere, 1 for a box, 2 for a plane, 3 for a rake, 4 for a disk, 5 for the whole volume, and
+// 6 and 7 for the volume weighted by the velocity and the fourth channel.
+//
+// Weighted positions are only accepted in proportion to their weight, where the CPU particles
+// pick voxels from the cumulative weights instead. Both follow the weights, but in fields
+// where few voxels carry most of the weight, particles may need several frames to
+// find a position, so fewer of them are alive at a time.
+vec3 seed_position(inout uint seed, int emitter, out bool accepted) {
+    accepted = true;
+    int shape = u_emitter_shape[emitter];
+    int axis = u_emitter_axis[emitter];
+    vec3 center = u_emitter_position[emitter];
+    float r = u_emitter_radius[emitter];
+    vec3 n = unit(axis);
+    vec3 u = unit(axis + 1);
+    vec3 v = unit(axis + 2);
+    if (shape == 0) {
+        float theta = random(seed) * 6.2831853;
+        float z = random(seed) * 2.0 - 1.0;
+        vec3 direction = vec3(sqrt(1.0 - z * z) * vec2(cos(theta), sin(theta)), z);
+        return center + direction * r * pow(random(seed), 1.0 / 3.0);
+    } else if (shape == 1) {
+        vec3 offset = vec3(random(seed), random(seed), random(seed)) * 2.0 - 1.0;
+        return center + offset * r;
+    } else if (shape == 2) {
+        return center + (u * (random(seed) * 2.0 - 1.0) + v * (random(seed) * 2.0 - 1.0)) * r;
+    } else if (shape == 3) {
+        float seeds = float(u_rake_seeds);
+        float k = min(floor(random(seed) * seeds), seeds - 1.0);
+        return center + n * ((k + 0.5) / seeds * 2.0 - 1.0) * r;
+    } else if (shape == 4) {
+        float theta = random(seed) * 6.2831853;
+        return center + (u * cos(theta) + v * sin(theta)) * r * sqrt(random(seed));
+    }
+    // Between the centers of the outer voxels, which bound the volume as the voxel indices
+    // [0, size - 1] bound it for the CPU particles.
+    vec3 size = vec3(textureSize(uData, 0));
+    vec3 tex = (vec3(random(seed), random(seed), random(seed)) * (size - 1.0) + 0.5) / size;
+    if (shape >= 6) {
+        accepted = random(seed) < seed_weight(tex, shape);
+    }
+    return (u_texture_to_world * vec4(tex, 1.0)).xyz;
+}
+
+// Returns whether a world position is inside the mask. Everything is inside without a mask.
+bool inside_mask(vec3 position) {
+    if (u_use_mask == 0) {
+        return true;
+    }
+    vec3 tex = (u_world_to_texture * vec4(position, 1.0)).xyz;
+    if (any(lessThan(tex, vec3(0.0))) || any(greaterThan(tex, vec3(1.0)))) {
+        return false;
+    }
+    return texture(uMask, tex).r > 0.5;
+}
+
+void main(void) {
+    ivec2 id = ivec2(f_texpos * u_size);
+    vec4 state = texelFetch(uSampler, ivec3(id, u_layer), 0);
+    vec4 noise = texelFetch(uNoise, id, 0);
+    int emitter = int(texelFetch(uEmitters, id, 0).r);
+
+    vec3 position = state.xyz;
+    float age = state.w;
+    float lifetime = u_emitter_lifetime[emitter] * (0.5 + noise.w);
+
+    if (age < 1.0 || age > lifetime) {
+        // Respawn within the part of the emitter's seeding region inside the mask. Particles that
+        // find no such position stay dead and try again next frame.
+        uint seed = floatBitsToUint(noise.x) ^ hash(uint(u_frame));
+        for (int attempt = 0; attempt < 16; attempt++) {
+            bool accepted;
+            vec3 spawn = seed_position(seed, emitter, accepted);
+            if (accepted && inside_mask(spawn)) {
+                color = vec4(spawn, 1.0);
+                return;
+            }
+        }
+        color = vec4(position, 0.0);
+        return;
+    }
+
+    vec3 v = velocity(position);
+    float dist = length(v);
+    if (dist <= 0.0 || dist < u_max_velocity * u_highpass || dist > u_max_velocity * u_lowpass) {
+        color = vec4(position, 0.0);
+        return;
+    }
+
+    vec3 next = advect(position, v, u_speed * u_emitter_speed[emitter]);
+    if (u_mask_kills != 0 && !inside_mask(next)) {
+        color = vec4(next, 0.0);
+        return;
+    }
+
+    color = vec4(next, age + 1.0);
+}
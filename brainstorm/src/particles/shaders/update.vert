@@ -0,0 +1,14 @@
+#version 300 es
+
+precision highp float;
+
+in vec2 v_texpos;
+
+out vec2 f_texpos;
+
+// One point per particle, covering one texel of the state texture.
+void main(void) {
+    f_texpos = v_texpos;
+    gl_Position = vec4(v_texpos * 2.0 - 1.0, 0.0, 1.0);
+    gl_PointSize = 1.0;
+}
@@ -0,0 +1,552 @@
+//! Static streamlines traced on the CPU from the seeding regions of the emitters, for figures
+//! where the ephemeral particles will not do.
+//!
+//! Each streamline is traced both downstream and upstream from its seed, along the direction
+//! of the field so that the step is a distance in world space. Tracing stops where the
+//! magnitude falls outside the high- and low-pass filters, where the line leaves the volume
+//! or the mask, and where it turns sharper than the largest turn allowed.
+//!
+//! In tractography mode the lines are fibers, tracked along the axis of the field whatever the
+//! sign of its vectors, since the eigenvectors of diffusion tensors have no orientation. Each
+//! step keeps to the orientation closest to the previous one, and fibers stop where the fourth
+//! channel, the fractional anisotropy, falls below a threshold instead of at the filters.
+
+use super::shaders::{STREAMLINE_FRAGMENT_SHADER, STREAMLINE_VERTEX_SHADER};
+use super::{
+    create_rng, fieldprovider::FieldProvider, seeding::Importance, ColorMode, Colormap, Emitter,
+    EmptyCells, Integrator, Interpolation, Mask, ScalarVolume,
+};
+use crate::graphics::{render_target, DrawMode, Drawable};
+use crate::State;
+use gl_bindings::{shaders::OurShader, shaders::ShaderAttribute, Buffer, BufferType};
+use na::Matrix4;
+use serde_derive::{Deserialize, Serialize};
+use std::{f32, rc::Rc, str};
+
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+
+/// Position, tangent of lines or normal of tubes, and color.
+const VERTEX_SIZE: usize = 9;
+/// Vertices around each point of a tube.
+const TUBE_SIDES: usize = 6;
+/// How many random positions in the seeding region to try before giving up on a mask.
+const MASK_SEED_ATTEMPTS: usize = 16;
+/// The fewest updates between two tracings while the inputs keep changing, such as while an
+/// emitter is moved.
+const TRACE_INTERVAL: u32 = 15;
+
+type Vector3 = (f32, f32, f32);
+
+/// How streamlines are traced and drawn.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct StreamlineSettings {
+    /// Streamlines traced from the seeding region of each emitter.
+    pub seeds: usize,
+    /// Distance between two points of a streamline, in world units.
+    pub step: f32,
+    /// The longest a streamline gets, in world units, half of it on each side of the seed.
+    pub max_length: f32,
+    /// The sharpest turn between two steps before a streamline ends, in degrees.
+    pub max_turn: f32,
+    /// Whether to draw tubes instead of lines.
+    pub tubes: bool,
+    /// Radius of the tubes, in world units.
+    pub tube_radius: f32,
+    /// The shortest line kept, in world units.
+    pub min_length: f32,
+    /// Whether to track fibers instead of following the field.
+    pub tractography: bool,
+    /// The lowest fourth channel, such as the fractional anisotropy, that fibers go through.
+    pub fa_threshold: f32,
+}
+
+impl Default for StreamlineSettings {
+    fn default() -> Self {
+        StreamlineSettings {
+            seeds: 100,
+            step: 0.005,
+            max_length: 0.5,
+            max_turn: 60.0,
+            tubes: false,
+            tube_radius: 0.003,
+            min_length: 0.0,
+            tractography: false,
+            fa_threshold: 0.2,
+        }
+    }
+}
+
+/// Everything that the streamlines depend on, to tell when to trace them again. The lines
+/// follow the frame of the field rather than the time, so that playback does not trace them
+/// again on every update.
+#[derive(Clone, PartialEq)]
+struct Inputs {
+    settings: StreamlineSettings,
+    emitters: Vec<Emitter>,
+    filters: (f32, f32),
+    integrator: Integrator,
+    tolerance: f32,
+    interpolation: Interpolation,
+    empty_cells: EmptyCells,
+    frame: usize,
+    mask_kills: bool,
+    coloring: (ColorMode, Colormap),
+    seed: Option<u64>,
+}
+
+impl Inputs {
+    fn new(state: &State, field: &FieldProvider) -> Self {
+        Inputs {
+            settings: state.streamlines.clone(),
+            emitters: state.emitters.clone(),
+            filters: (state.highpass_filter, state.lowpass_filter),
+            integrator: state.integrator,
+            tolerance: state.tolerance,
+            interpolation: state.interpolation,
+            empty_cells: state.empty_cells,
+            frame: field.frame(),
+            mask_kills: state.mask_kills,
+            coloring: (state.color_mode, state.colormap),
+            seed: state.seed,
+        }
+    }
+}
+
+/// A point of a streamline, with the magnitude of the field there, or the fourth channel
+/// for fibers.
+struct Point {
+    position: Vector3,
+    speed: f32,
+}
+
+/// Streamlines drawn as lit lines or tubes.
+pub struct Streamlines {
+    vertices: Buffer<f32>,
+    indices: Buffer<u32>,
+    shader: Rc<OurShader>,
+    tubes: bool,
+    /// The state the streamlines were traced for, if they are up to date.
+    inputs: Option<Inputs>,
+    /// The inputs of the last update, to tell when they stop changing.
+    last_inputs: Option<Inputs>,
+    /// Updates since the streamlines were last traced.
+    updates_since_trace: u32,
+}
+
+impl Drawable for Streamlines {
+    fn get_shader(&self) -> Option<Rc<OurShader>> {
+        Some(self.shader.clone())
+    }
+
+    fn draw_transformed(&self, view_matrix: &Matrix4<f32>) {
+        if self.indices.is_empty() {
+            return;
+        }
+        self.shader.uniform1i("u_tubes", self.tubes as i32);
+        let mode = if self.tubes {
+            DrawMode::TRIANGLES
+        } else {
+            DrawMode::LINES
+        };
+        render_target::draw_indices(
+            mode,
+            &self.vertices,
+            &self.indices,
+            &self.render_states(),
+            view_matrix,
+        );
+    }
+}
+
+impl Streamlines {
+    /// Creates the streamlines without any lines, to be traced by `update`.
+    pub fn new() -> Self {
+        let shader = OurShader::new(
+            str::from_utf8(STREAMLINE_VERTEX_SHADER).expect("Failed to read vertex shader"),
+            str::from_utf8(STREAMLINE_FRAGMENT_SHADER).expect("Failed to read fragment shader"),
+            &[
+                ShaderAttribute {
+                    name: "a_position".to_string(),
+                    size: 3,
+                },
+                ShaderAttribute {
+                    name: "a_normal".to_string(),
+                    size: 3,
+                },
+                ShaderAttribute {
+                    name: "a_color".to_string(),
+                    size: 3,
+                },
+            ],
+        );
+        Streamlines {
+            vertices: Buffer::new(BufferType::Array),
+            indices: Buffer::new(BufferType::IndexArray),
+            shader: Rc::new(shader),
+            tubes: false,
+            inputs: None,
+            last_inputs: None,
+            updates_since_trace: TRACE_INTERVAL,
+        }
+    }
+
+    /// Sets the direction of the light illuminating the streamlines.
+    pub fn set_light_dir(&self, (x, y, z): Vector3) {
+        let dist = (x * x + y * y + z * z).sqrt();
+        self.shader
+            .uniform3f("lightDir", x / dist, y / dist, z / dist);
+    }
+
+    /// Makes the next update trace the streamlines again, after the field, mask or overlay
+    /// changed.
+    pub fn invalidate(&mut self) {
+        self.inputs = None;
+    }
+
+    /// Traces the streamlines again if the settings, seeds or filters in `state` or the frame
+    /// of the field changed since they were last traced. While they keep changing, the lines
+    /// are traced at most every `TRACE_INTERVAL` updates, and once more when they settle.
+    /// The overlay colors them in the overlay color mode. Returns how many there are if they
+    /// were traced.
+    pub fn update(
+        &mut self,
+        field: &FieldProvider,
+        mask: Option<&Mask>,
+        overlay: Option<&ScalarVolume>,
+        state: &State,
+    ) -> Option<usize> {
+        let inputs = Inputs::new(state, field);
+        self.updates_since_trace = self.updates_since_trace.saturating_add(1);
+        if self.inputs.as_ref() == Some(&inputs) {
+            return None;
+        }
+        let settled = self.last_inputs.as_ref() == Some(&inputs);
+        self.last_inputs = Some(inputs.clone());
+        if !settled && self.updates_since_trace < TRACE_INTERVAL {
+            return None;
+        }
+        self.updates_since_trace = 0;
+        let tracer = Tracer {
+            field,
+            mask: mask.filter(|_| state.mask_kills),
+            overlay,
+            state,
+        };
+        let lines = tracer.trace_all(mask);
+
+        self.vertices.clear();
+        self.indices.clear();
+        self.tubes = state.streamlines.tubes;
+        for (emitter, line) in &lines {
+            let colors = tracer.colors(&state.emitters[*emitter], line);
+            if self.tubes {
+                self.push_tube(line, &colors, state.streamlines.tube_radius);
+            } else {
+                self.push_line(line, &colors);
+            }
+        }
+        self.vertices.bind();
+        let len = self.vertices.len();
+        self.vertices.upload_data(0, len, true);
+        self.indices.bind();
+        let len = self.indices.len();
+        self.indices.upload_data(0, len, true);
+
+        self.inputs = Some(inputs);
+        Some(lines.len())
+    }
+
+    /// Adds a streamline as line segments, with the tangent in place of the normal.
+    fn push_line(&mut self, line: &[Point], colors: &[Vector3]) {
+        let first = (self.vertices.len() / VERTEX_SIZE) as u32;
+        for (i, (point, color)) in line.iter().zip(colors).enumerate() {
+            push_vertex(&mut self.vertices, point.position, tangent(line, i), *color);
+        }
+        for i in 1..line.len() as u32 {
+            self.indices.push(&[first + i - 1, first + i]);
+        }
+    }
+
+    /// Adds a streamline as a tube, turning its cross section along with the line so that
+    /// the tube does not twist.
+    fn push_tube(&mut self, line: &[Point], colors: &[Vector3], radius: f32) {
+        let first = (self.vertices.len() / VERTEX_SIZE) as u32;
+        let mut normal = perpendicular(tangent(line, 0));
+        for (i, (point, color)) in line.iter().zip(colors).enumerate() {
+            let t = tangent(line, i);
+            let projected = sub(normal, scale(t, dot(normal, t)));
+            normal = if dot(projected, projected) > 0.0 {
+                normalize(projected)
+            } else {
+                perpendicular(t)
+            };
+            let binormal = cross(t, normal);
+            for side in 0..TUBE_SIDES {
+                let angle = side as f32 / TUBE_SIDES as f32 * 2.0 * f32::consts::PI;
+                let n = add(scale(normal, angle.cos()), scale(binormal, angle.sin()));
+                let position = add(point.position, scale(n, radius));
+                push_vertex(&mut self.vertices, position, n, *color);
+            }
+        }
+        let sides = TUBE_SIDES as u32;
+        for i in 1..line.len() as u32 {
+            let (previous, current) = (first + (i - 1) * sides, first + i * sides);
+            for side in 0..sides {
+                let next = (side + 1) % sides;
+                self.indices.push(&[
+                    previous + side,
+                    previous + next,
+                    current + side,
+                    current + side,
+                    previous + next,
+                    current + next,
+                ]);
+            }
+        }
+    }
+}
+
+/// What tracing the streamlines needs.
+struct Tracer<'a> {
+    field: &'a FieldProvider,
+    /// The mask ending streamlines that leave it, if they should.
+    mask: Option<&'a Mask>,
+    overlay: Option<&'a ScalarVolume>,
+    state: &'a State,
+}
+
+impl<'a> Tracer<'a> {
+    /// Traces the streamlines of every emitter, from seeds inside `mask` if there is one.
+    /// Returns each line with the index of its emitter.
+    fn trace_all(&self, mask: Option<&Mask>) -> Vec<(usize, Vec<Point>)> {
+        let field = self.field;
+        let transform = field.transform();
+        // The same seeds every time, so that the lines only change with their settings.
+        let mut rng = create_rng(Some(self.state.seed.unwrap_or(0)));
+        let mut importance: Vec<Importance> = Vec::new();
+        let mut seeds = Vec::new();
+        for (index, emitter) in self.state.emitters.iter().enumerate() {
+            let region = emitter.region();
+            if !importance.iter().any(|i| i.shape() == region.shape) {
+                importance.extend(Importance::new(field, region.shape));
+            }
+            let weights = importance.iter().find(|i| i.shape() == region.shape);
+            for _ in 0..self.state.streamlines.seeds {
+                let seed = (0..MASK_SEED_ATTEMPTS)
+                    .map(|_| region.sample(&mut rng, field, weights))
+                    .find(|&seed| mask.is_none_or(|mask| mask.contains(transform, seed)));
+                seeds.extend(seed.map(|seed| (index, seed)));
+            }
+        }
+        trace_seeds(self, seeds)
+    }
+
+    /// Traces a streamline through `seed`, or returns `None` if it is shorter than a step
+    /// or the shortest length kept.
+    fn trace(&self, seed: Vector3) -> Option<Vec<Point>> {
+        let speed = self.speed(seed)?;
+        let mut line = self.trace_from(seed, -1.0);
+        line.reverse();
+        line.push(Point {
+            position: seed,
+            speed,
+        });
+        line.extend(self.trace_from(seed, 1.0));
+        let length: f32 = line
+            .windows(2)
+            .map(|pair| {
+                let d = sub(pair[1].position, pair[0].position);
+                dot(d, d).sqrt()
+            })
+            .sum();
+        if line.len() < 2 || length < self.state.streamlines.min_length {
+            return None;
+        }
+        Some(line)
+    }
+
+    /// Follows the field from `seed`, downstream for a positive `sign` and upstream for a
+    /// negative one, until a termination rule applies. Leaves out the seed.
+    fn trace_from(&self, seed: Vector3, sign: f32) -> Vec<Point> {
+        let settings = &self.state.streamlines;
+        let min_cos = settings.max_turn.to_radians().cos();
+        let steps = (settings.max_length / 2.0 / settings.step) as usize;
+        let (dx, dy, dz, _) = self.field.delta(seed);
+        let mut heading = self.direction(seed, sign, scale((dx, dy, dz), sign));
+
+        let mut points = Vec::new();
+        let mut position = seed;
+        for _ in 0..steps {
+            let previous = heading;
+            let direction = |position| self.direction(position, sign, previous);
+            let next = self.state.integrator.step(
+                direction,
+                position,
+                settings.step,
+                self.state.tolerance,
+            );
+            let speed = match self.speed(next) {
+                Some(speed) => speed,
+                None => break,
+            };
+            heading = normalize(sub(next, position));
+            if dot(previous, heading) < min_cos {
+                break;
+            }
+            position = next;
+            points.push(Point { position, speed });
+        }
+        points
+    }
+
+    /// The direction to follow at `position`, as a unit vector so that each step covers the
+    /// same distance. Streamlines go downstream for a positive `sign` and upstream for a
+    /// negative one, while fibers follow the axis of the field in the orientation closest to
+    /// `heading`.
+    fn direction(&self, position: Vector3, sign: f32, heading: Vector3) -> Vector3 {
+        let v = if self.state.streamlines.tractography {
+            let (dx, dy, dz, _) = self.field.axis(position, heading);
+            (dx, dy, dz)
+        } else {
+            let (dx, dy, dz, fa) = self.field.delta(position);
+            scale((dx, dy, dz), fa * sign)
+        };
+        scale(v, 1.0 / dot(v, v).sqrt())
+    }
+
+    /// The magnitude of the field at `position`, or `None` if a streamline should not go
+    /// there: outside the volume or the mask, or outside the filters. For fibers, the fourth
+    /// channel, or `None` where it is below the threshold.
+    fn speed(&self, position: Vector3) -> Option<f32> {
+        let transform = self.field.transform();
+        let ((x1, y1, z1), (x2, y2, z2)) = transform.bounds();
+        let (x, y, z) = position;
+        if !(x >= x1 && x <= x2 && y >= y1 && y <= y2 && z >= z1 && z <= z2) {
+            return None;
+        }
+        if self
+            .mask
+            .is_some_and(|mask| !mask.contains(transform, position))
+        {
+            return None;
+        }
+        let (dx, dy, dz, fa) = self.field.delta(position);
+        if self.state.streamlines.tractography {
+            return Some(fa).filter(|&fa| fa > 0.0 && fa >= self.state.streamlines.fa_threshold);
+        }
+        let speed = (fa * dx, fa * dy, fa * dz);
+        let speed = dot(speed, speed).sqrt();
+        let max = self.field.max_velocity();
+        let (highpass, lowpass) = (self.state.highpass_filter, self.state.lowpass_filter);
+        if !(speed > 0.0 && speed >= max * highpass && speed <= max * lowpass) {
+            return None;
+        }
+        Some(speed)
+    }
+
+    /// The color of each point of a line of `emitter`, by the color mode. The age is the
+    /// distance along the line from its upstream end. Fibers are colored by their direction.
+    fn colors(&self, emitter: &Emitter, line: &[Point]) -> Vec<Vector3> {
+        let color_mode = if self.state.streamlines.tractography {
+            ColorMode::Direction
+        } else {
+            self.state.color_mode
+        };
+        let colormap = self.state.colormap;
+        let max = self.field.max_velocity();
+        let last = (line.len() - 1).max(1) as f32;
+        let transform = self.field.transform();
+        line.iter()
+            .enumerate()
+            .map(|(i, point)| match color_mode {
+                ColorMode::Direction => {
+                    let (x, y, z) = tangent(line, i);
+                    (x.abs(), y.abs(), z.abs())
+                }
+                ColorMode::Speed => colormap.sample(point.speed / max),
+                ColorMode::Age => colormap.sample(i as f32 / last),
+                ColorMode::Emitter => emitter.color,
+                ColorMode::Overlay => match self.overlay {
+                    Some(overlay) => colormap.sample(overlay.sample(transform, point.position)),
+                    None => (1.0, 1.0, 1.0),
+                },
+            })
+            .collect()
+    }
+}
+
+/// Traces the lines from their seeds in parallel on the thread pool of rayon, keeping each
+/// with the index of its emitter, in the order of the seeds.
+#[cfg(not(target_arch = "wasm32"))]
+fn trace_seeds(tracer: &Tracer, seeds: Vec<(usize, Vector3)>) -> Vec<(usize, Vec<Point>)> {
+    seeds
+        .into_par_iter()
+        .filter_map(|(index, seed)| tracer.trace(seed).map(|line| (index, line)))
+        .collect()
+}
+
+/// Traces the lines from their seeds one after another.
+#[cfg(target_arch = "wasm32")]
+fn trace_seeds(tracer: &Tracer, seeds: Vec<(usize, Vector3)>) -> Vec<(usize, Vec<Point>)> {
+    seeds
+        .into_iter()
+        .filter_map(|(index, seed)| tracer.trace(seed).map(|line| (index, line)))
+        .collect()
+}
+
+fn push_vertex(
+    vertices: &mut Buffer<f32>,
+    (x, y, z): Vector3,
+    (nx, ny, nz): Vector3,
+    (r, g, b): Vector3,
+) {
+    vertices.push(&[x, y, z, nx, ny, nz, r, g, b]);
+}
+
+/// The direction of a line at the point `i`, from its neighbors.
+fn tangent(line: &[Point], i: usize) -> Vector3 {
+    let before = line[i.saturating_sub(1)].position;
+    let after = line[(i + 1).min(line.len() - 1)].position;
+    normalize(sub(after, before))
+}
+
+/// A unit vector perpendicular to the unit vector `v`.
+fn perpendicular(v: Vector3) -> Vector3 {
+    let axis = if v.0.abs() < 0.9 {
+        (1.0, 0.0, 0.0)
+    } else {
+        (0.0, 1.0, 0.0)
+    };
+    normalize(cross(v, axis))
+}
+
+fn add((ax, ay, az): Vector3, (bx, by, bz): Vector3) -> Vector3 {
+    (ax + bx, ay + by, az + bz)
+}
+
+fn sub((ax, ay, az): Vector3, (bx, by, bz): Vector3) -> Vector3 {
+    (ax - bx, ay - by, az - bz)
+}
+
+fn scale((x, y, z): Vector3, s: f32) -> Vector3 {
+    (x * s, y * s, z * s)
+}
+
+fn dot((ax, ay, az): Vector3, (bx, by, bz): Vector3) -> f32 {
+    ax * bx + ay * by + az * bz
+}
+
+fn cross((ax, ay, az): Vector3, (bx, by, bz): Vector3) -> Vector3 {
+    (ay * bz - az * by, az * bx - ax * bz, ax * by - ay * bx)
+}
+
+fn normalize(v: Vector3) -> Vector3 {
+    let length = dot(v, v).sqrt();
+    if length > 0.0 {
+        scale(v, 1.0 / length)
+    } else {
+        v
+    }
+}
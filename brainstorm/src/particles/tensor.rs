@@ -0,0 +1,94 @@
+//! Conversion of diffusion tensors to a principal direction and fractional anisotropy.
+
+use super::Vector4;
+
+/// A symmetric 3x3 tensor stored as its upper triangle: `xx, xy, xz, yy, yz, zz`.
+pub type SymmetricTensor = [f32; 6];
+
+/// Maximum number of Jacobi sweeps before giving up on further precision.
+const MAX_SWEEPS: usize = 32;
+
+/// Returns the principal eigenvector of the tensor as the first three components and the
+/// fractional anisotropy as the fourth.
+pub fn principal_direction(tensor: &SymmetricTensor) -> Vector4 {
+    let (values, vectors) = eigen_decomposition(tensor);
+
+    let mut principal = 0;
+    for i in 1..3 {
+        if values[i] > values[principal] {
+            principal = i;
+        }
+    }
+
+    let fa = fractional_anisotropy(values);
+    if !fa.is_finite() {
+        return (0.0, 0.0, 0.0, 0.0);
+    }
+    (
+        vectors[0][principal] as f32,
+        vectors[1][principal] as f32,
+        vectors[2][principal] as f32,
+        fa as f32,
+    )
+}
+
+/// Returns the eigenvalues of the tensor, in no particular order.
+pub fn eigenvalues(tensor: &SymmetricTensor) -> [f64; 3] {
+    eigen_decomposition(tensor).0
+}
+
+/// Computes the fractional anisotropy from the eigenvalues of a tensor.
+fn fractional_anisotropy([l1, l2, l3]: [f64; 3]) -> f64 {
+    let norm = (l1 * l1 + l2 * l2 + l3 * l3).sqrt();
+    if norm == 0.0 {
+        return 0.0;
+    }
+    let diff = ((l1 - l2).powi(2) + (l2 - l3).powi(2) + (l3 - l1).powi(2)).sqrt();
+    ((0.5f64).sqrt() * diff / norm).min(1.0)
+}
+
+/// Cyclic Jacobi eigenvalue algorithm for symmetric 3x3 matrices.
+/// Returns the eigenvalues and a matrix with the corresponding eigenvectors as columns.
+fn eigen_decomposition(t: &SymmetricTensor) -> ([f64; 3], [[f64; 3]; 3]) {
+    let (xx, xy, xz) = (f64::from(t[0]), f64::from(t[1]), f64::from(t[2]));
+    let (yy, yz, zz) = (f64::from(t[3]), f64::from(t[4]), f64::from(t[5]));
+    let mut a = [[xx, xy, xz], [xy, yy, yz], [xz, yz, zz]];
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+    for _ in 0..MAX_SWEEPS {
+        let off = a[0][1].abs() + a[0][2].abs() + a[1][2].abs();
+        if off < 1e-15 {
+            break;
+        }
+        for &(p, q) in &[(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0.0 {
+                continue;
+            }
+            // Compute the rotation that zeroes out a[p][q].
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+
+            for row in a.iter_mut() {
+                let akp = row[p];
+                let akq = row[q];
+                row[p] = c * akp - s * akq;
+                row[q] = s * akp + c * akq;
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            for (k, (&apk, &aqk)) in row_p.iter().zip(row_q.iter()).enumerate() {
+                a[p][k] = c * apk - s * aqk;
+                a[q][k] = s * apk + c * aqk;
+            }
+            for row in v.iter_mut() {
+                let vp = row[p];
+                let vq = row[q];
+                row[p] = c * vp - s * vq;
+                row[q] = s * vp + c * vq;
+            }
+        }
+    }
+
+    ([a[0][0], a[1][1], a[2][2]], v)
+}
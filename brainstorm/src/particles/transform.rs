@@ -0,0 +1,182 @@
+//! Mappings between voxel indices, texture coordinates and world space.
+
+use na::{Matrix4, Vector3, Vector4};
+use serde_derive::{Deserialize, Serialize};
+use std::f32;
+
+type Vector3f = (f32, f32, f32);
+
+/// The mapping from voxel indices to physical coordinates,
+/// as given by NRRD `space directions` and `space origin`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Affine {
+    /// Physical position of the first voxel.
+    pub origin: Vector3f,
+    /// Physical step taken along each voxel axis. The lengths are the voxel spacings.
+    pub directions: [Vector3f; 3],
+}
+
+impl Affine {
+    /// Creates an axis-aligned mapping with the given voxel spacing.
+    pub fn from_spacing((sx, sy, sz): Vector3f) -> Self {
+        Affine {
+            origin: (0.0, 0.0, 0.0),
+            directions: [(sx, 0.0, 0.0), (0.0, sy, 0.0), (0.0, 0.0, sz)],
+        }
+    }
+
+    /// The distance between voxel centers along each axis.
+    pub fn spacing(&self) -> Vector3f {
+        let len = |(x, y, z): Vector3f| (x * x + y * y + z * z).sqrt();
+        (
+            len(self.directions[0]),
+            len(self.directions[1]),
+            len(self.directions[2]),
+        )
+    }
+
+    fn to_matrix(&self) -> Matrix4<f32> {
+        let [(ax, ay, az), (bx, by, bz), (cx, cy, cz)] = self.directions;
+        let (ox, oy, oz) = self.origin;
+        #[rustfmt::skip]
+        let matrix = Matrix4::new(
+            ax, bx, cx, ox,
+            ay, by, cy, oy,
+            az, bz, cz, oz,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        matrix
+    }
+}
+
+impl Default for Affine {
+    fn default() -> Self {
+        Affine::from_spacing((1.0, 1.0, 1.0))
+    }
+}
+
+/// Maps between voxel indices, texture coordinates and the world space of the scene.
+/// World space is the physical space of the volume, centered on the origin and scaled so that
+/// the largest side of its bounding box has length 1. Voxels lie at their integer indices,
+/// which are the centers of their texels, so the volume spans the indices [0, size - 1].
+#[derive(Clone, Debug)]
+pub struct WorldTransform {
+    voxel_to_world: Matrix4<f32>,
+    world_to_voxel: Matrix4<f32>,
+    dimensions: Vector3f,
+    min: Vector3f,
+    max: Vector3f,
+}
+
+impl WorldTransform {
+    /// Creates the transform for a volume of the given dimensions.
+    pub fn new(affine: &Affine, (width, height, depth): (usize, usize, usize)) -> Self {
+        let voxel_to_physical = affine.to_matrix();
+        let dimensions = (width as f32, height as f32, depth as f32);
+
+        // Find the physical bounding box of the volume.
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for &x in &[0.0, dimensions.0 - 1.0] {
+            for &y in &[0.0, dimensions.1 - 1.0] {
+                for &z in &[0.0, dimensions.2 - 1.0] {
+                    let corner = voxel_to_physical * Vector4::new(x, y, z, 1.0);
+                    for i in 0..3 {
+                        min[i] = min[i].min(corner[i]);
+                        max[i] = max[i].max(corner[i]);
+                    }
+                }
+            }
+        }
+
+        let extent = Vector3::new(max[0] - min[0], max[1] - min[1], max[2] - min[2]);
+        let center = Vector3::new(max[0] + min[0], max[1] + min[1], max[2] + min[2]) / 2.0;
+        let largest = extent.x.max(extent.y).max(extent.z);
+        let scale = if largest > 0.0 { 1.0 / largest } else { 1.0 };
+        let physical_to_world = Matrix4::new_scaling(scale) * Matrix4::new_translation(&(-center));
+
+        let voxel_to_world = physical_to_world * voxel_to_physical;
+        let world_to_voxel = voxel_to_world
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity);
+        let half = extent * scale / 2.0;
+
+        WorldTransform {
+            voxel_to_world,
+            world_to_voxel,
+            dimensions,
+            min: (-half.x, -half.y, -half.z),
+            max: (half.x, half.y, half.z),
+        }
+    }
+
+    /// Converts a world position to (fractional) voxel indices.
+    pub fn to_voxel(&self, p: Vector3f) -> Vector3f {
+        transform_point(&self.world_to_voxel, p)
+    }
+
+    /// Converts (fractional) voxel indices to a world position.
+    pub fn to_world(&self, p: Vector3f) -> Vector3f {
+        transform_point(&self.voxel_to_world, p)
+    }
+
+    /// Converts a world position to texture coordinates in the range [0, 1].
+    pub fn to_texture(&self, p: Vector3f) -> Vector3f {
+        let (x, y, z) = self.to_voxel(p);
+        let (w, h, d) = self.dimensions;
+        ((x + 0.5) / w, (y + 0.5) / h, (z + 0.5) / d)
+    }
+
+    /// Converts texture coordinates to a world position.
+    pub fn texture_to_world(&self, (x, y, z): Vector3f) -> Vector3f {
+        let (w, h, d) = self.dimensions;
+        self.to_world((x * w - 0.5, y * h - 0.5, z * d - 0.5))
+    }
+
+    /// The matrix mapping world positions to texture coordinates.
+    pub fn world_to_texture(&self) -> Matrix4<f32> {
+        let (w, h, d) = self.dimensions;
+        Matrix4::new_nonuniform_scaling(&Vector3::new(1.0 / w, 1.0 / h, 1.0 / d))
+            * Matrix4::new_translation(&Vector3::new(0.5, 0.5, 0.5))
+            * self.world_to_voxel
+    }
+
+    /// The derivatives of the voxel indices with respect to world space, where row `i`
+    /// holds the change of voxel index `i` along each world axis.
+    pub fn world_to_voxel_derivatives(&self) -> [[f32; 3]; 3] {
+        let m = &self.world_to_voxel;
+        let mut derivatives = [[0.0; 3]; 3];
+        for (i, row) in derivatives.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = m[(i, j)];
+            }
+        }
+        derivatives
+    }
+
+    /// The axis-aligned bounding box of the volume in world space, as (min, max).
+    pub fn bounds(&self) -> (Vector3f, Vector3f) {
+        (self.min, self.max)
+    }
+
+    /// The world-space length of one texture unit along each axis, averaged.
+    pub fn texture_scale(&self) -> f32 {
+        let (w, h, d) = self.dimensions;
+        let m = &self.voxel_to_world;
+        let len = |c: usize, n: f32| {
+            (m[(0, c)] * m[(0, c)] + m[(1, c)] * m[(1, c)] + m[(2, c)] * m[(2, c)]).sqrt() * n
+        };
+        (len(0, w) + len(1, h) + len(2, d)) / 3.0
+    }
+}
+
+impl Default for WorldTransform {
+    fn default() -> Self {
+        WorldTransform::new(&Affine::default(), (1, 1, 1))
+    }
+}
+
+fn transform_point(m: &Matrix4<f32>, (x, y, z): Vector3f) -> Vector3f {
+    let p = m * Vector4::new(x, y, z, 1.0);
+    (p.x, p.y, p.z)
+}
@@ -0,0 +1,44 @@
+//! Sessions hold everything needed to reproduce a view: the application state including the
+//! field file path, the camera and the settings of the GUI elements.
+//!
+//! They are stored as JSON, with the GUI settings under the names of their elements, so that
+//! sessions saved before a setting or element was added still load, with its default.
+
+use crate::{camera::CameraPose, file_loading::LoadError, gui::Setting, State};
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+
+#[derive(Deserialize, Serialize)]
+pub struct Session {
+    #[serde(default)]
+    pub state: State,
+    pub camera: CameraPose,
+    /// Settings of the GUI elements, by the names given by `Gui::settings`.
+    #[serde(default)]
+    pub gui: BTreeMap<String, Setting>,
+}
+
+impl Session {
+    pub fn read(path: &Path) -> Result<Self, LoadError> {
+        let data = std::fs::read(path).map_err(|error| LoadError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+        serde_json::from_slice(&data).map_err(|e| {
+            LoadError::Format(format!(
+                "{} is not a valid session file: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), LoadError> {
+        let out = serde_json::to_vec_pretty(self)
+            .map_err(|e| LoadError::Format(format!("Failed to write session: {}", e)))?;
+        std::fs::write(path, out).map_err(|error| LoadError::Io {
+            path: path.to_owned(),
+            error,
+        })
+    }
+}
@@ -1,25 +1,101 @@
+use crate::particles::{
+    ColorMode, Colormap, Emitter, EmptyCells, Integrator, Interpolation, LandmarkSettings,
+    Quantity, StreamlineSettings, DEFAULT_PARTICLE_COUNT, EMITTER_COLORS, MAX_EMITTERS,
+};
+use serde_derive::{Deserialize, Serialize};
+
 /// Holds application state.
+/// Transient fields are left out of saved sessions and take their default values when loaded.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub struct State {
+    #[serde(skip)]
     pub mouse_x: f64,
+    #[serde(skip)]
     pub mouse_y: f64,
+    #[serde(skip)]
     pub is_running: bool,
     pub highpass_filter: f32,
     pub lowpass_filter: f32,
+    /// Overall speed of the particles and playback, which emitters scale for their particles.
     pub speed_multiplier: f32,
-    pub seeding_size: f32,
-    pub lifetime: f32,
+    /// Where particles spawn and how they behave. There is always at least one emitter.
+    pub emitters: Vec<Emitter>,
+    /// The emitter edited in the GUI, which follows the camera target.
+    pub selected_emitter: usize,
     pub mesh_transparency: f32,
     pub particle_size: f32,
-    pub particle_respawn_per_tick: u32,
+    /// Number of CPU particles, shared evenly by the emitters.
+    pub particle_count: usize,
     pub file_path: Option<std::path::PathBuf>,
+    #[serde(skip)]
     pub reload_file: bool,
     pub camera_target: (f32, f32, f32),
+    #[serde(skip)]
     pub window_w: f32,
+    #[serde(skip)]
     pub window_h: f32,
     pub use_cpu_particles: bool,
+    #[serde(skip)]
     pub directional_data: Vec<(f32, f32, f32)>,
-    pub options_file: Option<reparser::Options>,
+    #[serde(skip)]
+    pub options_file: Option<crate::file_loading::NrrdHeader>,
     pub particle_transparency: f32,
+    /// What the color of the particles shows.
+    pub color_mode: ColorMode,
+    /// The colormap of the color modes showing a scalar.
+    pub colormap: Colormap,
+    /// Current time in a time-varying field, in frames.
+    pub time: f32,
+    pub frame_count: usize,
+    /// Time between two frames, in the same unit as the particle advection.
+    pub time_step: f32,
+    pub playing: bool,
+    pub loop_playback: bool,
+    /// How the CPU particles reconstruct the field between voxels.
+    pub interpolation: Interpolation,
+    /// What the CPU particles do in cells where every corner is zero.
+    pub empty_cells: EmptyCells,
+    /// How the CPU particles are advanced along the field.
+    pub integrator: Integrator,
+    /// Largest error per substep of the adaptive integrator, in world units.
+    pub tolerance: f32,
+    /// Label volume restricting where particles spawn, using the voxel grid of the field.
+    pub mask_file: Option<std::path::PathBuf>,
+    #[serde(skip)]
+    pub reload_mask: bool,
+    /// Whether particles die when they leave the mask.
+    pub mask_kills: bool,
+    /// Scalar volume coloring the particles, mesh and map, using the voxel grid of the field.
+    pub overlay_file: Option<std::path::PathBuf>,
+    #[serde(skip)]
+    pub reload_overlay: bool,
+    /// Quantity derived from the field that colors the particles, mesh and map instead of
+    /// the overlay.
+    pub derived: Option<Quantity>,
+    /// Whether the mesh shows an isosurface of the derived quantity.
+    pub derived_surface: bool,
+    /// Level of the isosurface, as a fraction of the value range of the derived quantity.
+    pub derived_level: f32,
+    /// Whether the critical points of the field are shown among the world points.
+    pub show_critical_points: bool,
+    /// How the landmarks shown as world points are found.
+    pub landmarks: LandmarkSettings,
+    /// Whether static streamlines are traced from the seeding regions and shown.
+    pub show_streamlines: bool,
+    /// How the streamlines are traced and drawn.
+    pub streamlines: StreamlineSettings,
+    /// Seed of the random choices of the particle engines, for reproducible runs.
+    pub seed: Option<u64>,
+    /// Whether to restart the particle engines from the seed.
+    #[serde(skip)]
+    pub reseed: bool,
+    /// Where to save the session, if requested.
+    #[serde(skip)]
+    pub save_session: Option<std::path::PathBuf>,
+    /// Which session to load, if requested.
+    #[serde(skip)]
+    pub load_session: Option<std::path::PathBuf>,
 }
 
 impl State {
@@ -32,11 +108,11 @@ impl State {
             highpass_filter: 0.0,
             lowpass_filter: 1.0,
             speed_multiplier: 0.5,
-            seeding_size: 1.0,
-            lifetime: 100.0,
+            emitters: vec![Emitter::default()],
+            selected_emitter: 0,
             mesh_transparency: 0.02,
             particle_size: 8.0,
-            particle_respawn_per_tick: 1000,
+            particle_count: DEFAULT_PARTICLE_COUNT,
             file_path: None,
             reload_file: false,
             camera_target: (0.0, 0.0, 0.0),
@@ -46,7 +122,135 @@ impl State {
             directional_data: Vec::new(),
             options_file: None,
             particle_transparency: 0.2,
+            color_mode: ColorMode::default(),
+            colormap: Colormap::default(),
+            time: 0.0,
+            frame_count: 1,
+            time_step: 1.0,
+            playing: false,
+            loop_playback: true,
+            interpolation: Interpolation::default(),
+            empty_cells: EmptyCells::default(),
+            integrator: Integrator::default(),
+            tolerance: 1e-5,
+            mask_file: None,
+            reload_mask: false,
+            mask_kills: false,
+            overlay_file: None,
+            reload_overlay: false,
+            derived: None,
+            derived_surface: false,
+            derived_level: 0.5,
+            show_critical_points: false,
+            landmarks: LandmarkSettings::default(),
+            show_streamlines: false,
+            streamlines: StreamlineSettings::default(),
+            seed: None,
+            reseed: false,
+            save_session: None,
+            load_session: None,
+        }
+    }
+
+    /// The emitter edited in the GUI.
+    pub fn emitter(&self) -> &Emitter {
+        &self.emitters[self.selected_emitter]
+    }
+
+    pub fn emitter_mut(&mut self) -> &mut Emitter {
+        &mut self.emitters[self.selected_emitter]
+    }
+
+    /// Adds a copy of the selected emitter with the first color no other emitter has, and
+    /// selects it. Does nothing if there are already `MAX_EMITTERS`.
+    pub fn add_emitter(&mut self) {
+        if self.emitters.len() >= MAX_EMITTERS {
+            return;
+        }
+        let unused = |color: &&(f32, f32, f32)| self.emitters.iter().all(|e| e.color != **color);
+        let color = EMITTER_COLORS
+            .iter()
+            .find(unused)
+            .unwrap_or(&EMITTER_COLORS[0]);
+        let mut emitter = self.emitter().clone();
+        emitter.color = *color;
+        self.emitters.push(emitter);
+        self.selected_emitter = self.emitters.len() - 1;
+    }
+
+    /// Removes the selected emitter and selects the one before it, unless it is the last one.
+    pub fn remove_emitter(&mut self) {
+        if self.emitters.len() < 2 {
+            return;
         }
+        self.emitters.remove(self.selected_emitter);
+        self.select_emitter(self.selected_emitter.saturating_sub(1));
+    }
+
+    /// Selects the next emitter, starting over after the last one.
+    pub fn select_next_emitter(&mut self) {
+        self.select_emitter((self.selected_emitter + 1) % self.emitters.len());
+    }
+
+    /// Makes the emitters of a state read from a session usable: keeps the first
+    /// `MAX_EMITTERS`, selects the last one if the selected one does not exist, and corrects
+    /// sizes outside [0, 1] and lifetimes and speeds that are not finite. Returns what was
+    /// changed, or an error if there are no emitters at all.
+    pub fn fix_emitters(&mut self) -> Result<Vec<String>, String> {
+        if self.emitters.is_empty() {
+            return Err("The session has no emitters.".to_owned());
+        }
+        let mut changes = Vec::new();
+        if self.emitters.len() > MAX_EMITTERS {
+            changes.push(format!(
+                "kept the first {} of {} emitters",
+                MAX_EMITTERS,
+                self.emitters.len()
+            ));
+            self.emitters.truncate(MAX_EMITTERS);
+        }
+        if self.selected_emitter >= self.emitters.len() {
+            changes.push(format!(
+                "selected emitter {} instead of {}",
+                self.emitters.len(),
+                self.selected_emitter + 1
+            ));
+            self.selected_emitter = self.emitters.len() - 1;
+        }
+        let defaults = Emitter::default();
+        for (i, emitter) in self.emitters.iter_mut().enumerate() {
+            let mut fixed = Vec::new();
+            if !(0.0..=1.0).contains(&emitter.size) {
+                emitter.size = if emitter.size.is_nan() {
+                    defaults.size
+                } else {
+                    emitter.size.clamp(0.0, 1.0)
+                };
+                fixed.push("size");
+            }
+            if !emitter.lifetime.is_finite() {
+                emitter.lifetime = defaults.lifetime;
+                fixed.push("lifetime");
+            }
+            if !emitter.speed.is_finite() {
+                emitter.speed = defaults.speed;
+                fixed.push("speed");
+            }
+            if !fixed.is_empty() {
+                changes.push(format!(
+                    "corrected the {} of emitter {}",
+                    fixed.join(" and "),
+                    i + 1
+                ));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Selects an emitter, moving the camera target to it so that it stays in place.
+    fn select_emitter(&mut self, index: usize) {
+        self.selected_emitter = index;
+        self.camera_target = self.emitter().position;
     }
 }
 
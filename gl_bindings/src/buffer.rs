@@ -76,6 +76,11 @@ impl<T: Clone + GlPrimitive> Buffer<T> {
         self.data.get(index)
     }
 
+    /// Gets the data of the buffer as a slice, for writing many elements at once.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
     pub fn bind_buffer_base(&self) {
         let context = Context::get_context();
         context.bind_buffer_base(Context::TRANSFORM_FEEDBACK_BUFFER, 0, Some(&self.buffer));
@@ -59,6 +59,7 @@ pub trait AbstractContext {
     const UNSIGNED_BYTE: u32;
     const RGBA: u32;
     const RGBA32F: u32;
+    const RGBA16F: u32;
     const RGBA8: u32;
     const LUMINANCE: u32;
     const TEXTURE0: u32;
@@ -247,6 +248,9 @@ pub trait AbstractContext {
     fn end_transform_feedback(&self);
 
     fn blend_func(&self, s_factor: GLEnum, d_factor: GLEnum);
+    /// The internal format for float textures that can be filtered linearly, half floats
+    /// to save memory, or `None` if float textures are not supported.
+    fn float_texture_format(&self) -> Option<u32>;
 }
 
 impl GlPrimitive for f32 {
@@ -102,6 +102,7 @@ impl AbstractContext for GLContext {
     const UNSIGNED_BYTE: u32 = gl::UNSIGNED_BYTE;
     const RGBA: u32 = gl::RGBA;
     const RGBA32F: u32 = gl::RGBA32F;
+    const RGBA16F: u32 = gl::RGBA16F;
     const RGBA8: u32 = gl::RGBA8;
     const LUMINANCE: u32 = gl::RED;
     const TEXTURE0: u32 = gl::TEXTURE0;
@@ -790,4 +791,18 @@ impl AbstractContext for GLContext {
             gl::BlendFunc(s_factor, d_factor);
         }
     }
+
+    fn float_texture_format(&self) -> Option<u32> {
+        // Float textures, including linear filtering of them, are core since OpenGL 3.0. Half
+        // floats take half the memory of full floats for the same filtering.
+        let mut major = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        }
+        if major >= 3 {
+            Some(Self::RGBA16F)
+        } else {
+            None
+        }
+    }
 }
@@ -112,6 +112,46 @@ impl Texture {
         texture
     }
 
+    /// Creates a linearly filtered 3D texture from float data, stored as half floats.
+    /// Returns `None` if the context does not support float textures.
+    pub fn from_3d_data_linear_f(
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: TextureFormat,
+        data: &[f32],
+    ) -> Option<Self> {
+        let context = Context::get_context();
+        let internal_format = context.float_texture_format()?;
+
+        let texture = context.create_texture().unwrap();
+        context.bind_texture(Context::TEXTURE_3D, &texture);
+
+        let formatv: u32 = format.into();
+
+        let filter = Context::LINEAR as i32;
+        context.tex_parameteri(Context::TEXTURE_3D, Context::TEXTURE_MIN_FILTER, filter);
+        context.tex_parameteri(Context::TEXTURE_3D, Context::TEXTURE_MAG_FILTER, filter);
+
+        context.tex_image3d_f(
+            Context::TEXTURE_3D,
+            0,
+            internal_format as i32,
+            width as i32,
+            height as i32,
+            depth as i32,
+            0,
+            formatv,
+            Some(data),
+        );
+
+        Some(Texture {
+            texture,
+            _format: format,
+            _type: Context::TEXTURE_3D,
+        })
+    }
+
     pub fn from_data(width: u32, height: u32, format: TextureFormat, data: &[f32]) -> Self {
         let context = Context::get_context();
 
@@ -97,6 +97,7 @@ impl AbstractContext for WebGLContext {
     const UNSIGNED_BYTE: u32 = WebGL2RenderingContext::UNSIGNED_BYTE;
     const RGBA: u32 = WebGL2RenderingContext::RGBA;
     const RGBA32F: u32 = WebGL2RenderingContext::RGBA32F;
+    const RGBA16F: u32 = WebGL2RenderingContext::RGBA16F;
     const RGBA8: u32 = WebGL2RenderingContext::RGBA8;
     const LUMINANCE: u32 = WebGL2RenderingContext::LUMINANCE;
     const TEXTURE0: u32 = WebGL2RenderingContext::TEXTURE0;
@@ -625,4 +626,9 @@ impl AbstractContext for WebGLContext {
     fn blend_func(&self, s_factor: GLEnum, d_factor: GLEnum) {
         self.context.blend_func(s_factor, d_factor);
     }
+
+    fn float_texture_format(&self) -> Option<u32> {
+        // Half floats can always be filtered in WebGL 2, full floats only with an extension.
+        Some(Self::RGBA16F)
+    }
 }